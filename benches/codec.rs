@@ -0,0 +1,78 @@
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tokio_util::codec::{Decoder, Encoder};
+
+use minecraft_protocol::{codec::MinecraftCodec, stream::Packet};
+
+/// Roughly the size of a Chunk Data and Update Light packet for a simple chunk
+const CHUNK_PACKET_SIZE: usize = 40_000;
+const SHARED_SECRET: [u8; 16] = [42; 16];
+
+fn packet(size: usize) -> Packet {
+    Packet {
+        id: 0x24,
+        data: (0..size).map(|i| (i % 7) as u8).collect::<Vec<u8>>().into(),
+    }
+}
+
+fn codec(encrypted: bool, compression: Option<usize>) -> MinecraftCodec {
+    let mut codec = MinecraftCodec::new();
+    if encrypted {
+        codec.enable_encryption(&SHARED_SECRET);
+    }
+    codec.set_compression(compression);
+    codec
+}
+
+fn encoded(size: usize, encrypted: bool, compression: Option<usize>) -> BytesMut {
+    let mut buffer = BytesMut::new();
+    codec(encrypted, compression)
+        .encode(packet(size), &mut buffer)
+        .unwrap();
+    buffer
+}
+
+fn bench_codec(c: &mut Criterion) {
+    let cases = [
+        ("plain", false, None),
+        ("encrypted", true, None),
+        ("encrypted+compressed", true, Some(256)),
+    ];
+
+    for (size_name, size) in [("small", 32), ("chunk", CHUNK_PACKET_SIZE)] {
+        let mut group = c.benchmark_group(format!("encode/{size_name}"));
+        group.throughput(Throughput::Bytes(size as u64));
+        for (name, encrypted, compression) in cases {
+            group.bench_function(name, |b| {
+                let mut codec = codec(encrypted, compression);
+                let mut buffer = BytesMut::with_capacity(size * 2);
+                b.iter_batched(
+                    || packet(size),
+                    |packet| {
+                        codec.encode(packet, &mut buffer).unwrap();
+                        buffer.clear();
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("decode/{size_name}"));
+        group.throughput(Throughput::Bytes(size as u64));
+        for (name, encrypted, compression) in cases {
+            let frame = encoded(size, encrypted, compression);
+            group.bench_function(name, |b| {
+                b.iter_batched(
+                    || (codec(encrypted, compression), frame.clone()),
+                    |(mut codec, mut buffer)| codec.decode(&mut buffer).unwrap().unwrap(),
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_codec);
+criterion_main!(benches);
@@ -0,0 +1,314 @@
+//! Generates the block, registry and biome tables of `src/blocks.rs`, `src/registries.rs` and
+//! `src/protocol/play/registry_codec.rs` from the output of the vanilla data generator, which
+//! `java -DbundlerMainClass=net.minecraft.data.Main -jar server.jar --all` writes to `generated`
+//! for a 1.19.4 server jar. The build reads that output from `data`, or from the directory in
+//! `MC_GENERATED_DIR`.
+//!
+//! The build uses `reports/blocks.json`, `reports/registries.json` and the biomes of
+//! `data/minecraft/worldgen/biome`, and fails if any of them is missing or the reports are not
+//! the full output, since a partial block list gives blocks the wrong state IDs.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+/// Registries given their own constant, which the reports must have
+const NAMED_REGISTRIES: [(&str, &str); 5] = [
+    ("ITEMS", "minecraft:item"),
+    ("ENTITY_TYPES", "minecraft:entity_type"),
+    ("SOUND_EVENTS", "minecraft:sound_event"),
+    ("BLOCK_ENTITY_TYPES", "minecraft:block_entity_type"),
+    ("MENUS", "minecraft:menu"),
+];
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-env-changed=MC_GENERATED_DIR");
+    let generated = env::var_os("MC_GENERATED_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir.join("data"));
+    println!("cargo:rerun-if-changed={}", generated.display());
+
+    let reports = generated.join("reports");
+    let blocks = read_json(&reports.join("blocks.json"));
+    let registries = read_json(&reports.join("registries.json"));
+    check_reports(&blocks, &registries);
+    fs::write(out_dir.join("blocks.rs"), generate_blocks(&blocks)).unwrap();
+    fs::write(
+        out_dir.join("registries.rs"),
+        generate_registries(&registries),
+    )
+    .unwrap();
+    let biomes = generated.join("data/minecraft/worldgen/biome");
+    fs::write(out_dir.join("biomes.rs"), generate_biomes(&biomes)).unwrap();
+}
+
+fn read_json(path: &Path) -> Map<String, Value> {
+    let json = fs::read_to_string(path).unwrap_or_else(|error| {
+        panic!(
+            "Failed to read {}, which the data generator writes: {error}. Copy its output \
+             to `data` or set MC_GENERATED_DIR to it.",
+            path.display()
+        )
+    });
+    match serde_json::from_str(&json) {
+        Ok(Value::Object(object)) => object,
+        _ => panic!("{} is not a JSON object", path.display()),
+    }
+}
+
+/// Panic unless the reports are the full output of the data generator: every registry the crate
+/// names is there, every block of the block registry is in the block report, and the state IDs
+/// have no gaps
+fn check_reports(blocks: &Map<String, Value>, registries: &Map<String, Value>) {
+    let entries = |name: &str| {
+        registries
+            .get(name)
+            .and_then(|registry| registry["entries"].as_object())
+            .filter(|entries| !entries.is_empty())
+            .unwrap_or_else(|| {
+                panic!("registries.json has no {name} entries, it is not the full report")
+            })
+    };
+    for (_, name) in NAMED_REGISTRIES {
+        entries(name);
+    }
+
+    let block_registry = entries("minecraft:block");
+    for name in block_registry.keys() {
+        assert!(
+            blocks.contains_key(name),
+            "blocks.json has no {name}, it is not the full report"
+        );
+    }
+    assert_eq!(
+        blocks.len(),
+        block_registry.len(),
+        "blocks.json and the block registry have different blocks"
+    );
+
+    let mut ids = blocks
+        .values()
+        .flat_map(|block| block["states"].as_array().unwrap())
+        .map(|state| state["id"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    assert!(
+        ids.iter().copied().eq(0..ids.len() as u64),
+        "State IDs in blocks.json have gaps, it is not the full report"
+    );
+}
+
+struct Block {
+    name: String,
+    /// Names and values, with the names sorted like vanilla sorts them. The last property
+    /// changes fastest between consecutive state IDs.
+    properties: Vec<(String, Vec<String>)>,
+    first_state: u64,
+    default_state: u64,
+}
+
+/// Read a block, checking that its state IDs are the ones [`Block`] can compute
+fn read_block(name: &str, block: &Value) -> Block {
+    let properties = block["properties"]
+        .as_object()
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, values)| {
+                    let values: Vec<String> = values
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|value| value.as_str().unwrap().to_string())
+                        .collect();
+                    (name.clone(), values)
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let states = block["states"].as_array().unwrap();
+    let first_state = states
+        .iter()
+        .map(|state| state["id"].as_u64().unwrap())
+        .min()
+        .unwrap();
+
+    let mut default_state = None;
+    for state in states {
+        let id = state["id"].as_u64().unwrap();
+        let mut offset = 0;
+        for (property, values) in &properties {
+            let value = state["properties"][property].as_str().unwrap();
+            let index = values.iter().position(|v| v == value).unwrap() as u64;
+            offset = offset * values.len() as u64 + index;
+        }
+        assert_eq!(
+            id,
+            first_state + offset,
+            "Unexpected ID of a state of {name}"
+        );
+        if state["default"].as_bool() == Some(true) {
+            default_state = Some(id);
+        }
+    }
+    let state_count = properties
+        .iter()
+        .map(|(_, values)| values.len())
+        .product::<usize>();
+    assert_eq!(states.len(), state_count, "Missing states of {name}");
+
+    Block {
+        name: name.to_string(),
+        properties,
+        first_state,
+        default_state: default_state.unwrap_or(first_state),
+    }
+}
+
+fn generate_blocks(report: &Map<String, Value>) -> String {
+    let mut blocks = report
+        .iter()
+        .map(|(name, block)| read_block(name, block))
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|block| block.first_state);
+
+    let mut code = String::new();
+    code.push_str("pub static BLOCKS: &[Block] = &[\n");
+    for block in &blocks {
+        let properties = block
+            .properties
+            .iter()
+            .map(|(name, values)| format!("Property {{ name: {name:?}, values: &{values:?} }}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        code.push_str(&format!(
+            "    Block {{ name: {:?}, properties: &[{properties}], first_state: {}, \
+             default_state: {} }},\n",
+            block.name, block.first_state, block.default_state
+        ));
+    }
+    code.push_str("];\n");
+    for block in &blocks {
+        code.push_str(&format!(
+            "/// Default state of `{}`\npub const {}: BlockState = BlockState({});\n",
+            block.name,
+            constant_name(&block.name),
+            block.default_state
+        ));
+    }
+
+    let by_name = sorted_by_name(blocks.iter().map(|block| block.name.as_str()));
+    code.push_str(&format!(
+        "/// Indices into [`BLOCKS`] in the order of their names\n\
+         static BLOCKS_BY_NAME: &[u32] = &{by_name:?};\n"
+    ));
+    code
+}
+
+fn generate_registries(report: &Map<String, Value>) -> String {
+    let mut code = String::new();
+    let mut registries = Vec::new();
+    for (name, registry) in report {
+        let mut entries = BTreeMap::new();
+        for (entry, value) in registry["entries"].as_object().unwrap() {
+            let id = value["protocol_id"].as_u64().unwrap();
+            assert!(entries.insert(id, entry.clone()).is_none());
+        }
+        assert!(
+            entries.keys().copied().eq(0..entries.len() as u64),
+            "Protocol IDs of {name} have gaps"
+        );
+        let entries = entries.into_values().collect::<Vec<_>>();
+        let by_name = sorted_by_name(entries.iter().map(String::as_str));
+        let constant = constant_name(name);
+        code.push_str(&format!(
+            "static {constant}_REGISTRY: Registry = Registry {{ name: {name:?}, entries: \
+             &{entries:?}, by_name: &{by_name:?} }};\n"
+        ));
+        registries.push(constant);
+    }
+
+    code.push_str("/// Every registry of the reports\npub static REGISTRIES: &[&Registry] = &[");
+    for constant in &registries {
+        code.push_str(&format!("&{constant}_REGISTRY, "));
+    }
+    code.push_str("];\n");
+    for (constant, name) in NAMED_REGISTRIES {
+        code.push_str(&format!(
+            "pub static {constant}: &Registry = &{}_REGISTRY;\n",
+            constant_name(name)
+        ));
+    }
+    code
+}
+
+/// The biomes of a directory of biome JSON files, in the order of their names. Only what the
+/// registry codec sends to clients is read.
+fn generate_biomes(dir: &Path) -> String {
+    let mut paths = fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("Failed to read biomes in {}: {error}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    // The generator and the registry codec rely on these two
+    for biome in ["plains", "the_void"] {
+        assert!(
+            paths.contains(&dir.join(format!("{biome}.json"))),
+            "No {biome} biome in {}",
+            dir.display()
+        );
+    }
+
+    let mut code = String::from(
+        "/// Biomes in the order of their IDs, which chunks refer to\npub static BIOMES: &[Biome] = &[\n",
+    );
+    for path in paths {
+        let name = format!("minecraft:{}", path.file_stem().unwrap().to_str().unwrap());
+        let biome = Value::Object(read_json(&path));
+        let effects = &biome["effects"];
+        let color = |name: &str| {
+            effects[name]
+                .as_i64()
+                .unwrap_or_else(|| panic!("Biome {} has no {name}", path.display()))
+        };
+        let mood_sound = effects["mood_sound"]["sound"].as_str();
+        code.push_str(&format!(
+            "    Biome {{ name: {name:?}, has_precipitation: {}, temperature: {:?}, downfall: {:?}, \
+             sky_color: {}, fog_color: {}, water_color: {}, water_fog_color: {}, \
+             mood_sound: {mood_sound:?} }},\n",
+            biome["has_precipitation"].as_bool().unwrap(),
+            biome["temperature"].as_f64().unwrap() as f32,
+            biome["downfall"].as_f64().unwrap() as f32,
+            color("sky_color"),
+            color("fog_color"),
+            color("water_color"),
+            color("water_fog_color"),
+        ));
+    }
+    code.push_str("];\n");
+    code
+}
+
+/// Name of the constant for an identifier, like `STONE` for `minecraft:stone`
+fn constant_name(name: &str) -> String {
+    name.trim_start_matches("minecraft:")
+        .replace(['/', '.', ':'], "_")
+        .to_uppercase()
+}
+
+/// Indices of `names` sorted by the names, for binary searches
+fn sorted_by_name<'a>(names: impl Iterator<Item = &'a str>) -> Vec<u32> {
+    let mut names = names.enumerate().collect::<Vec<_>>();
+    names.sort_by_key(|&(_, name)| name);
+    names.into_iter().map(|(index, _)| index as u32).collect()
+}
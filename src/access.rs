@@ -0,0 +1,313 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+pub const BANNED_PLAYERS_FILE: &str = "banned-players.json";
+pub const BANNED_IPS_FILE: &str = "banned-ips.json";
+pub const WHITELIST_FILE: &str = "whitelist.json";
+
+pub const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+pub const DEFAULT_BAN_SOURCE: &str = "Server";
+pub const NOT_WHITELISTED_MESSAGE: &str = "You are not white-listed on this server!";
+
+/// Format of the `created` and `expires` dates in the vanilla files
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+fn now() -> DateTime<FixedOffset> {
+    Local::now().fixed_offset()
+}
+
+fn serialize_date<S: Serializer>(date: &DateTime<FixedOffset>, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&date.format(DATE_FORMAT).to_string())
+}
+
+/// Unreadable dates count as now, like vanilla does
+fn deserialize_date<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<FixedOffset>, D::Error> {
+    let date = String::deserialize(d)?;
+    Ok(DateTime::parse_from_str(&date, DATE_FORMAT).unwrap_or_else(|_| now()))
+}
+
+fn serialize_expires<S: Serializer>(
+    expires: &Option<DateTime<FixedOffset>>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match expires {
+        Some(date) => serialize_date(date, s),
+        None => s.serialize_str("forever"),
+    }
+}
+
+/// `forever`, or an unreadable date, never expires
+fn deserialize_expires<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+    let date = String::deserialize(d)?;
+    Ok(DateTime::parse_from_str(&date, DATE_FORMAT).ok())
+}
+
+fn default_reason() -> String {
+    DEFAULT_BAN_REASON.to_string()
+}
+
+fn default_source() -> String {
+    DEFAULT_BAN_SOURCE.to_string()
+}
+
+/// Details shared by player and IP bans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    #[serde(
+        serialize_with = "serialize_date",
+        deserialize_with = "deserialize_date"
+    )]
+    pub created: DateTime<FixedOffset>,
+    #[serde(default = "default_source")]
+    pub source: String,
+    /// `None` for a permanent ban
+    #[serde(
+        serialize_with = "serialize_expires",
+        deserialize_with = "deserialize_expires"
+    )]
+    pub expires: Option<DateTime<FixedOffset>>,
+    #[serde(default = "default_reason")]
+    pub reason: String,
+}
+impl Ban {
+    /// A permanent ban starting now, with the default source
+    pub fn new(reason: Option<&str>) -> Ban {
+        Ban {
+            created: now(),
+            source: default_source(),
+            expires: None,
+            reason: reason.unwrap_or(DEFAULT_BAN_REASON).to_string(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires < now())
+    }
+
+    /// The rest of the kick message, after the line saying what is banned
+    fn details(&self) -> String {
+        let mut message = format!("\nReason: {}", self.reason);
+        if let Some(expires) = self.expires {
+            message += &format!(
+                "\nYour ban will be removed on {}",
+                expires.format(DATE_FORMAT)
+            );
+        }
+        message
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannedPlayer {
+    pub uuid: Uuid,
+    pub name: String,
+    #[serde(flatten)]
+    pub ban: Ban,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannedIp {
+    pub ip: IpAddr,
+    #[serde(flatten)]
+    pub ban: Ban,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhitelistedPlayer {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+/// An entry of a [`JsonList`], which holds at most one entry per key
+pub trait ListEntry {
+    type Key: PartialEq;
+
+    fn key(&self) -> Self::Key;
+
+    fn is_expired(&self) -> bool {
+        false
+    }
+}
+impl ListEntry for BannedPlayer {
+    type Key = Uuid;
+
+    fn key(&self) -> Uuid {
+        self.uuid
+    }
+
+    fn is_expired(&self) -> bool {
+        self.ban.is_expired()
+    }
+}
+impl ListEntry for BannedIp {
+    type Key = IpAddr;
+
+    fn key(&self) -> IpAddr {
+        self.ip
+    }
+
+    fn is_expired(&self) -> bool {
+        self.ban.is_expired()
+    }
+}
+impl ListEntry for WhitelistedPlayer {
+    type Key = Uuid;
+
+    fn key(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+/// A list of entries stored as a JSON array, saved after every change
+pub struct JsonList<T> {
+    path: PathBuf,
+    entries: Vec<T>,
+}
+impl<T: ListEntry + Serialize + DeserializeOwned> JsonList<T> {
+    /// Load the list from `path`, starting empty if the file does not exist yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(JsonList { path, entries })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[T] {
+        &self.entries
+    }
+
+    /// Find the entry for `key`, removing it if it has expired
+    pub fn get(&mut self, key: &T::Key) -> Result<Option<&T>> {
+        let Some(index) = self.entries.iter().position(|entry| entry.key() == *key) else {
+            return Ok(None);
+        };
+        if self.entries[index].is_expired() {
+            self.entries.remove(index);
+            self.save()?;
+            return Ok(None);
+        }
+        Ok(Some(&self.entries[index]))
+    }
+
+    /// Add an entry, replacing any existing one with the same key
+    pub fn add(&mut self, entry: T) -> Result<()> {
+        let key = entry.key();
+        self.entries.retain(|existing| existing.key() != key);
+        self.entries.push(entry);
+        self.save()
+    }
+
+    /// Remove the entry for `key`, returning whether there was one
+    pub fn remove(&mut self, key: &T::Key) -> Result<bool> {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.key() != *key);
+        if self.entries.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
+
+/// The ban lists and whitelist of a server, in the vanilla files of one directory. Every change
+/// is written back to its file right away.
+pub struct AccessControl {
+    pub banned_players: Mutex<JsonList<BannedPlayer>>,
+    pub banned_ips: Mutex<JsonList<BannedIp>>,
+    pub whitelist: Mutex<JsonList<WhitelistedPlayer>>,
+    whitelist_enabled: AtomicBool,
+}
+impl AccessControl {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        Ok(AccessControl {
+            banned_players: Mutex::new(JsonList::load(dir.join(BANNED_PLAYERS_FILE))?),
+            banned_ips: Mutex::new(JsonList::load(dir.join(BANNED_IPS_FILE))?),
+            whitelist: Mutex::new(JsonList::load(dir.join(WHITELIST_FILE))?),
+            whitelist_enabled: AtomicBool::new(false),
+        })
+    }
+
+    pub fn whitelist_enabled(&self) -> bool {
+        self.whitelist_enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_whitelist_enabled(&self, enabled: bool) {
+        self.whitelist_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn ban(&self, uuid: Uuid, name: &str, ban: Ban) -> Result<()> {
+        self.banned_players.lock().unwrap().add(BannedPlayer {
+            uuid,
+            name: name.to_string(),
+            ban,
+        })
+    }
+
+    pub fn pardon(&self, uuid: Uuid) -> Result<bool> {
+        self.banned_players.lock().unwrap().remove(&uuid)
+    }
+
+    pub fn ban_ip(&self, ip: IpAddr, ban: Ban) -> Result<()> {
+        self.banned_ips.lock().unwrap().add(BannedIp { ip, ban })
+    }
+
+    pub fn pardon_ip(&self, ip: IpAddr) -> Result<bool> {
+        self.banned_ips.lock().unwrap().remove(&ip)
+    }
+
+    pub fn add_to_whitelist(&self, uuid: Uuid, name: &str) -> Result<()> {
+        self.whitelist.lock().unwrap().add(WhitelistedPlayer {
+            uuid,
+            name: name.to_string(),
+        })
+    }
+
+    pub fn remove_from_whitelist(&self, uuid: Uuid) -> Result<bool> {
+        self.whitelist.lock().unwrap().remove(&uuid)
+    }
+
+    /// The message to kick a logging in player with, or `None` if they may join. Checked in the
+    /// same order as vanilla: player bans, the whitelist, then IP bans.
+    pub fn check_login(&self, uuid: Uuid, ip: IpAddr) -> Result<Option<String>> {
+        if let Some(banned) = self.banned_players.lock().unwrap().get(&uuid)? {
+            return Ok(Some(format!(
+                "You are banned from this server.{}",
+                banned.ban.details()
+            )));
+        }
+        if self.whitelist_enabled() && self.whitelist.lock().unwrap().get(&uuid)?.is_none() {
+            return Ok(Some(NOT_WHITELISTED_MESSAGE.to_string()));
+        }
+        if let Some(banned) = self.banned_ips.lock().unwrap().get(&ip)? {
+            return Ok(Some(format!(
+                "Your IP address is banned from this server.{}",
+                banned.ban.details()
+            )));
+        }
+        Ok(None)
+    }
+}
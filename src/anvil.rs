@@ -0,0 +1,1230 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    hash::Hash,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::ZlibEncoder,
+    Compression,
+};
+
+use crate::{
+    blocks::BlockState,
+    chunk::{
+        BitStorage, Chunk, ChunkSection, ContainerKind, PalettedContainer, AIR, HEIGHT, MIN_Y,
+        SECTION_COUNT, STONE,
+    },
+    generator::biome_id,
+    nbt::{self, Compound, Tag},
+    protocol::{
+        play::{
+            clientbound::ChunkBlockEntity,
+            registry_codec::{BIOMES, PLAINS},
+            Position,
+        },
+        Reader, Writer,
+    },
+    registries::BLOCK_ENTITY_TYPES,
+};
+
+/// Data version of chunks saved by 1.19.4
+pub const DATA_VERSION: i32 = 3337;
+/// Bytes in a sector of a region file
+pub const SECTOR_SIZE: usize = 4096;
+/// Chunks along each side of a region
+pub const REGION_WIDTH: i32 = 32;
+/// Largest chunk NBT a region may decompress to, which is more than a packet may hold because
+/// block entities full of items add up
+pub const MAX_CHUNK_NBT_SIZE: usize = 1 << 24;
+
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+const GZIP: u8 = 1;
+const ZLIB: u8 = 2;
+const UNCOMPRESSED: u8 = 3;
+/// Set on the compression type when the chunk is too big for the region and in its own file
+const EXTERNAL: u8 = 128;
+/// Regions kept in memory, after which the least recently used is read again when needed
+const MAX_CACHED_REGIONS: usize = 16;
+
+/// Turns the names in saved chunks into the IDs sent to clients
+pub trait BlockRegistry: Send + Sync {
+    /// ID of a block state, given its properties in order of their names, or `None` if the
+    /// block or its properties are unknown
+    fn block_state(&self, name: &str, properties: &[(&str, &str)]) -> Option<u32>;
+    /// ID of a biome, or `None` if it is unknown
+    fn biome(&self, name: &str) -> Option<u32>;
+    /// ID of a block entity type, or `None` to leave the block entity out
+    fn block_entity_type(&self, name: &str) -> Option<i32>;
+
+    /// Name and properties of a block state, the other way around from
+    /// [`block_state`](Self::block_state), or `None` if it cannot be saved
+    fn block_state_name(&self, state: u32) -> Option<(String, Vec<(String, String)>)>;
+    fn biome_name(&self, biome: u32) -> Option<String>;
+    fn block_entity_name(&self, kind: i32) -> Option<String>;
+}
+
+/// Knows the blocks and block entity types generated from the data generator reports, and the
+/// biomes of the registry codec
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinBlocks;
+impl BlockRegistry for BuiltinBlocks {
+    fn block_state(&self, name: &str, properties: &[(&str, &str)]) -> Option<u32> {
+        BlockState::from_name(name, properties).map(BlockState::id)
+    }
+
+    fn biome(&self, name: &str) -> Option<u32> {
+        biome_id(name)
+    }
+
+    fn block_entity_type(&self, name: &str) -> Option<i32> {
+        BLOCK_ENTITY_TYPES.id(name).map(|id| id as i32)
+    }
+
+    fn block_state_name(&self, state: u32) -> Option<(String, Vec<(String, String)>)> {
+        let state = BlockState::from_id(state)?;
+        let properties = state
+            .properties()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Some((state.block().name.to_string(), properties))
+    }
+
+    fn biome_name(&self, biome: u32) -> Option<String> {
+        BIOMES
+            .get(biome as usize)
+            .map(|biome| biome.name.to_string())
+    }
+
+    fn block_entity_name(&self, kind: i32) -> Option<String> {
+        BLOCK_ENTITY_TYPES
+            .get(u32::try_from(kind).ok()?)
+            .map(str::to_string)
+    }
+}
+
+/// A region file, `r.<x>.<z>.mca`, holding up to 32 by 32 chunks
+#[derive(Debug, Clone)]
+pub struct Region {
+    data: Vec<u8>,
+    /// Directory of chunks stored outside the region, in `c.<x>.<z>.mcc`
+    dir: Option<PathBuf>,
+}
+impl Region {
+    pub fn open(path: impl AsRef<Path>) -> Result<Region> {
+        let path = path.as_ref();
+        let data = fs::read(path)
+            .map_err(|error| anyhow!("Failed to read region {}: {error}", path.display()))?;
+        let mut region = Region::from_bytes(data)?;
+        region.dir = path.parent().map(Path::to_path_buf);
+        Ok(region)
+    }
+
+    /// A region read from memory, which fails to load chunks stored in their own files
+    pub fn from_bytes(data: Vec<u8>) -> Result<Region> {
+        if data.len() < HEADER_SIZE {
+            return Err(anyhow!(
+                "Region of {} bytes is missing its header",
+                data.len()
+            ));
+        }
+        Ok(Region { data, dir: None })
+    }
+
+    /// A region without any chunks, whose large chunks go in their own files in `dir`
+    pub fn new(dir: Option<PathBuf>) -> Region {
+        Region {
+            data: vec![0; HEADER_SIZE],
+            dir,
+        }
+    }
+
+    /// Position of a chunk in the header, from its chunk coordinates
+    fn header_index(x: i32, z: i32) -> usize {
+        (x.rem_euclid(REGION_WIDTH) + z.rem_euclid(REGION_WIDTH) * REGION_WIDTH) as usize * 4
+    }
+
+    /// First sector and number of sectors of a chunk, which are 0 if it was never saved
+    fn location(&self, x: i32, z: i32) -> (usize, usize) {
+        let entry = &self.data[Self::header_index(x, z)..][..4];
+        let offset = u32::from_be_bytes([0, entry[0], entry[1], entry[2]]);
+        (offset as usize, entry[3] as usize)
+    }
+
+    pub fn contains(&self, x: i32, z: i32) -> bool {
+        self.location(x, z) != (0, 0)
+    }
+
+    /// Seconds since the Unix epoch when a chunk was last saved
+    pub fn timestamp(&self, x: i32, z: i32) -> u32 {
+        let entry = &self.data[SECTOR_SIZE + Self::header_index(x, z)..][..4];
+        u32::from_be_bytes(entry.try_into().unwrap())
+    }
+
+    /// NBT of a chunk from its chunk coordinates, or `None` if it was never saved. Only the
+    /// position inside the region is used.
+    pub fn read_chunk(&self, x: i32, z: i32) -> Result<Option<Compound>> {
+        let (offset, sectors) = self.location(x, z);
+        if (offset, sectors) == (0, 0) {
+            return Ok(None);
+        }
+        let start = offset * SECTOR_SIZE;
+        let end = (offset + sectors) * SECTOR_SIZE;
+        if offset < 2 || start + 5 > self.data.len() {
+            return Err(anyhow!(
+                "Chunk {x}, {z} is at sector {offset}, outside the region"
+            ));
+        }
+        let length = u32::from_be_bytes(self.data[start..start + 4].try_into().unwrap()) as usize;
+        let compression = self.data[start + 4];
+        let payload_end = start + 4 + length;
+        if length == 0 || payload_end > end.min(self.data.len()) {
+            return Err(anyhow!(
+                "Chunk {x}, {z} of {length} bytes overflows its {sectors} sectors"
+            ));
+        }
+
+        let payload = if compression & EXTERNAL != 0 {
+            let dir = self
+                .dir
+                .as_ref()
+                .ok_or_else(|| anyhow!("Chunk {x}, {z} is stored outside the region"))?;
+            let path = dir.join(format!("c.{x}.{z}.mcc"));
+            fs::read(&path)
+                .map_err(|error| anyhow!("Failed to read chunk {}: {error}", path.display()))?
+        } else {
+            self.data[start + 5..payload_end].to_vec()
+        };
+        let nbt = decompress(compression & !EXTERNAL, &payload)?;
+        nbt::read_compound_limited(&mut Reader::new(nbt.into()), MAX_CHUNK_NBT_SIZE)
+    }
+
+    /// Compress and store the NBT of a chunk from its chunk coordinates, in the first free
+    /// sectors it fits in. Chunks of more than 255 sectors go in their own file, like vanilla.
+    pub fn write_chunk(&mut self, x: i32, z: i32, nbt: &Compound) -> Result<()> {
+        let mut writer = Writer::new();
+        nbt::write_named(&mut writer, "", &Tag::Compound(nbt.clone()));
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&Bytes::from(writer))?;
+        let mut payload = encoder.finish()?;
+
+        let external = self
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(format!("c.{x}.{z}.mcc")));
+        let mut compression = ZLIB;
+        if (payload.len() + 5).div_ceil(SECTOR_SIZE) > u8::MAX as usize {
+            let path = external.ok_or_else(|| {
+                anyhow!("Chunk {x}, {z} is too big for a region without a directory")
+            })?;
+            fs::write(&path, &payload)
+                .map_err(|error| anyhow!("Failed to write chunk {}: {error}", path.display()))?;
+            payload.clear();
+            compression |= EXTERNAL;
+        } else if let Some(path) = external.filter(|path| path.exists()) {
+            fs::remove_file(path)?;
+        }
+
+        let sectors = (payload.len() + 5).div_ceil(SECTOR_SIZE);
+        let offset = self.allocate(x, z, sectors);
+        let start = offset * SECTOR_SIZE;
+        let end = (offset + sectors) * SECTOR_SIZE;
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[start..start + 4].copy_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        self.data[start + 4] = compression;
+        self.data[start + 5..start + 5 + payload.len()].copy_from_slice(&payload);
+        self.data[start + 5 + payload.len()..end].fill(0);
+
+        let index = Self::header_index(x, z);
+        let location = (offset as u32) << 8 | sectors as u32;
+        self.data[index..index + 4].copy_from_slice(&location.to_be_bytes());
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
+        self.data[SECTOR_SIZE + index..][..4].copy_from_slice(&timestamp.to_be_bytes());
+        Ok(())
+    }
+
+    /// First of `count` free sectors for a chunk, where the sectors it has now count as free
+    fn allocate(&self, x: i32, z: i32, count: usize) -> usize {
+        let mut used = vec![false; self.data.len().div_ceil(SECTOR_SIZE)];
+        used[..2].fill(true);
+        for chunk_z in 0..REGION_WIDTH {
+            for chunk_x in 0..REGION_WIDTH {
+                if (chunk_x, chunk_z) == (x.rem_euclid(REGION_WIDTH), z.rem_euclid(REGION_WIDTH)) {
+                    continue;
+                }
+                let (offset, sectors) = self.location(chunk_x, chunk_z);
+                for sector in offset..(offset + sectors).min(used.len()) {
+                    used[sector] = true;
+                }
+            }
+        }
+        // Sectors past the end of the file are all free
+        let mut start = 2;
+        while used[start..].iter().take(count).any(|&used| used) {
+            start += 1;
+        }
+        start
+    }
+
+    /// Write the whole region to `path`, replacing the file only once it is complete
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let temp = path.with_extension("mca.tmp");
+        let mut data = self.data.clone();
+        data.resize(data.len().div_ceil(SECTOR_SIZE) * SECTOR_SIZE, 0);
+        fs::write(&temp, data)
+            .map_err(|error| anyhow!("Failed to write region {}: {error}", temp.display()))?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+}
+
+/// Decompress a chunk, refusing to go beyond [`MAX_CHUNK_NBT_SIZE`]
+fn decompress(compression: u8, payload: &[u8]) -> Result<Vec<u8>> {
+    let reader: Box<dyn Read + '_> = match compression {
+        GZIP => Box::new(GzDecoder::new(payload)),
+        ZLIB => Box::new(ZlibDecoder::new(payload)),
+        UNCOMPRESSED => Box::new(payload),
+        _ => return Err(anyhow!("Unknown chunk compression {compression}")),
+    };
+    let mut nbt = Vec::new();
+    reader
+        .take(MAX_CHUNK_NBT_SIZE as u64 + 1)
+        .read_to_end(&mut nbt)?;
+    if nbt.len() > MAX_CHUNK_NBT_SIZE {
+        return Err(anyhow!(
+            "Chunk decompresses to more than {MAX_CHUNK_NBT_SIZE} bytes"
+        ));
+    }
+    Ok(nbt)
+}
+
+/// Names in saved chunks that a registry does not know. Unknown blocks load as stone, or as air
+/// when their name ends in `air`, unknown biomes as plains, and unknown block entities are left
+/// out. Saving puts them back wherever their placeholders are still in place.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Unknown {
+    pub blocks: BTreeSet<String>,
+    pub biomes: BTreeSet<String>,
+    pub block_entities: BTreeSet<String>,
+}
+impl Unknown {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.biomes.is_empty() && self.block_entities.is_empty()
+    }
+
+    /// Add the names of `other`, returning the ones that were not known to be unknown yet
+    fn merge(&mut self, other: Unknown) -> Vec<String> {
+        let mut new = Vec::new();
+        for (names, other) in [
+            (&mut self.blocks, other.blocks),
+            (&mut self.biomes, other.biomes),
+            (&mut self.block_entities, other.block_entities),
+        ] {
+            for name in other {
+                if names.insert(name.clone()) {
+                    new.push(name);
+                }
+            }
+        }
+        new
+    }
+}
+
+/// Convert the NBT of a 1.19.4 chunk, or `None` if it is not fully generated yet. Names the
+/// registry does not know are added to `unknown`.
+pub fn chunk_from_nbt(
+    nbt: &Compound,
+    registry: &dyn BlockRegistry,
+    unknown: &mut Unknown,
+) -> Result<Option<Chunk>> {
+    if nbt.get("Level").is_some() {
+        return Err(anyhow!("Chunk was saved before 1.18"));
+    }
+    if !is_full(nbt) {
+        return Ok(None);
+    }
+    let x = int(nbt, "xPos")?;
+    let z = int(nbt, "zPos")?;
+
+    let plains = registry.biome(PLAINS).unwrap_or(0);
+    let mut sections = vec![ChunkSection::new(plains); SECTION_COUNT];
+    for section in list(nbt, "sections")? {
+        let Tag::Compound(section) = section else {
+            return Err(anyhow!("Section is not a compound"));
+        };
+        // Sections just below and above the world only hold light
+        let y = int(section, "Y")?;
+        let Some(index) = usize::try_from(y - MIN_Y / 16)
+            .ok()
+            .filter(|&index| index < SECTION_COUNT)
+        else {
+            continue;
+        };
+
+        let block_states = match compound(section, "block_states") {
+            Ok(block_states) => {
+                let palette = list(block_states, "palette")?
+                    .iter()
+                    .map(|entry| {
+                        let (state, name) = block_state(entry, registry)?;
+                        unknown.blocks.extend(name.map(str::to_string));
+                        Ok(state)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                container(ContainerKind::BlockStates, block_states, &palette)?
+            }
+            Err(_) => PalettedContainer::single(ContainerKind::BlockStates, AIR),
+        };
+        let biomes = match compound(section, "biomes") {
+            Ok(biomes) => {
+                let palette = list(biomes, "palette")?
+                    .iter()
+                    .map(|entry| {
+                        let (biome, name) = biome(entry, registry, plains)?;
+                        unknown.biomes.extend(name.map(str::to_string));
+                        Ok(biome)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                container(ContainerKind::Biomes, biomes, &palette)?
+            }
+            Err(_) => sections[index].biomes().clone(),
+        };
+        sections[index] = ChunkSection::from_containers(block_states, biomes);
+    }
+
+    let mut chunk = Chunk::from_sections(x, z, sections).unwrap();
+    if let Ok(block_entities) = list(nbt, "block_entities") {
+        for block_entity in block_entities {
+            let Tag::Compound(block_entity) = block_entity else {
+                return Err(anyhow!("Block entity is not a compound"));
+            };
+            match convert_block_entity(block_entity, registry)? {
+                Some(block_entity) => chunk.block_entities.push(block_entity),
+                None => {
+                    let name = string(block_entity, "id")?;
+                    unknown.block_entities.insert(name.to_string());
+                }
+            }
+        }
+    }
+    Ok(Some(chunk))
+}
+
+fn is_full(nbt: &Compound) -> bool {
+    matches!(nbt.get("Status"), Some(Tag::String(status)) if status == "full" || status == "minecraft:full")
+}
+
+/// ID a block state in a palette loads as, and its name if the registry does not know it
+fn block_state<'a>(entry: &'a Tag, registry: &dyn BlockRegistry) -> Result<(u32, Option<&'a str>)> {
+    let Tag::Compound(entry) = entry else {
+        return Err(anyhow!("Block state is not a compound"));
+    };
+    let name = string(entry, "Name")?;
+    let properties = match compound(entry, "Properties") {
+        Ok(properties) => properties
+            .iter()
+            .map(|(name, value)| match value {
+                Tag::String(value) => Ok((name, value.as_str())),
+                _ => Err(anyhow!("Property {name} is not a string")),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Err(_) => Vec::new(),
+    };
+    Ok(match registry.block_state(name, &properties) {
+        Some(state) => (state, None),
+        None if name.ends_with("air") => (AIR, Some(name)),
+        None => (STONE, Some(name)),
+    })
+}
+
+/// ID a biome in a palette loads as, and its name if the registry does not know it
+fn biome<'a>(
+    entry: &'a Tag,
+    registry: &dyn BlockRegistry,
+    plains: u32,
+) -> Result<(u32, Option<&'a str>)> {
+    let Tag::String(name) = entry else {
+        return Err(anyhow!("Biome is not a string"));
+    };
+    Ok(match registry.biome(name) {
+        Some(biome) => (biome, None),
+        None => (plains, Some(name)),
+    })
+}
+
+/// Bits of the indices into a saved palette of more than one entry
+fn saved_bits(kind: ContainerKind, palette_len: usize) -> u8 {
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()) as u8;
+    match kind {
+        ContainerKind::BlockStates => bits.max(4),
+        ContainerKind::Biomes => bits,
+    }
+}
+
+/// Index into the palette of every entry of a saved container. Region files store indices with
+/// the fewest bits the palette needs, but no fewer than 4 for block states.
+fn palette_indices(kind: ContainerKind, nbt: &Compound, palette_len: usize) -> Result<Vec<usize>> {
+    match palette_len {
+        0 => return Err(anyhow!("Empty palette")),
+        1 => return Ok(vec![0; kind.entries()]),
+        _ => {}
+    }
+    let bits = saved_bits(kind, palette_len);
+    let Some(Tag::LongArray(data)) = nbt.get("data") else {
+        return Err(anyhow!("Palette of {palette_len} entries has no data"));
+    };
+    let storage = BitStorage::from_longs(bits, kind.entries(), data)
+        .ok_or_else(|| anyhow!("{} longs of data for {bits} bits", data.len()))?;
+    (0..kind.entries())
+        .map(|index| {
+            let entry = storage.get(index) as usize;
+            match entry < palette_len {
+                true => Ok(entry),
+                false => Err(anyhow!("Palette index {entry} out of {palette_len}")),
+            }
+        })
+        .collect()
+}
+
+/// Unpack a saved container whose palette has already been turned into IDs
+fn container(kind: ContainerKind, nbt: &Compound, palette: &[u32]) -> Result<PalettedContainer> {
+    if palette.len() == 1 {
+        return Ok(PalettedContainer::single(kind, palette[0]));
+    }
+    let values = palette_indices(kind, nbt, palette.len())?
+        .into_iter()
+        .map(|entry| palette[entry])
+        .collect::<Vec<_>>();
+    Ok(PalettedContainer::from_values(kind, &values))
+}
+
+/// The entries of a saved container that the registry does not know, to save them again
+struct Kept {
+    palette: Vec<Tag>,
+    /// The placeholder each entry of the palette loaded as, if the registry does not know it
+    placeholders: Vec<Option<u32>>,
+    indices: Vec<usize>,
+}
+impl Kept {
+    /// Find the entries of a saved container that load as placeholders, if there are any
+    fn find(
+        kind: ContainerKind,
+        nbt: &Compound,
+        placeholder: impl Fn(&Tag) -> Result<Option<u32>>,
+    ) -> Result<Option<Kept>> {
+        let palette = list(nbt, "palette")?.to_vec();
+        let placeholders = palette
+            .iter()
+            .map(placeholder)
+            .collect::<Result<Vec<_>>>()?;
+        if placeholders.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let indices = palette_indices(kind, nbt, palette.len())?;
+        Ok(Some(Kept {
+            palette,
+            placeholders,
+            indices,
+        }))
+    }
+
+    /// The saved entry at `index`, if it was unknown and `value` is still the placeholder it
+    /// loaded as
+    fn entry(&self, index: usize, value: u32) -> Option<usize> {
+        let entry = self.indices[index];
+        (self.placeholders[entry] == Some(value)).then_some(entry)
+    }
+}
+
+/// Convert a chunk to the NBT of 1.19.4. Light is left for vanilla to calculate, since the
+/// server does not keep it.
+///
+/// When the chunk replaces `saved`, the one it was loaded from, the tags the server does not use
+/// are kept. So are the blocks, biomes and block entities the registry does not know, wherever
+/// the placeholders they loaded as are still in place.
+pub fn chunk_to_nbt(
+    chunk: &Chunk,
+    registry: &dyn BlockRegistry,
+    saved: Option<&Compound>,
+) -> Result<Compound> {
+    let saved = saved.filter(|saved| {
+        is_full(saved)
+            && int(saved, "xPos").ok() == Some(chunk.x)
+            && int(saved, "zPos").ok() == Some(chunk.z)
+    });
+    let mut saved_sections = HashMap::new();
+    for section in saved.map_or(Ok(&[][..]), |saved| list(saved, "sections"))? {
+        if let Tag::Compound(section) = section {
+            saved_sections.insert(int(section, "Y")?, section);
+        }
+    }
+    let plains = registry.biome(PLAINS).unwrap_or(0);
+
+    let mut sections = Vec::with_capacity(SECTION_COUNT);
+    let mut kept_blocks = Vec::with_capacity(SECTION_COUNT);
+    for (index, section) in chunk.sections().iter().enumerate() {
+        let y = index as i32 + MIN_Y / 16;
+        let saved = saved_sections.get(&y);
+        let kept_states = match saved.and_then(|saved| compound(saved, "block_states").ok()) {
+            Some(nbt) => Kept::find(ContainerKind::BlockStates, nbt, |entry| {
+                let (state, name) = block_state(entry, registry)?;
+                Ok(name.map(|_| state))
+            })?,
+            None => None,
+        };
+        let kept_biomes = match saved.and_then(|saved| compound(saved, "biomes").ok()) {
+            Some(nbt) => Kept::find(ContainerKind::Biomes, nbt, |entry| {
+                let (biome, name) = biome(entry, registry, plains)?;
+                Ok(name.map(|_| biome))
+            })?,
+            None => None,
+        };
+
+        let block_states = save_container(section.block_states(), kept_states.as_ref(), |state| {
+            let (name, properties) = registry
+                .block_state_name(state)
+                .ok_or_else(|| anyhow!("Block state {state} has no name"))?;
+            let mut entry = Compound::new().with("Name", name);
+            if !properties.is_empty() {
+                let mut compound = Compound::new();
+                for (name, value) in properties {
+                    compound.insert(&name, Tag::String(value));
+                }
+                entry.insert("Properties", Tag::Compound(compound));
+            }
+            Ok(Tag::Compound(entry))
+        })?;
+        let biomes = save_container(section.biomes(), kept_biomes.as_ref(), |biome| {
+            registry
+                .biome_name(biome)
+                .map(Tag::String)
+                .ok_or_else(|| anyhow!("Biome {biome} has no name"))
+        })?;
+
+        // The saved light no longer matches the blocks
+        let mut nbt = saved.map_or_else(Compound::new, |&saved| saved.clone());
+        nbt.remove("SkyLight");
+        nbt.remove("BlockLight");
+        sections.push(Tag::Compound(
+            nbt.with("Y", y as i8)
+                .with("block_states", block_states)
+                .with("biomes", biomes),
+        ));
+        kept_blocks.push(kept_states);
+    }
+
+    let mut block_entities = Vec::new();
+    for block_entity in &chunk.block_entities {
+        // Block entities the registry does not know only come from the saved chunk
+        let Some(id) = registry.block_entity_name(block_entity.kind) else {
+            continue;
+        };
+        let mut nbt = block_entity.data.clone().unwrap_or_default();
+        nbt.insert("id", Tag::String(id));
+        nbt.insert("x", Tag::Int(chunk.x * 16 + block_entity.x as i32));
+        nbt.insert("y", Tag::Int(block_entity.y as i32));
+        nbt.insert("z", Tag::Int(chunk.z * 16 + block_entity.z as i32));
+        nbt.insert("keepPacked", Tag::Byte(0));
+        block_entities.push(Tag::Compound(nbt));
+    }
+    // Unknown block entities stay with the unknown blocks they belong to
+    for block_entity in saved.map_or(Ok(&[][..]), |saved| list(saved, "block_entities"))? {
+        let Tag::Compound(nbt) = block_entity else {
+            continue;
+        };
+        if registry.block_entity_type(string(nbt, "id")?).is_some() {
+            continue;
+        }
+        let (x, y, z) = (
+            int(nbt, "x")?.rem_euclid(16),
+            int(nbt, "y")?,
+            int(nbt, "z")?.rem_euclid(16),
+        );
+        let Some(index) = usize::try_from(y - MIN_Y)
+            .ok()
+            .filter(|&index| index < HEIGHT)
+        else {
+            continue;
+        };
+        let section = index / 16;
+        let cell = ((index % 16) * 16 + z as usize) * 16 + x as usize;
+        let state = chunk.sections()[section].block_states().get(cell);
+        let kept = kept_blocks[section]
+            .as_ref()
+            .is_some_and(|kept| kept.entry(cell, state).is_some());
+        if kept {
+            block_entities.push(block_entity.clone());
+        }
+    }
+
+    let mut nbt = Compound::new()
+        .with("LastUpdate", 0i64)
+        .with("InhabitedTime", 0i64);
+    for (name, tag) in saved.into_iter().flat_map(Compound::iter) {
+        nbt.insert(name, tag.clone());
+    }
+    Ok(nbt
+        .with("DataVersion", DATA_VERSION)
+        .with("xPos", chunk.x)
+        .with("yPos", MIN_Y / 16)
+        .with("zPos", chunk.z)
+        .with("Status", "minecraft:full")
+        .with("isLightOn", false)
+        .with("sections", sections)
+        .with("block_entities", block_entities)
+        .with("Heightmaps", chunk.heightmaps()))
+}
+
+/// An entry of a container to save, as an ID or as an entry of the saved palette
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Entry {
+    Id(u32),
+    Kept(usize),
+}
+
+/// Pack a container with a palette of the names `name` gives its IDs, keeping the entries of
+/// `kept` where their placeholders are
+fn save_container(
+    container: &PalettedContainer,
+    kept: Option<&Kept>,
+    name: impl Fn(u32) -> Result<Tag>,
+) -> Result<Compound> {
+    let kind = container.kind();
+    let mut palette = Vec::new();
+    let mut entries = HashMap::new();
+    let indices = (0..kind.entries())
+        .map(|index| {
+            let value = container.get(index);
+            let entry = match kept.and_then(|kept| kept.entry(index, value)) {
+                Some(entry) => Entry::Kept(entry),
+                None => Entry::Id(value),
+            };
+            *entries.entry(entry).or_insert_with(|| {
+                palette.push(entry);
+                palette.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let kept_palette = kept.map_or(&[][..], |kept| &kept.palette);
+    let names = palette
+        .iter()
+        .map(|&entry| match entry {
+            Entry::Id(value) => name(value),
+            Entry::Kept(entry) => Ok(kept_palette[entry].clone()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut nbt = Compound::new().with("palette", names);
+    if palette.len() > 1 {
+        let mut storage = BitStorage::new(saved_bits(kind, palette.len()), kind.entries());
+        for (index, &entry) in indices.iter().enumerate() {
+            storage.set(index, entry);
+        }
+        nbt.insert("data", Tag::LongArray(storage.to_longs()));
+    }
+    Ok(nbt)
+}
+
+/// A block entity as sent in Chunk Data, without the fields that are sent separately
+fn convert_block_entity(
+    nbt: &Compound,
+    registry: &dyn BlockRegistry,
+) -> Result<Option<ChunkBlockEntity>> {
+    let Some(kind) = registry.block_entity_type(string(nbt, "id")?) else {
+        return Ok(None);
+    };
+    let (x, y, z) = (int(nbt, "x")?, int(nbt, "y")?, int(nbt, "z")?);
+    let mut data = Compound::new();
+    for (name, tag) in nbt.iter() {
+        if !matches!(name, "id" | "x" | "y" | "z" | "keepPacked") {
+            data.insert(name, tag.clone());
+        }
+    }
+    Ok(Some(ChunkBlockEntity {
+        x: x.rem_euclid(16) as u8,
+        y: y as i16,
+        z: z.rem_euclid(16) as u8,
+        kind,
+        data: (!data.is_empty()).then_some(data),
+    }))
+}
+
+/// Any integer tag, since vanilla saves section Y as a byte and coordinates as ints
+fn int(nbt: &Compound, name: &str) -> Result<i32> {
+    match nbt.get(name) {
+        Some(Tag::Byte(value)) => Ok(*value as i32),
+        Some(Tag::Short(value)) => Ok(*value as i32),
+        Some(Tag::Int(value)) => Ok(*value),
+        _ => Err(anyhow!("Missing integer {name}")),
+    }
+}
+
+fn string<'a>(nbt: &'a Compound, name: &str) -> Result<&'a str> {
+    match nbt.get(name) {
+        Some(Tag::String(value)) => Ok(value),
+        _ => Err(anyhow!("Missing string {name}")),
+    }
+}
+
+fn list<'a>(nbt: &'a Compound, name: &str) -> Result<&'a [Tag]> {
+    match nbt.get(name) {
+        Some(Tag::List(list)) => Ok(list),
+        _ => Err(anyhow!("Missing list {name}")),
+    }
+}
+
+fn compound<'a>(nbt: &'a Compound, name: &str) -> Result<&'a Compound> {
+    match nbt.get(name) {
+        Some(Tag::Compound(compound)) => Ok(compound),
+        _ => Err(anyhow!("Missing compound {name}")),
+    }
+}
+
+/// A region, or `None` if it does not exist, and the use it was last used on
+type CachedRegion = (Option<Arc<Region>>, u64);
+
+/// The most recently used regions, by their coordinates
+#[derive(Default)]
+struct Regions {
+    cached: HashMap<(i32, i32), CachedRegion>,
+    /// Counts every use of a region, to tell which was used last
+    uses: u64,
+    /// Counts every saved region, so a region read during a save is not cached over it
+    saves: u64,
+}
+impl Regions {
+    fn get(&mut self, key: (i32, i32)) -> Option<Option<Arc<Region>>> {
+        self.uses += 1;
+        let (region, used) = self.cached.get_mut(&key)?;
+        *used = self.uses;
+        Some(region.clone())
+    }
+
+    fn insert(&mut self, key: (i32, i32), region: Option<Arc<Region>>) {
+        self.uses += 1;
+        self.cached.insert(key, (region, self.uses));
+        if self.cached.len() > MAX_CACHED_REGIONS {
+            let oldest = self
+                .cached
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(&key, _)| key);
+            if let Some(oldest) = oldest {
+                self.cached.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// A vanilla world folder, holding the chunks that were saved in its region files
+pub struct AnvilWorld {
+    dir: PathBuf,
+    spawn: Option<Position>,
+    registry: Arc<dyn BlockRegistry>,
+    regions: Mutex<Regions>,
+    unknown: Mutex<Unknown>,
+    /// Held while saving, so two saves do not write the same region at once
+    saving: Mutex<()>,
+}
+impl AnvilWorld {
+    /// Open the world in `dir`, which holds `level.dat` and the `region` directory. A world
+    /// without them is created when chunks are first saved, but only gets region files.
+    pub fn open(dir: impl AsRef<Path>) -> Result<AnvilWorld> {
+        let dir = dir.as_ref().to_path_buf();
+        let level = dir.join("level.dat");
+        let spawn = match level.exists() {
+            true => Some(read_spawn(&level)?),
+            false => None,
+        };
+        match spawn {
+            Some(spawn) => println!(
+                "Loaded world {} with spawn at {}, {}, {}",
+                dir.display(),
+                spawn.x,
+                spawn.y,
+                spawn.z
+            ),
+            None => println!("World {} has no level.dat", dir.display()),
+        }
+        Ok(AnvilWorld {
+            dir,
+            spawn,
+            registry: Arc::new(BuiltinBlocks),
+            regions: Mutex::default(),
+            unknown: Mutex::default(),
+            saving: Mutex::new(()),
+        })
+    }
+
+    /// Resolve block and biome names with `registry` instead of [`BuiltinBlocks`]
+    pub fn registry(mut self, registry: impl BlockRegistry + 'static) -> Self {
+        self.registry = Arc::new(registry);
+        self
+    }
+
+    /// Block the player spawns on top of, if the world has a `level.dat`
+    pub fn spawn_point(&self) -> Option<Position> {
+        self.spawn
+    }
+
+    fn region_dir(&self) -> PathBuf {
+        self.dir.join("region")
+    }
+
+    fn region_path(&self, (x, z): (i32, i32)) -> PathBuf {
+        self.region_dir().join(format!("r.{x}.{z}.mca"))
+    }
+
+    /// Coordinates of the region a chunk is in
+    fn region_key(x: i32, z: i32) -> (i32, i32) {
+        (x.div_euclid(REGION_WIDTH), z.div_euclid(REGION_WIDTH))
+    }
+
+    /// Names in the chunks loaded so far that the registry does not know
+    pub fn unknown(&self) -> Unknown {
+        self.unknown.lock().unwrap().clone()
+    }
+
+    fn region(&self, x: i32, z: i32) -> Result<Option<Arc<Region>>> {
+        let key = Self::region_key(x, z);
+        let saves = {
+            let mut regions = self.regions.lock().unwrap();
+            if let Some(region) = regions.get(key) {
+                return Ok(region);
+            }
+            regions.saves
+        };
+        // Other regions stay available while the file is read
+        let path = self.region_path(key);
+        let region = match path.exists() {
+            true => Some(Arc::new(Region::open(path)?)),
+            false => None,
+        };
+        let mut regions = self.regions.lock().unwrap();
+        if regions.saves == saves {
+            regions.insert(key, region.clone());
+        }
+        Ok(region)
+    }
+
+    /// The saved chunk at chunk coordinates, or `None` if there is none
+    pub fn load_chunk(&self, x: i32, z: i32) -> Result<Option<Chunk>> {
+        let Some(region) = self.region(x, z)? else {
+            return Ok(None);
+        };
+        let Some(nbt) = region.read_chunk(x, z)? else {
+            return Ok(None);
+        };
+        let mut unknown = Unknown::default();
+        let chunk = chunk_from_nbt(&nbt, self.registry.as_ref(), &mut unknown)?;
+        if !unknown.is_empty() {
+            let new = self.unknown.lock().unwrap().merge(unknown);
+            if !new.is_empty() {
+                println!(
+                    "Chunk {x}, {z} has unknown {}, which load as placeholders",
+                    new.join(", ")
+                );
+            }
+        }
+        if let Some(chunk) = &chunk {
+            if (chunk.x, chunk.z) != (x, z) {
+                return Err(anyhow!(
+                    "Chunk {x}, {z} is saved as {}, {}",
+                    chunk.x,
+                    chunk.z
+                ));
+            }
+        }
+        Ok(chunk)
+    }
+
+    /// Save chunks to their regions, writing each region file once
+    pub fn save_chunks(&self, chunks: &[Chunk]) -> Result<()> {
+        let _saving = self.saving.lock().unwrap();
+        let mut by_region = HashMap::<_, Vec<_>>::new();
+        for chunk in chunks {
+            by_region
+                .entry(Self::region_key(chunk.x, chunk.z))
+                .or_default()
+                .push(chunk);
+        }
+        fs::create_dir_all(self.region_dir())?;
+
+        for (key, chunks) in by_region {
+            let (x, z) = (key.0 * REGION_WIDTH, key.1 * REGION_WIDTH);
+            let mut region = match self.region(x, z)? {
+                Some(region) => region.as_ref().clone(),
+                None => Region::new(Some(self.region_dir())),
+            };
+            for chunk in chunks {
+                let saved = region.read_chunk(chunk.x, chunk.z).unwrap_or_else(|error| {
+                    println!(
+                        "Replacing unreadable chunk {}, {}: {error}",
+                        chunk.x, chunk.z
+                    );
+                    None
+                });
+                let nbt = chunk_to_nbt(chunk, self.registry.as_ref(), saved.as_ref())?;
+                region.write_chunk(chunk.x, chunk.z, &nbt)?;
+            }
+            region.save(self.region_path(key))?;
+            let mut regions = self.regions.lock().unwrap();
+            regions.saves += 1;
+            regions.insert(key, Some(Arc::new(region)));
+        }
+        Ok(())
+    }
+}
+/// Block below the world spawn in a gzipped `level.dat`
+fn read_spawn(path: &Path) -> Result<Position> {
+    let file =
+        fs::read(path).map_err(|error| anyhow!("Failed to read {}: {error}", path.display()))?;
+    let nbt = decompress(GZIP, &file)?;
+    let level = nbt::read_compound_limited(&mut Reader::new(nbt.into()), MAX_CHUNK_NBT_SIZE)?
+        .ok_or_else(|| anyhow!("{} is empty", path.display()))?;
+    let data = compound(&level, "Data")?;
+    if let Ok(version) = int(data, "DataVersion") {
+        if version != DATA_VERSION {
+            println!("World was saved with data version {version}, expected {DATA_VERSION}");
+        }
+    }
+    Ok(Position {
+        x: int(data, "SpawnX")?,
+        y: int(data, "SpawnY")? - 1,
+        z: int(data, "SpawnZ")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// Blocks and biomes of every position of a chunk
+    fn contents(chunk: &Chunk) -> Vec<(u32, u32)> {
+        chunk
+            .sections()
+            .iter()
+            .flat_map(|section| {
+                (0..4096).map(|index| {
+                    (
+                        section.block_states().get(index),
+                        section.biomes().get(index / 64),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// A chunk as vanilla saves it, with a block, biome and block entity from a mod
+    fn vanilla_chunk() -> Compound {
+        let widget = Compound::new()
+            .with("Name", "examplemod:widget")
+            .with("Properties", Compound::new().with("facing", "north"));
+        let palette = vec![
+            Tag::Compound(Compound::new().with("Name", "minecraft:bedrock")),
+            Tag::Compound(widget),
+            Tag::Compound(Compound::new().with("Name", "minecraft:stone")),
+        ];
+        // Bedrock at the bottom, the widget at 3, -63, 5 and stone above
+        let mut blocks = BitStorage::new(4, 4096);
+        for index in 256..4096 {
+            blocks.set(index, 2);
+        }
+        blocks.set((16 + 5) * 16 + 3, 1);
+        let mut biomes = BitStorage::new(1, 64);
+        biomes.set(63, 1);
+
+        let section = Compound::new()
+            .with("Y", -4i8)
+            .with("SkyLight", Tag::ByteArray(vec![0; 2048]))
+            .with(
+                "block_states",
+                Compound::new()
+                    .with("palette", palette)
+                    .with("data", Tag::LongArray(blocks.to_longs())),
+            )
+            .with(
+                "biomes",
+                Compound::new()
+                    .with(
+                        "palette",
+                        vec![
+                            Tag::String("minecraft:plains".to_string()),
+                            Tag::String("examplemod:glade".to_string()),
+                        ],
+                    )
+                    .with("data", Tag::LongArray(biomes.to_longs())),
+            );
+        let light = Compound::new()
+            .with("Y", -5i8)
+            .with("SkyLight", Tag::ByteArray(vec![0; 2048]));
+        let block_entity = Compound::new()
+            .with("id", "examplemod:widget")
+            .with("x", 16 + 3)
+            .with("y", -63)
+            .with("z", -32 + 5)
+            .with("keepPacked", Tag::Byte(0))
+            .with("Energy", 7);
+        Compound::new()
+            .with("DataVersion", DATA_VERSION)
+            .with("xPos", 1)
+            .with("yPos", -4)
+            .with("zPos", -2)
+            .with("Status", "minecraft:full")
+            .with("InhabitedTime", 1234i64)
+            .with("isLightOn", true)
+            .with(
+                "structures",
+                Compound::new().with("References", Compound::new()),
+            )
+            .with(
+                "sections",
+                vec![Tag::Compound(light), Tag::Compound(section)],
+            )
+            .with("block_entities", vec![Tag::Compound(block_entity)])
+    }
+
+    fn load(nbt: &Compound) -> (Chunk, Unknown) {
+        let mut unknown = Unknown::default();
+        let chunk = chunk_from_nbt(nbt, &BuiltinBlocks, &mut unknown)
+            .unwrap()
+            .unwrap();
+        (chunk, unknown)
+    }
+
+    #[test]
+    fn vanilla_chunk_survives_saving() {
+        let saved = vanilla_chunk();
+        let (chunk, unknown) = load(&saved);
+        assert_eq!(chunk.get_block(3, -63, 5), STONE);
+        assert!(unknown.blocks.contains("examplemod:widget"));
+        assert!(unknown.biomes.contains("examplemod:glade"));
+        assert!(unknown.block_entities.contains("examplemod:widget"));
+
+        let resaved = chunk_to_nbt(&chunk, &BuiltinBlocks, Some(&saved)).unwrap();
+        let (reloaded, reloaded_unknown) = load(&resaved);
+        assert_eq!(contents(&reloaded), contents(&chunk));
+        assert_eq!(reloaded_unknown, unknown);
+        assert_eq!(resaved.get("InhabitedTime"), Some(&Tag::Long(1234)));
+        assert!(resaved.get("structures").is_some());
+        assert_eq!(resaved.get("block_entities"), saved.get("block_entities"));
+
+        let sections = list(&resaved, "sections").unwrap();
+        let Some(Tag::Compound(section)) = sections.iter().find(
+            |section| matches!(section, Tag::Compound(section) if int(section, "Y").unwrap() == -4),
+        ) else {
+            panic!("Section -4 is missing");
+        };
+        assert!(section.get("SkyLight").is_none());
+        let saved_section = match &list(&saved, "sections").unwrap()[1] {
+            Tag::Compound(section) => section,
+            _ => unreachable!(),
+        };
+        let widget = &list(compound(saved_section, "block_states").unwrap(), "palette").unwrap()[1];
+        assert!(list(compound(section, "block_states").unwrap(), "palette")
+            .unwrap()
+            .contains(widget));
+
+        // Saving again changes nothing
+        let again = chunk_to_nbt(&reloaded, &BuiltinBlocks, Some(&resaved)).unwrap();
+        assert_eq!(again, resaved);
+    }
+
+    #[test]
+    fn replaced_placeholders_save_as_themselves() {
+        let saved = vanilla_chunk();
+        let (mut chunk, _) = load(&saved);
+        chunk.set_block(3, -63, 5, AIR);
+
+        let resaved = chunk_to_nbt(&chunk, &BuiltinBlocks, Some(&saved)).unwrap();
+        let (reloaded, unknown) = load(&resaved);
+        assert_eq!(reloaded.get_block(3, -63, 5), AIR);
+        assert!(unknown.blocks.is_empty());
+        assert!(unknown.block_entities.is_empty());
+        assert_eq!(resaved.get("block_entities"), Some(&Tag::List(Vec::new())));
+    }
+
+    #[test]
+    fn unnamed_block_states_are_not_saved() {
+        let mut chunk = Chunk::new(0, 0, 0);
+        chunk.set_block(0, 0, 0, 30_000);
+        assert!(chunk_to_nbt(&chunk, &BuiltinBlocks, None).is_err());
+    }
+
+    /// A chunk whose NBT takes at least `size` bytes, even compressed
+    fn chunk_nbt(x: i32, size: usize) -> Compound {
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let noise = (0..size / 8)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as i64
+            })
+            .collect::<Vec<_>>();
+        Compound::new()
+            .with("xPos", x)
+            .with("Noise", Tag::LongArray(noise))
+    }
+
+    #[test]
+    fn region_round_trips_and_reuses_sectors() {
+        let mut region = Region::new(None);
+        let small = chunk_nbt(0, 100);
+        let neighbour = chunk_nbt(1, 100);
+        region.write_chunk(0, 0, &small).unwrap();
+        region.write_chunk(1, 0, &neighbour).unwrap();
+        assert_eq!(region.location(0, 0), (2, 1));
+        assert_eq!(region.location(1, 0), (3, 1));
+
+        // Growing moves the chunk past its neighbour, leaving its sector free
+        let large = chunk_nbt(0, 3 * SECTOR_SIZE);
+        region.write_chunk(0, 0, &large).unwrap();
+        assert_eq!(region.location(0, 0), (4, 4));
+        assert_eq!(region.read_chunk(0, 0).unwrap(), Some(large));
+
+        // Shrinking fits it back in the first free sector
+        region.write_chunk(0, 0, &small).unwrap();
+        assert_eq!(region.location(0, 0), (2, 1));
+        // A new chunk fits where the large one was
+        let other = chunk_nbt(2, 2 * SECTOR_SIZE);
+        region.write_chunk(2, 0, &other).unwrap();
+        assert_eq!(region.location(2, 0), (4, 3));
+
+        let path = env::temp_dir().join(format!("region-test-{}.mca", std::process::id()));
+        region.save(&path).unwrap();
+        let reopened = Region::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.read_chunk(0, 0).unwrap(), Some(small));
+        assert_eq!(reopened.read_chunk(1, 0).unwrap(), Some(neighbour));
+        assert_eq!(reopened.read_chunk(2, 0).unwrap(), Some(other));
+        assert_eq!(reopened.read_chunk(3, 0).unwrap(), None);
+        assert!(reopened.timestamp(0, 0) > 0);
+    }
+
+    #[test]
+    fn region_cache_evicts_least_recently_used() {
+        let mut regions = Regions::default();
+        for x in 0..MAX_CACHED_REGIONS as i32 {
+            regions.insert((x, 0), None);
+        }
+        assert!(regions.get((0, 0)).is_some());
+        regions.insert((-1, 0), None);
+        assert_eq!(regions.cached.len(), MAX_CACHED_REGIONS);
+        assert!(regions.get((0, 0)).is_some());
+        assert!(regions.get((1, 0)).is_none());
+        assert!(regions.get((-1, 0)).is_some());
+    }
+}
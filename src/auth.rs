@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use mojang_api::ServerAuthResponse;
+use reqwest::StatusCode;
+
+use crate::{player::offline_uuid, protocol::LoginSuccess};
+
+const HAS_JOINED_URL: &str = "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+/// Decides who a logging in player is
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    /// Whether the connection is encrypted before authenticating. Without encryption there is no
+    /// server hash, and `authenticate` is called with an empty one.
+    fn online_mode(&self) -> bool {
+        true
+    }
+
+    /// Look up the profile of `username`, who should have joined the server identified by
+    /// `server_hash`
+    async fn authenticate(&self, username: &str, server_hash: &str) -> Result<LoginSuccess>;
+}
+
+/// Checks players with Mojang's session server, like an online-mode server
+#[derive(Default)]
+pub struct MojangAuthenticator {
+    client: reqwest::Client,
+}
+#[async_trait]
+impl Authenticator for MojangAuthenticator {
+    async fn authenticate(&self, username: &str, server_hash: &str) -> Result<LoginSuccess> {
+        let response = self
+            .client
+            .get(HAS_JOINED_URL)
+            .query(&[("username", username), ("serverId", server_hash)])
+            .send()
+            .await?;
+        // The session server answers without content if the player did not join
+        if response.status() == StatusCode::NO_CONTENT {
+            return Err(anyhow!("{username} has not joined {server_hash}"));
+        }
+        let response: ServerAuthResponse = serde_json::from_str(&response.text().await?)?;
+        Ok(response.into())
+    }
+}
+
+/// Trusts the username the client sends, like an offline-mode server
+pub struct OfflineAuthenticator;
+#[async_trait]
+impl Authenticator for OfflineAuthenticator {
+    fn online_mode(&self) -> bool {
+        false
+    }
+
+    async fn authenticate(&self, username: &str, _server_hash: &str) -> Result<LoginSuccess> {
+        Ok(LoginSuccess {
+            uuid: offline_uuid(username),
+            username: username.to_string(),
+            properties: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use std::env;
+
+use minecraft_protocol::{capture::Direction, dissect, keylog, pcap};
+
+const DEFAULT_PORT: u16 = 25565;
+
+/// Decode the Minecraft sessions in a pcap capture:
+///
+/// `pcap_import <capture.pcap> [--keylog <file>] [--port <port>]`
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut path = None;
+    let mut keylog_path = None;
+    let mut port = DEFAULT_PORT;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keylog" => keylog_path = args.next(),
+            "--port" => port = args.next().expect("Missing port").parse().unwrap(),
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.expect("Usage: pcap_import <capture.pcap> [--keylog <file>] [--port <port>]");
+
+    let keys = match keylog_path {
+        Some(keylog_path) => keylog::read_file(keylog_path).unwrap(),
+        None => Vec::new(),
+    };
+
+    let sessions = pcap::read_file(&path).unwrap();
+    for session in sessions
+        .iter()
+        .filter(|session| session.server.port() == port)
+    {
+        let started_at = session.started.as_millis() as i64;
+        let shared_secret = keylog::find_secret(&keys, session.client, started_at);
+
+        println!(
+            "Session {} -> {} at {started_at}{}",
+            session.client,
+            session.server,
+            match shared_secret {
+                Some(_) => " (decrypting)",
+                None => "",
+            }
+        );
+
+        let decoded = dissect::decode_session(session, shared_secret).await;
+        for packet in decoded.packets {
+            let arrow = match packet.direction {
+                Direction::Serverbound => "<",
+                Direction::Clientbound => ">",
+            };
+            println!(
+                "  +{:.3}s {arrow} [{:?}] {}",
+                packet.timestamp.as_secs_f64(),
+                packet.state,
+                packet.description
+            );
+        }
+        for note in decoded.notes {
+            println!("  ! {note}");
+        }
+    }
+}
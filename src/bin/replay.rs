@@ -0,0 +1,36 @@
+use std::{env, net::ToSocketAddrs, sync::Arc};
+
+use minecraft_protocol::{
+    auth::OfflineAuthenticator,
+    capture::Capture,
+    crypto::{KeyPair, DEFAULT_KEY_SIZE},
+    replay::Replay,
+};
+
+/// Replay a capture file against a server at `host:port`, or against an in-process offline-mode
+/// connection if no address is given.
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
+        .expect("Usage: replay <capture.mccap> [host:port]");
+
+    let capture = Capture::load(&path).unwrap();
+    println!("Loaded {} records from {path}", capture.records.len());
+    let replay = Replay::new(capture).realtime(true);
+
+    match args.next() {
+        Some(address) => {
+            let address = address.to_socket_addrs().unwrap().next().unwrap();
+            replay.replay_to(address).await.unwrap();
+        }
+        None => {
+            let key_pair = Arc::new(KeyPair::generate(DEFAULT_KEY_SIZE).unwrap());
+            replay
+                .replay_through_connection(key_pair, Arc::new(OfflineAuthenticator))
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+/// A property of a block, like `facing` or `waterlogged`
+#[derive(Debug)]
+pub struct Property {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+/// A block and the range of its states, generated from `blocks.json`
+#[derive(Debug)]
+pub struct Block {
+    pub name: &'static str,
+    /// Sorted by name. The last property changes fastest between consecutive state IDs.
+    pub properties: &'static [Property],
+    first_state: u32,
+    default_state: u32,
+}
+impl Block {
+    /// Look a block up by its identifier, like `minecraft:stone`
+    pub fn by_name(name: &str) -> Option<&'static Block> {
+        BLOCKS_BY_NAME
+            .binary_search_by(|&index| BLOCKS[index as usize].name.cmp(name))
+            .ok()
+            .map(|position| &BLOCKS[BLOCKS_BY_NAME[position] as usize])
+    }
+
+    /// The state a block is placed in when nothing decides otherwise
+    pub fn default_state(&'static self) -> BlockState {
+        BlockState(self.default_state)
+    }
+
+    pub fn state_count(&self) -> u32 {
+        self.properties
+            .iter()
+            .map(|property| property.values.len() as u32)
+            .product()
+    }
+
+    /// Every state of the block, in the order of their IDs
+    pub fn states(&self) -> impl Iterator<Item = BlockState> {
+        (self.first_state..self.first_state + self.state_count()).map(BlockState)
+    }
+
+    /// Number of states between one value of a property and the next
+    fn stride(&self, property: usize) -> u32 {
+        self.properties[property + 1..]
+            .iter()
+            .map(|property| property.values.len() as u32)
+            .product()
+    }
+
+    fn property_index(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| property.name == name)
+    }
+}
+
+/// The ID of a block state, as stored in chunks
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockState(u32);
+impl BlockState {
+    /// A state from its ID, if the ID belongs to a known block
+    pub fn from_id(id: u32) -> Option<BlockState> {
+        let state = BlockState(id);
+        state
+            .try_block()
+            .is_some_and(|block| id < block.first_state + block.state_count())
+            .then_some(state)
+    }
+
+    /// A state from a block's identifier and some of its properties, taking the others from its
+    /// default state. Unknown properties or values give `None`.
+    pub fn from_name(name: &str, properties: &[(&str, &str)]) -> Option<BlockState> {
+        let mut state = Block::by_name(name)?.default_state();
+        for &(property, value) in properties {
+            state = state.with(property, value)?;
+        }
+        Some(state)
+    }
+
+    pub const fn id(self) -> u32 {
+        self.0
+    }
+
+    fn try_block(self) -> Option<&'static Block> {
+        let index = BLOCKS.partition_point(|block| block.first_state <= self.0);
+        BLOCKS.get(index.checked_sub(1)?)
+    }
+
+    pub fn block(self) -> &'static Block {
+        self.try_block().unwrap()
+    }
+
+    /// Index of the value of each property, from the first property on
+    fn value_index(self, block: &Block, property: usize) -> usize {
+        let offset = self.0 - block.first_state;
+        (offset / block.stride(property)) as usize % block.properties[property].values.len()
+    }
+
+    /// Value of a property, if the block has it
+    pub fn get(self, property: &str) -> Option<&'static str> {
+        let block = self.block();
+        let index = block.property_index(property)?;
+        Some(block.properties[index].values[self.value_index(block, index)])
+    }
+
+    /// The same state with a property changed, if the block has the property and value
+    pub fn with(self, property: &str, value: &str) -> Option<BlockState> {
+        let block = self.block();
+        let index = block.property_index(property)?;
+        let values = block.properties[index].values;
+        let new = values.iter().position(|&v| v == value)? as u32;
+        let old = self.value_index(block, index) as u32;
+        let stride = block.stride(index);
+        Some(BlockState(self.0 - old * stride + new * stride))
+    }
+
+    /// Names and values of every property of the state
+    pub fn properties(self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let block = self.block();
+        (0..block.properties.len()).map(move |index| {
+            let property = &block.properties[index];
+            (
+                property.name,
+                property.values[self.value_index(block, index)],
+            )
+        })
+    }
+
+    pub fn is_default(self) -> bool {
+        self.0 == self.block().default_state
+    }
+}
+impl fmt::Debug for BlockState {
+    /// Formats like vanilla, as `minecraft:grass_block[snowy=false]`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(block) = self.try_block() else {
+            return write!(f, "BlockState({})", self.0);
+        };
+        write!(f, "{}", block.name)?;
+        if !block.properties.is_empty() {
+            let properties = self
+                .properties()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_are_default_states() {
+        for (constant, name) in [(AIR, "minecraft:air"), (STONE, "minecraft:stone")] {
+            assert_eq!(Block::by_name(name).unwrap().default_state(), constant);
+            assert_eq!(constant.block().name, name);
+        }
+        assert_eq!(GRASS_BLOCK.get("snowy"), Some("false"));
+    }
+
+    #[test]
+    fn blocks_have_their_states() {
+        assert_eq!(AIR.id(), 0);
+        assert_eq!(STONE.id(), 1);
+        assert_eq!(GRASS_BLOCK.block().state_count(), 2);
+        let stairs = Block::by_name("minecraft:oak_stairs").unwrap();
+        // Four facings, two halves, five shapes and waterlogged or not
+        assert_eq!(stairs.state_count(), 80);
+        assert_eq!(stairs.states().count(), 80);
+    }
+
+    #[test]
+    fn properties_change_the_state() {
+        let snowy = GRASS_BLOCK.with("snowy", "true").unwrap();
+        assert_ne!(snowy, GRASS_BLOCK);
+        assert!(!snowy.is_default());
+        assert_eq!(
+            BlockState::from_name("minecraft:grass_block", &[("snowy", "true")]),
+            Some(snowy)
+        );
+        assert_eq!(format!("{snowy:?}"), "minecraft:grass_block[snowy=true]");
+        assert_eq!(GRASS_BLOCK.with("snowy", "maybe"), None);
+        assert_eq!(BlockState::from_id(snowy.id()), Some(snowy));
+    }
+}
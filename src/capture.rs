@@ -0,0 +1,161 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+
+use crate::{
+    protocol::{ConnectionState, Reader, Writer},
+    stream::Packet,
+};
+
+const MAGIC: &[u8; 6] = b"MCCAP\0";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Serverbound,
+    Clientbound,
+}
+
+/// A single decoded packet in a capture
+#[derive(Debug)]
+pub struct CaptureRecord {
+    /// Time since the start of the capture
+    pub timestamp: Duration,
+    pub direction: Direction,
+    /// State of the connection when the packet was sent or received
+    pub state: ConnectionState,
+    pub packet: Packet,
+}
+
+/// A capture file, with the records of a single connection.
+///
+/// The file starts with a header of the magic `MCCAP\0`, a version byte and the Unix time in
+/// milliseconds at which the capture started. Every record after that is:
+///
+/// * VarLong: microseconds since the start of the capture
+/// * Byte: direction in the lowest bit, state in the bits above it
+/// * VarInt: packet ID
+/// * VarInt: length of the data, followed by the data itself
+#[derive(Debug)]
+pub struct Capture {
+    /// Unix time in milliseconds at which the capture started
+    pub started_at: i64,
+    pub records: Vec<CaptureRecord>,
+}
+impl Capture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Capture> {
+        Capture::try_from(Bytes::from(std::fs::read(path)?))
+    }
+}
+impl TryFrom<Bytes> for Capture {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(anyhow!("Not a capture file"));
+        }
+        let mut cursor = Reader::new(bytes.slice(MAGIC.len()..));
+        let version = cursor.read_u8()?;
+        if version != VERSION {
+            return Err(anyhow!("Unsupported capture version {version}"));
+        }
+        let started_at = cursor.read_i64()?;
+
+        let mut records = Vec::new();
+        while !cursor.is_empty() {
+            let timestamp = Duration::from_micros(cursor.read_varlong()? as u64);
+            let flags = cursor.read_u8()?;
+            let direction = match flags & 1 {
+                0 => Direction::Serverbound,
+                _ => Direction::Clientbound,
+            };
+            let state = state_from_u8(flags >> 1)?;
+            let id = cursor.read_varint()?;
+            let length = cursor.read_varint()? as usize;
+            let data = cursor.read_bytes(length)?;
+            records.push(CaptureRecord {
+                timestamp,
+                direction,
+                state,
+                packet: Packet { id, data },
+            });
+        }
+
+        Ok(Capture {
+            started_at,
+            records,
+        })
+    }
+}
+
+fn state_to_u8(state: ConnectionState) -> u8 {
+    match state {
+        ConnectionState::Handshaking => 0,
+        ConnectionState::Status => 1,
+        ConnectionState::Login => 2,
+        ConnectionState::Play => 3,
+        ConnectionState::Done => 4,
+    }
+}
+
+fn state_from_u8(value: u8) -> Result<ConnectionState> {
+    Ok(match value {
+        0 => ConnectionState::Handshaking,
+        1 => ConnectionState::Status,
+        2 => ConnectionState::Login,
+        3 => ConnectionState::Play,
+        4 => ConnectionState::Done,
+        _ => return Err(anyhow!("Invalid state {value}")),
+    })
+}
+
+/// Appends records to a capture file. Clones write to the same file, so both halves of a
+/// split connection can record into it.
+#[derive(Clone)]
+pub struct CaptureWriter {
+    file: Arc<Mutex<BufWriter<File>>>,
+    start: Instant,
+}
+impl CaptureWriter {
+    pub fn create(path: impl AsRef<Path>) -> Result<CaptureWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let mut header = Writer::new();
+        header.write_raw(MAGIC);
+        header.write_u8(VERSION);
+        header.write_i64(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64);
+        file.write_all(&Bytes::from(header))?;
+
+        Ok(CaptureWriter {
+            file: Arc::new(Mutex::new(file)),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(
+        &self,
+        direction: Direction,
+        state: ConnectionState,
+        packet: &Packet,
+    ) -> Result<()> {
+        let mut writer = Writer::new();
+        writer.write_varlong(self.start.elapsed().as_micros() as i64);
+        writer.write_u8(state_to_u8(state) << 1 | (direction == Direction::Clientbound) as u8);
+        writer.write_varint(packet.id);
+        writer.write_varint(packet.data.len() as i32);
+        writer.write_raw(&packet.data);
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&Bytes::from(writer))?;
+        // Flush every record, so a capture is complete even if the connection task panics
+        file.flush()?;
+        Ok(())
+    }
+}
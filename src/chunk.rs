@@ -0,0 +1,641 @@
+use bytes::Bytes;
+
+use crate::{
+    blocks,
+    nbt::{Compound, Tag},
+    protocol::{
+        play::{
+            clientbound::{ChunkBlockEntity, ChunkDataAndUpdateLight, LightData},
+            registry_codec::BIOMES,
+        },
+        Writer,
+    },
+};
+
+/// Lowest block of the overworld
+pub const MIN_Y: i32 = -64;
+/// Height of the overworld in blocks
+pub const HEIGHT: usize = 384;
+pub const SECTION_COUNT: usize = HEIGHT / 16;
+pub const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+/// Biomes are stored for every 4x4x4 blocks
+pub const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
+
+/// Block state of air, which does not count towards a section's block count
+pub const AIR: u32 = blocks::AIR.id();
+pub const STONE: u32 = blocks::STONE.id();
+/// Grass block with `snowy=false`
+pub const GRASS_BLOCK: u32 = blocks::GRASS_BLOCK.id();
+pub const DIRT: u32 = blocks::DIRT.id();
+pub const BEDROCK: u32 = blocks::BEDROCK.id();
+/// Bits needed for the ID of any block state in 1.19.4, as sent by direct palettes
+pub const BLOCK_STATE_BITS: u8 = 15;
+
+/// Values of a fixed number of bits packed into longs, without spanning two longs
+#[derive(Debug, Clone)]
+pub struct BitStorage {
+    bits: u8,
+    len: usize,
+    data: Vec<u64>,
+}
+impl BitStorage {
+    pub fn new(bits: u8, len: usize) -> BitStorage {
+        let per_long = 64 / bits as usize;
+        BitStorage {
+            bits,
+            len,
+            data: vec![0; len.div_ceil(per_long)],
+        }
+    }
+
+    /// Values already packed into longs, like in region files, or `None` if there are not
+    /// exactly enough longs for `len` values
+    pub fn from_longs(bits: u8, len: usize, longs: &[i64]) -> Option<BitStorage> {
+        let per_long = 64 / bits as usize;
+        (longs.len() == len.div_ceil(per_long)).then(|| BitStorage {
+            bits,
+            len,
+            data: longs.iter().map(|&long| long as u64).collect(),
+        })
+    }
+
+    fn per_long(&self) -> usize {
+        64 / self.bits as usize
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        let per_long = self.per_long();
+        let offset = (index % per_long) * self.bits as usize;
+        ((self.data[index / per_long] >> offset) & self.mask()) as u32
+    }
+
+    pub fn set(&mut self, index: usize, value: u32) {
+        let per_long = self.per_long();
+        let offset = (index % per_long) * self.bits as usize;
+        let mask = self.mask();
+        let long = &mut self.data[index / per_long];
+        *long = (*long & !(mask << offset)) | ((value as u64 & mask) << offset);
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn data(&self) -> &[u64] {
+        &self.data
+    }
+
+    /// The longs as signed NBT or protocol longs
+    pub fn to_longs(&self) -> Vec<i64> {
+        self.data.iter().map(|&long| long as i64).collect()
+    }
+}
+
+/// What a [`PalettedContainer`] holds, which decides its size and palette limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    BlockStates,
+    Biomes,
+}
+impl ContainerKind {
+    /// Number of entries in a section
+    pub fn entries(self) -> usize {
+        match self {
+            ContainerKind::BlockStates => BLOCKS_PER_SECTION,
+            ContainerKind::Biomes => BIOMES_PER_SECTION,
+        }
+    }
+
+    /// Fewest bits of an indirect palette
+    fn min_bits(self) -> u8 {
+        match self {
+            ContainerKind::BlockStates => 4,
+            ContainerKind::Biomes => 1,
+        }
+    }
+
+    /// Most bits of an indirect palette, above which IDs are stored directly
+    fn max_indirect_bits(self) -> u8 {
+        match self {
+            ContainerKind::BlockStates => 8,
+            ContainerKind::Biomes => 3,
+        }
+    }
+
+    fn direct_bits(self) -> u8 {
+        match self {
+            ContainerKind::BlockStates => BLOCK_STATE_BITS,
+            ContainerKind::Biomes => bits_for(BIOMES.len()),
+        }
+    }
+}
+
+/// Bits needed to store values below `count`
+fn bits_for(count: usize) -> u8 {
+    (usize::BITS - count.saturating_sub(1).leading_zeros()).max(1) as u8
+}
+
+#[derive(Debug, Clone)]
+enum Palette {
+    /// Every entry has the same value, so nothing is stored
+    Single(u32),
+    Indirect(Vec<u32>),
+    /// Entries are the IDs themselves
+    Direct,
+}
+
+/// Block states or biomes of a section, stored as indices into a palette like vanilla does
+#[derive(Debug, Clone)]
+pub struct PalettedContainer {
+    kind: ContainerKind,
+    palette: Palette,
+    storage: Option<BitStorage>,
+}
+impl PalettedContainer {
+    /// A container where every entry is `value`
+    pub fn single(kind: ContainerKind, value: u32) -> PalettedContainer {
+        PalettedContainer {
+            kind,
+            palette: Palette::Single(value),
+            storage: None,
+        }
+    }
+
+    /// A container with every entry given, in YZX order
+    pub fn from_values(kind: ContainerKind, values: &[u32]) -> PalettedContainer {
+        let mut container = PalettedContainer::single(kind, values.first().copied().unwrap_or(0));
+        for (index, &value) in values.iter().enumerate().take(kind.entries()) {
+            container.set(index, value);
+        }
+        container
+    }
+
+    pub fn kind(&self) -> ContainerKind {
+        self.kind
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        match (&self.palette, &self.storage) {
+            (Palette::Single(value), _) => *value,
+            (Palette::Indirect(palette), Some(storage)) => palette[storage.get(index) as usize],
+            (Palette::Direct, Some(storage)) => storage.get(index),
+            _ => unreachable!("Only single-valued containers have no storage"),
+        }
+    }
+
+    /// Set an entry, returning its previous value
+    pub fn set(&mut self, index: usize, value: u32) -> u32 {
+        let previous = self.get(index);
+        if previous == value {
+            return previous;
+        }
+        let bits = self.bits();
+        let stored = match &mut self.palette {
+            Palette::Single(_) => None,
+            Palette::Indirect(palette) => match palette.iter().position(|&id| id == value) {
+                Some(palette_index) => Some(palette_index as u32),
+                None if palette.len() < 1 << bits => {
+                    palette.push(value);
+                    Some(palette.len() as u32 - 1)
+                }
+                None => None,
+            },
+            Palette::Direct => Some(value),
+        };
+        match (stored, &mut self.storage) {
+            (Some(stored), Some(storage)) => storage.set(index, stored),
+            _ => {
+                // The palette is full, so make room for one more value
+                let entries = match &self.palette {
+                    Palette::Indirect(palette) => palette.len() + 1,
+                    _ => 2,
+                };
+                self.resize(entries);
+                self.set(index, value);
+            }
+        }
+        previous
+    }
+
+    /// Bits per entry in the data, 0 for single-valued containers
+    fn bits(&self) -> u8 {
+        self.storage.as_ref().map_or(0, BitStorage::bits)
+    }
+
+    /// Rebuild the container with room for `entries` different values
+    fn resize(&mut self, entries: usize) {
+        let values = (0..self.kind.entries())
+            .map(|index| self.get(index))
+            .collect::<Vec<_>>();
+        let bits = bits_for(entries).max(self.kind.min_bits());
+
+        if bits > self.kind.max_indirect_bits() {
+            let mut storage = BitStorage::new(self.kind.direct_bits(), self.kind.entries());
+            for (index, &value) in values.iter().enumerate() {
+                storage.set(index, value);
+            }
+            self.palette = Palette::Direct;
+            self.storage = Some(storage);
+        } else {
+            let mut palette = Vec::new();
+            let mut storage = BitStorage::new(bits, self.kind.entries());
+            for (index, &value) in values.iter().enumerate() {
+                let palette_index = match palette.iter().position(|&id| id == value) {
+                    Some(palette_index) => palette_index,
+                    None => {
+                        palette.push(value);
+                        palette.len() - 1
+                    }
+                };
+                storage.set(index, palette_index as u32);
+            }
+            self.palette = Palette::Indirect(palette);
+            self.storage = Some(storage);
+        }
+    }
+
+    /// Count the entries for which `predicate` holds
+    pub fn count(&self, predicate: impl Fn(u32) -> bool) -> usize {
+        match &self.palette {
+            Palette::Single(value) if predicate(*value) => self.kind.entries(),
+            Palette::Single(_) => 0,
+            _ => (0..self.kind.entries())
+                .filter(|&index| predicate(self.get(index)))
+                .count(),
+        }
+    }
+
+    /// Write the container as sent in Chunk Data
+    pub fn write(&self, writer: &mut Writer) {
+        match &self.palette {
+            Palette::Single(value) => {
+                writer.write_u8(0);
+                writer.write_varint(*value as i32);
+            }
+            Palette::Indirect(palette) => {
+                writer.write_u8(self.bits());
+                writer.write_varint(palette.len() as i32);
+                for &id in palette {
+                    writer.write_varint(id as i32);
+                }
+            }
+            Palette::Direct => writer.write_u8(self.bits()),
+        }
+        let data = self.storage.as_ref().map_or(&[][..], BitStorage::data);
+        writer.write_varint(data.len() as i32);
+        for &long in data {
+            writer.write_i64(long as i64);
+        }
+    }
+}
+
+/// 16x16x16 blocks of a chunk
+#[derive(Debug, Clone)]
+pub struct ChunkSection {
+    /// Number of blocks that are not air
+    block_count: u16,
+    block_states: PalettedContainer,
+    biomes: PalettedContainer,
+}
+impl ChunkSection {
+    /// A section of only air in `biome`
+    pub fn new(biome: u32) -> ChunkSection {
+        ChunkSection {
+            block_count: 0,
+            block_states: PalettedContainer::single(ContainerKind::BlockStates, AIR),
+            biomes: PalettedContainer::single(ContainerKind::Biomes, biome),
+        }
+    }
+
+    pub fn from_containers(
+        block_states: PalettedContainer,
+        biomes: PalettedContainer,
+    ) -> ChunkSection {
+        ChunkSection {
+            block_count: block_states.count(|state| state != AIR) as u16,
+            block_states,
+            biomes,
+        }
+    }
+
+    fn block_index(x: usize, y: usize, z: usize) -> usize {
+        (y * 16 + z) * 16 + x
+    }
+
+    /// Block state at coordinates from 0 to 15 inside the section
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.block_states.get(Self::block_index(x, y, z))
+    }
+
+    /// Set a block state, returning the previous one
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: u32) -> u32 {
+        let previous = self.block_states.set(Self::block_index(x, y, z), state);
+        if previous == AIR && state != AIR {
+            self.block_count += 1;
+        } else if previous != AIR && state == AIR {
+            self.block_count -= 1;
+        }
+        previous
+    }
+
+    /// Biome at coordinates from 0 to 3, for every 4x4x4 blocks
+    pub fn get_biome(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.biomes.get((y * 4 + z) * 4 + x)
+    }
+
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: u32) {
+        self.biomes.set((y * 4 + z) * 4 + x, biome);
+    }
+
+    pub fn block_count(&self) -> u16 {
+        self.block_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.block_count == 0
+    }
+
+    pub fn block_states(&self) -> &PalettedContainer {
+        &self.block_states
+    }
+    pub fn biomes(&self) -> &PalettedContainer {
+        &self.biomes
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_i16(self.block_count as i16);
+        self.block_states.write(writer);
+        self.biomes.write(writer);
+    }
+}
+
+/// A 16 block wide column of sections, from [`MIN_Y`] to the top of the world
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    sections: Vec<ChunkSection>,
+    pub block_entities: Vec<ChunkBlockEntity>,
+}
+impl Chunk {
+    /// A chunk of only air in `biome`
+    pub fn new(x: i32, z: i32, biome: u32) -> Chunk {
+        Chunk {
+            x,
+            z,
+            sections: vec![ChunkSection::new(biome); SECTION_COUNT],
+            block_entities: Vec::new(),
+        }
+    }
+
+    /// Sections from the bottom of the world up, or `None` if there are not [`SECTION_COUNT`]
+    pub fn from_sections(x: i32, z: i32, sections: Vec<ChunkSection>) -> Option<Chunk> {
+        (sections.len() == SECTION_COUNT).then_some(Chunk {
+            x,
+            z,
+            sections,
+            block_entities: Vec::new(),
+        })
+    }
+
+    pub fn sections(&self) -> &[ChunkSection] {
+        &self.sections
+    }
+    pub fn sections_mut(&mut self) -> &mut [ChunkSection] {
+        &mut self.sections
+    }
+
+    /// Section and Y inside it of a world Y coordinate, if it is inside the world
+    fn locate(y: i32) -> Option<(usize, usize)> {
+        let y = usize::try_from(y - MIN_Y).ok().filter(|&y| y < HEIGHT)?;
+        Some((y / 16, y % 16))
+    }
+
+    /// Block state at X and Z from 0 to 15 and a world Y, which is air outside the world
+    pub fn get_block(&self, x: usize, y: i32, z: usize) -> u32 {
+        match Self::locate(y) {
+            Some((section, y)) => self.sections[section].get_block(x, y, z),
+            None => AIR,
+        }
+    }
+
+    /// Set a block state, returning the previous one. Blocks outside the world are ignored.
+    pub fn set_block(&mut self, x: usize, y: i32, z: usize, state: u32) -> u32 {
+        match Self::locate(y) {
+            Some((section, y)) => self.sections[section].set_block(x, y, z, state),
+            None => AIR,
+        }
+    }
+
+    /// Biome of the 4x4x4 blocks around a block
+    pub fn get_biome(&self, x: usize, y: i32, z: usize) -> u32 {
+        let (section, y) = Self::locate(y).unwrap_or((0, 0));
+        self.sections[section].get_biome(x / 4, y / 4, z / 4)
+    }
+
+    /// Set the biome of every block in the chunk
+    pub fn fill_biome(&mut self, biome: u32) {
+        for section in &mut self.sections {
+            section.biomes = PalettedContainer::single(ContainerKind::Biomes, biome);
+        }
+    }
+
+    /// Y above the highest block that is not air in a column, or [`MIN_Y`] if there is none
+    pub fn height(&self, x: usize, z: usize) -> i32 {
+        for (index, section) in self.sections.iter().enumerate().rev() {
+            if section.is_empty() {
+                continue;
+            }
+            for y in (0..16).rev() {
+                if section.get_block(x, y, z) != AIR {
+                    return MIN_Y + (index * 16 + y) as i32 + 1;
+                }
+            }
+        }
+        MIN_Y
+    }
+
+    /// `MOTION_BLOCKING` and `WORLD_SURFACE` heightmaps. Both are approximated by the highest
+    /// block that is not air, which only differs for blocks without collision like flowers.
+    pub fn heightmaps(&self) -> Compound {
+        let mut storage = BitStorage::new(bits_for(HEIGHT + 1), 16 * 16);
+        for z in 0..16 {
+            for x in 0..16 {
+                storage.set(z * 16 + x, (self.height(x, z) - MIN_Y) as u32);
+            }
+        }
+        let longs = Tag::LongArray(storage.to_longs());
+        Compound::new()
+            .with("MOTION_BLOCKING", longs.clone())
+            .with("WORLD_SURFACE", longs)
+    }
+
+    /// Every section, as in the data of Chunk Data
+    pub fn encode_sections(&self) -> Bytes {
+        // Empty sections take up 8 bytes
+        let mut writer = Writer::with_capacity(SECTION_COUNT * 8);
+        for section in &self.sections {
+            section.write(&mut writer);
+        }
+        writer.into()
+    }
+
+    pub fn to_packet(&self, light: LightData) -> ChunkDataAndUpdateLight {
+        ChunkDataAndUpdateLight {
+            x: self.x,
+            z: self.z,
+            heightmaps: self.heightmaps(),
+            data: self.encode_sections(),
+            block_entities: self.block_entities.clone(),
+            light,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_storage_round_trips() {
+        for bits in [4, 5, 8, 15] {
+            let mut storage = BitStorage::new(bits, BLOCKS_PER_SECTION);
+            let per_long = 64 / bits as usize;
+            assert_eq!(storage.data().len(), BLOCKS_PER_SECTION.div_ceil(per_long));
+
+            let mask = (1 << bits) - 1;
+            let value = |index: usize| (index as u32).wrapping_mul(2_654_435_761) & mask;
+            for index in 0..BLOCKS_PER_SECTION {
+                storage.set(index, value(index));
+            }
+            for index in 0..BLOCKS_PER_SECTION {
+                assert_eq!(storage.get(index), value(index), "{bits} bits at {index}");
+            }
+
+            // Values never span two longs, so the bits left over at the top stay empty
+            let unused = 64 - per_long * bits as usize;
+            if unused > 0 {
+                assert!(storage.data().iter().all(|long| long >> (64 - unused) == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn bit_storage_packs_like_vanilla() {
+        let mut storage = BitStorage::new(4, 16);
+        for index in 0..16 {
+            storage.set(index, index as u32);
+        }
+        assert_eq!(storage.data(), &[0xFEDC_BA98_7654_3210]);
+
+        // 5 bits fit 12 values in a long, so the 13th starts the next one
+        let mut storage = BitStorage::new(5, 13);
+        storage.set(11, 0b11111);
+        storage.set(12, 1);
+        assert_eq!(storage.data(), &[0b11111 << 55, 1]);
+    }
+
+    #[test]
+    fn bit_storage_set_leaves_neighbours() {
+        let mut storage = BitStorage::new(5, 24);
+        for index in 0..24 {
+            storage.set(index, 31);
+        }
+        storage.set(12, 0);
+        assert_eq!(storage.get(11), 31);
+        assert_eq!(storage.get(12), 0);
+        assert_eq!(storage.get(13), 31);
+    }
+
+    #[test]
+    fn from_longs_checks_the_length() {
+        // 4096 values of 5 bits take 342 longs, the last one only partly used
+        let longs = vec![0; 342];
+        assert!(BitStorage::from_longs(5, BLOCKS_PER_SECTION, &longs).is_some());
+        assert!(BitStorage::from_longs(5, BLOCKS_PER_SECTION, &longs[1..]).is_none());
+        assert!(BitStorage::from_longs(5, BLOCKS_PER_SECTION, &[0; 343]).is_none());
+        assert!(BitStorage::from_longs(4, BLOCKS_PER_SECTION, &[0; 256]).is_some());
+        assert!(BitStorage::from_longs(15, BLOCKS_PER_SECTION, &[0; 1024]).is_some());
+
+        let storage = BitStorage::from_longs(4, 16, &[0xFEDC_BA98_7654_3210_u64 as i64]).unwrap();
+        assert_eq!(storage.get(15), 15);
+    }
+
+    #[test]
+    fn palette_grows_from_single_to_direct() {
+        let mut container = PalettedContainer::single(ContainerKind::BlockStates, AIR);
+        assert!(matches!(container.palette, Palette::Single(AIR)));
+        assert_eq!(container.bits(), 0);
+
+        let mut expected = vec![AIR; BLOCKS_PER_SECTION];
+        let mut set = |container: &mut PalettedContainer, index: usize, value: u32| {
+            assert_eq!(container.set(index, value), expected[index]);
+            expected[index] = value;
+        };
+
+        // Setting the value it already has keeps a single-valued container
+        set(&mut container, 0, AIR);
+        assert!(matches!(container.palette, Palette::Single(AIR)));
+
+        set(&mut container, 0, STONE);
+        assert!(matches!(&container.palette, Palette::Indirect(palette) if palette.len() == 2));
+        assert_eq!(container.bits(), 4);
+
+        // Distinct values up to 16 fit 4 bits, then the palette grows a bit at a time
+        for (count, bits) in [(16, 4), (17, 5), (32, 5), (33, 6), (256, 8)] {
+            for value in 2..count {
+                set(&mut container, value as usize * 3, 100 + value);
+            }
+            assert!(
+                matches!(&container.palette, Palette::Indirect(palette) if palette.len() == count as usize)
+            );
+            assert_eq!(container.bits(), bits, "{count} values");
+        }
+
+        // More than 256 values are stored directly
+        set(&mut container, 4000, 20_000);
+        assert!(matches!(container.palette, Palette::Direct));
+        assert_eq!(container.bits(), BLOCK_STATE_BITS);
+
+        for (index, &value) in expected.iter().enumerate() {
+            assert_eq!(container.get(index), value, "entry {index}");
+        }
+        assert_eq!(container.count(|state| state != AIR), 256);
+    }
+
+    #[test]
+    fn biome_palette_limits() {
+        let mut container = PalettedContainer::single(ContainerKind::Biomes, 0);
+        container.set(0, 1);
+        assert_eq!(container.bits(), 1);
+        for biome in 2..8 {
+            container.set(biome as usize, biome);
+        }
+        assert_eq!(container.bits(), 3);
+        container.set(8, 8);
+        assert!(matches!(container.palette, Palette::Direct));
+        assert_eq!(container.bits(), bits_for(BIOMES.len()));
+    }
+
+    #[test]
+    fn from_values_matches_set() {
+        let values = (0..BLOCKS_PER_SECTION as u32)
+            .map(|index| index % 40)
+            .collect::<Vec<_>>();
+        let container = PalettedContainer::from_values(ContainerKind::BlockStates, &values);
+        assert_eq!(container.bits(), 6);
+        for (index, &value) in values.iter().enumerate() {
+            assert_eq!(container.get(index), value);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Chunks sent in every direction around the player, like `view-distance` in
+/// `server.properties`
+pub const DEFAULT_VIEW_DISTANCE: i32 = 8;
+/// The client does not render fewer chunks than this
+pub const MIN_VIEW_DISTANCE: i32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ViewConfig {
+    /// Most chunks sent in every direction around the player. Clients that ask for fewer get
+    /// fewer.
+    pub view_distance: i32,
+    /// Chunks sent every tick at most, so a player moving quickly does not stall their
+    /// connection
+    pub chunks_per_tick: usize,
+}
+impl Default for ViewConfig {
+    fn default() -> Self {
+        ViewConfig {
+            view_distance: DEFAULT_VIEW_DISTANCE,
+            chunks_per_tick: 16,
+        }
+    }
+}
+
+/// Keeps track of the chunks a client has, and which to send or unload as the player moves.
+/// Chunks are in view if they are at most the view distance away from the center chunk on
+/// both axes, like the client checks.
+#[derive(Debug, Clone)]
+pub struct ChunkManager {
+    center: (i32, i32),
+    view_distance: i32,
+    chunks_per_tick: usize,
+    /// Chunks the client has been sent
+    loaded: HashSet<(i32, i32)>,
+    /// Chunks in view still to be sent, closest first
+    pending: VecDeque<(i32, i32)>,
+}
+impl ChunkManager {
+    pub fn new(center: (i32, i32), config: ViewConfig) -> ChunkManager {
+        let mut manager = ChunkManager {
+            center,
+            view_distance: config.view_distance.max(MIN_VIEW_DISTANCE),
+            chunks_per_tick: config.chunks_per_tick,
+            loaded: HashSet::new(),
+            pending: VecDeque::new(),
+        };
+        manager.update();
+        manager
+    }
+
+    /// The chunk the player is in
+    pub fn center(&self) -> (i32, i32) {
+        self.center
+    }
+
+    pub fn view_distance(&self) -> i32 {
+        self.view_distance
+    }
+
+    pub fn in_view(&self, (x, z): (i32, i32)) -> bool {
+        (x - self.center.0).abs() <= self.view_distance
+            && (z - self.center.1).abs() <= self.view_distance
+    }
+
+    pub fn is_loaded(&self, chunk: (i32, i32)) -> bool {
+        self.loaded.contains(&chunk)
+    }
+
+    /// Chunks in view that were not sent yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Move the player to a chunk. Returns the chunks to unload if it is a new center chunk,
+    /// or `None` if the player is still in the same one.
+    pub fn move_to(&mut self, center: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        if center == self.center {
+            return None;
+        }
+        self.center = center;
+        Some(self.update())
+    }
+
+    /// Change the view distance, returning the chunks to unload
+    pub fn set_view_distance(&mut self, view_distance: i32) -> Vec<(i32, i32)> {
+        let view_distance = view_distance.max(MIN_VIEW_DISTANCE);
+        if view_distance == self.view_distance {
+            return Vec::new();
+        }
+        self.view_distance = view_distance;
+        self.update()
+    }
+
+    /// Forget the chunks out of view and queue the ones in view the client does not have,
+    /// returning the forgotten ones
+    fn update(&mut self) -> Vec<(i32, i32)> {
+        let unload = self
+            .loaded
+            .iter()
+            .copied()
+            .filter(|&chunk| !self.in_view(chunk))
+            .collect::<Vec<_>>();
+        for chunk in &unload {
+            self.loaded.remove(chunk);
+        }
+        self.pending = spiral(self.center, self.view_distance)
+            .filter(|chunk| !self.loaded.contains(chunk))
+            .collect();
+        unload
+    }
+
+    /// The chunks to send this tick, which are counted as loaded from now on
+    pub fn next_chunks(&mut self) -> Vec<(i32, i32)> {
+        let count = self.chunks_per_tick.min(self.pending.len());
+        let chunks = self.pending.drain(..count).collect::<Vec<_>>();
+        self.loaded.extend(&chunks);
+        chunks
+    }
+}
+
+/// Chunks around `center` at most `radius` away on both axes, going around it ring by ring
+pub fn spiral(center: (i32, i32), radius: i32) -> impl Iterator<Item = (i32, i32)> {
+    let (x, z) = center;
+    (0..=radius).flat_map(move |ring| {
+        // The edges of the ring, clockwise from its corner at -X -Z without repeating corners
+        let top = (-ring..=ring).map(move |dx| (x + dx, z - ring));
+        let right = (-ring + 1..=ring).map(move |dz| (x + ring, z + dz));
+        let bottom = (-ring..ring).rev().map(move |dx| (x + dx, z + ring));
+        let left = (-ring + 1..ring).rev().map(move |dz| (x - ring, z + dz));
+        top.chain(right).chain(bottom).chain(left)
+    })
+}
@@ -0,0 +1,285 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use rand::{RngCore, SeedableRng};
+use reqwest::StatusCode;
+use serde_json::json;
+use tokio::net::TcpStream;
+use uuid::Uuid;
+
+use crate::{
+    player,
+    protocol::{
+        ConnectionState, EncryptionRequest, EncryptionResponse, Handshake, LoginDisconnect,
+        LoginPluginRequest, LoginPluginResponse, LoginStart, LoginSuccess, ServerStatus,
+        SetCompression, StatusPing, StatusPong, StatusRequest, StatusResponse, PROTOCOL_VERSION,
+    },
+    stream::{Packet, PacketStream},
+};
+
+/// The account a [`Client`] logs in with
+#[derive(Debug, Clone)]
+pub enum Account {
+    /// For servers with `online-mode=false`, the UUID is derived from the username
+    Offline { username: String },
+    /// A Minecraft account with an access token for the session server
+    Online {
+        username: String,
+        uuid: Uuid,
+        access_token: String,
+    },
+}
+impl Account {
+    pub fn offline(username: &str) -> Account {
+        Account::Offline {
+            username: username.to_string(),
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        match self {
+            Account::Offline { username } => username,
+            Account::Online { username, .. } => username,
+        }
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        match self {
+            Account::Offline { username } => player::offline_uuid(username),
+            Account::Online { uuid, .. } => *uuid,
+        }
+    }
+}
+
+/// The client side of the protocol, connecting to a server
+pub struct Client {
+    stream: PacketStream,
+    host: String,
+    port: u16,
+    state: ConnectionState,
+}
+impl Client {
+    pub async fn connect(host: &str, port: u16) -> Result<Client> {
+        let stream = TcpStream::connect((host, port)).await?;
+
+        Ok(Client {
+            stream: PacketStream::new(stream)?,
+            host: host.to_string(),
+            port,
+            state: ConnectionState::Handshaking,
+        })
+    }
+
+    pub fn state(&self) -> &ConnectionState {
+        &self.state
+    }
+
+    pub async fn read_packet(&mut self) -> Result<Packet> {
+        self.stream.read_packet().await
+    }
+
+    pub async fn write_packet(&mut self, packet: Packet) -> Result<()> {
+        self.stream.write_packet(packet).await
+    }
+
+    /// Give up the client, keeping the stream with its encryption state
+    pub fn into_stream(self) -> PacketStream {
+        self.stream
+    }
+
+    async fn handshake(&mut self, next_state: ConnectionState) -> Result<()> {
+        let handshake = Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            server_address: self.host.clone(),
+            server_port: self.port,
+            next_state,
+        };
+        println!("> {handshake:?}");
+        self.state = match handshake.next_state {
+            ConnectionState::Status => ConnectionState::Status,
+            _ => ConnectionState::Login,
+        };
+
+        let packet = Packet {
+            id: 0x00,
+            data: handshake.try_into()?,
+        };
+        self.write_packet(packet).await
+    }
+
+    /// Request the server's status and measure the round-trip time of a ping
+    pub async fn status(mut self) -> Result<(ServerStatus, Duration)> {
+        self.handshake(ConnectionState::Status).await?;
+
+        let request = StatusRequest;
+        println!("> {request:?}");
+        let packet = Packet {
+            id: 0x00,
+            data: request.into(),
+        };
+        self.write_packet(packet).await?;
+
+        let packet = self.read_packet().await?;
+        let response = match packet.id {
+            0x00 => StatusResponse::try_from(packet.data)?,
+            _ => return Err(anyhow!("Invalid packet id")),
+        };
+        println!("< {response:?}");
+        let status = response.status()?;
+
+        let ping = StatusPing {
+            payload: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64,
+        };
+        let payload = ping.payload;
+        println!("> {ping:?}");
+        let packet = Packet {
+            id: 0x01,
+            data: ping.into(),
+        };
+        let start = Instant::now();
+        self.write_packet(packet).await?;
+
+        let packet = self.read_packet().await?;
+        let latency = start.elapsed();
+        let pong = match packet.id {
+            0x01 => StatusPong::try_from(packet.data)?,
+            _ => return Err(anyhow!("Invalid packet id")),
+        };
+        println!("< {pong:?}");
+        if pong.payload != payload {
+            return Err(anyhow!("Pong payload does not match ping"));
+        }
+
+        self.state = ConnectionState::Done;
+        Ok((status, latency))
+    }
+
+    /// Log in to the server, returning once the connection is in the Play state
+    pub async fn login(&mut self, account: &Account) -> Result<LoginSuccess> {
+        self.handshake(ConnectionState::Login).await?;
+
+        let login_start = LoginStart {
+            username: account.username().to_string(),
+            uuid: Some(account.uuid()),
+        };
+        println!("> {login_start:?}");
+        let packet = Packet {
+            id: 0x00,
+            data: login_start.into(),
+        };
+        self.write_packet(packet).await?;
+
+        loop {
+            let packet = self.read_packet().await?;
+            match packet.id {
+                0x00 => {
+                    let disconnect = LoginDisconnect::try_from(packet.data)?;
+                    println!("< {disconnect:?}");
+                    self.state = ConnectionState::Done;
+                    return Err(anyhow!("Disconnected: {}", disconnect.reason));
+                }
+                0x01 => {
+                    self.handle_encryption_request(
+                        EncryptionRequest::try_from(packet.data)?,
+                        account,
+                    )
+                    .await?
+                }
+                0x02 => {
+                    let login_success = LoginSuccess::try_from(packet.data)?;
+                    println!("< {login_success:?}");
+                    self.state = ConnectionState::Play;
+                    return Ok(login_success);
+                }
+                0x03 => self.handle_set_compression(SetCompression::try_from(packet.data)?),
+                0x04 => {
+                    self.handle_login_plugin_request(LoginPluginRequest::try_from(packet.data)?)
+                        .await?
+                }
+                _ => return Err(anyhow!("Invalid packet id")),
+            }
+        }
+    }
+
+    async fn handle_encryption_request(
+        &mut self,
+        request: EncryptionRequest,
+        account: &Account,
+    ) -> Result<()> {
+        println!("< {request:?}");
+
+        let mut rng = rand::rngs::StdRng::from_entropy();
+        let mut shared_secret = [0; 16];
+        rng.fill_bytes(&mut shared_secret);
+
+        match account {
+            Account::Offline { .. } => {
+                return Err(anyhow!(
+                    "Server is in online mode, an online account is required"
+                ))
+            }
+            Account::Online {
+                uuid, access_token, ..
+            } => {
+                let server_hash =
+                    mojang_api::server_hash(&request.server_id, shared_secret, &request.public_key);
+                join_server(access_token, uuid, &server_hash).await?;
+            }
+        }
+
+        let response = EncryptionResponse::encrypt(&request, &shared_secret)?;
+        println!("> {response:?}");
+        let packet = Packet {
+            id: 0x01,
+            data: response.into(),
+        };
+        self.write_packet(packet).await?;
+
+        self.stream.enable_encryption(&shared_secret);
+
+        Ok(())
+    }
+
+    fn handle_set_compression(&mut self, set_compression: SetCompression) {
+        println!("< {set_compression:?}");
+
+        let threshold = usize::try_from(set_compression.threshold).ok();
+        self.stream.set_compression(threshold);
+    }
+
+    async fn handle_login_plugin_request(&mut self, request: LoginPluginRequest) -> Result<()> {
+        println!("< {request:?}");
+
+        let response = LoginPluginResponse::not_understood(&request);
+        println!("> {response:?}");
+        let packet = Packet {
+            id: 0x02,
+            data: response.into(),
+        };
+        self.write_packet(packet).await
+    }
+}
+
+/// Tell the session server we are joining, so the server's `hasJoined` check succeeds
+async fn join_server(access_token: &str, uuid: &Uuid, server_hash: &str) -> Result<()> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://sessionserver.mojang.com/session/minecraft/join")
+        .header("Content-Type", "application/json")
+        .body(
+            json!({
+                "accessToken": access_token,
+                "selectedProfile": uuid.simple().to_string(),
+                "serverId": server_hash,
+            })
+            .to_string(),
+        )
+        .send()
+        .await?;
+
+    if response.status() != StatusCode::NO_CONTENT {
+        return Err(anyhow!("Failed to join server: {}", response.text().await?));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::io::{Read, Write};
+
+use aes::cipher::{AsyncStreamCipher, NewCipher};
+use anyhow::{anyhow, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    protocol::{put_varint, varint_len, Reader},
+    stream::{AesCfb8, Packet},
+};
+
+/// Largest frame the length prefix can describe, a VarInt of at most 3 bytes
+pub const MAX_FRAME_SIZE: usize = (1 << 21) - 1;
+/// Largest packet the vanilla server accepts after decompression
+pub const MAX_DECOMPRESSED_SIZE: usize = 1 << 23;
+/// Bytes taken by the length prefix of the largest frame
+const FRAME_LENGTH_SIZE: usize = 3;
+
+/// Frames [`Packet`]s with their length prefix, and applies the compression and encryption state
+/// of the connection. Use it with `Framed` for both directions of a stream, or give each half
+/// of a split stream its own codec.
+pub struct MinecraftCodec {
+    max_packet_size: usize,
+    compression_threshold: Option<usize>,
+    encryptor: Option<AesCfb8>,
+    decryptor: Option<AesCfb8>,
+    /// How many bytes at the start of the read buffer are already decrypted
+    decrypted: usize,
+}
+impl MinecraftCodec {
+    pub fn new() -> Self {
+        MinecraftCodec {
+            max_packet_size: MAX_DECOMPRESSED_SIZE,
+            compression_threshold: None,
+            encryptor: None,
+            decryptor: None,
+            decrypted: 0,
+        }
+    }
+
+    /// Reject packets longer than `max_packet_size`, both before reading their frame and before
+    /// decompressing them
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.max_packet_size = max_packet_size.min(MAX_DECOMPRESSED_SIZE);
+    }
+
+    /// Compress packets of at least `threshold` bytes, or disable compression with `None`
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    /// Encrypt and decrypt everything from now on. Both directions use the shared secret as key
+    /// and IV, but keep their own cipher state.
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.encryptor = Some(AesCfb8::new_from_slices(shared_secret, shared_secret).unwrap());
+        self.decryptor = Some(AesCfb8::new_from_slices(shared_secret, shared_secret).unwrap());
+        // Anything still buffered was sent after the switch, so it is encrypted as well
+        self.decrypted = 0;
+    }
+}
+impl Default for MinecraftCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read a VarInt from the start of `bytes`, returning its value and length, or `None` if more
+/// bytes are needed
+fn peek_varint(bytes: &[u8]) -> Result<Option<(usize, usize)>> {
+    let mut result = 0;
+    for (read, byte) in bytes.iter().enumerate() {
+        if read >= 5 {
+            return Err(anyhow!("VarInt is too big"));
+        }
+        result |= ((byte & 0b0111_1111) as usize) << (7 * read);
+        if (byte & 0b1000_0000) == 0 {
+            return Ok(Some((result, read + 1)));
+        }
+    }
+    Ok(None)
+}
+
+impl Decoder for MinecraftCodec {
+    type Item = Packet;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>> {
+        if let Some(cipher) = &mut self.decryptor {
+            cipher.decrypt(&mut src[self.decrypted..]);
+        }
+        self.decrypted = src.len();
+
+        let Some((length, header)) = peek_varint(src)? else {
+            return Ok(None);
+        };
+        let max_frame_size = self.max_packet_size.min(MAX_FRAME_SIZE);
+        if length > max_frame_size {
+            return Err(anyhow!(
+                "Packet of {length} bytes exceeds the maximum of {max_frame_size}"
+            ));
+        }
+        if src.len() < header + length {
+            src.reserve(header + length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(header);
+        let frame = src.split_to(length).freeze();
+        self.decrypted -= header + length;
+
+        if self.compression_threshold.is_none() {
+            return Packet::try_from(frame).map(Some);
+        }
+
+        let mut cursor = Reader::new(frame);
+        let data_length = cursor.read_varint()? as usize;
+        if data_length == 0 {
+            return Packet::try_from(cursor.get_leftover_bytes()).map(Some);
+        }
+        if data_length > self.max_packet_size {
+            return Err(anyhow!(
+                "Decompressed packet of {data_length} bytes exceeds the maximum of {}",
+                self.max_packet_size
+            ));
+        }
+        let mut data = Vec::with_capacity(data_length);
+        ZlibDecoder::new(cursor.get_leftover_bytes().reader())
+            .take(data_length as u64 + 1)
+            .read_to_end(&mut data)?;
+        if data.len() != data_length {
+            return Err(anyhow!("Invalid decompressed length"));
+        }
+        Packet::try_from(Bytes::from(data)).map(Some)
+    }
+}
+
+/// Frames longer than this have a length prefix the client does not accept
+fn check_frame_size(length: usize) -> Result<()> {
+    if length > MAX_FRAME_SIZE {
+        return Err(anyhow!("Packet of {length} bytes is too big to send"));
+    }
+    Ok(())
+}
+
+impl Encoder<Packet> for MinecraftCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<()> {
+        let start = dst.len();
+        let uncompressed = varint_len(packet.id) + packet.data.len();
+
+        match self.compression_threshold {
+            None => {
+                check_frame_size(uncompressed)?;
+                dst.reserve(varint_len(uncompressed as i32) + uncompressed);
+                put_varint(dst, uncompressed as i32);
+                put_varint(dst, packet.id);
+                dst.put_slice(&packet.data);
+            }
+            Some(threshold) if uncompressed < threshold => {
+                check_frame_size(uncompressed + 1)?;
+                dst.reserve(varint_len(uncompressed as i32 + 1) + 1 + uncompressed);
+                put_varint(dst, uncompressed as i32 + 1);
+                dst.put_u8(0);
+                put_varint(dst, packet.id);
+                dst.put_slice(&packet.data);
+            }
+            Some(_) => {
+                // Clients refuse to inflate more than this, however well it compresses
+                if uncompressed > MAX_DECOMPRESSED_SIZE {
+                    return Err(anyhow!("Packet of {uncompressed} bytes is too big to send"));
+                }
+                // The compressed length is unknown until the data is written, so leave room for
+                // the largest length prefix and move the frame back if it ends up shorter
+                dst.reserve(FRAME_LENGTH_SIZE + varint_len(uncompressed as i32) + uncompressed);
+                dst.put_bytes(0, FRAME_LENGTH_SIZE);
+                put_varint(dst, uncompressed as i32);
+                let mut encoder = ZlibEncoder::new((&mut *dst).writer(), Compression::default());
+                let mut id = [0; 5];
+                let id_len = varint_len(packet.id);
+                put_varint(&mut &mut id[..], packet.id);
+                encoder.write_all(&id[..id_len])?;
+                encoder.write_all(&packet.data)?;
+                encoder.finish()?;
+
+                let length = dst.len() - start - FRAME_LENGTH_SIZE;
+                if let Err(e) = check_frame_size(length) {
+                    dst.truncate(start);
+                    return Err(e);
+                }
+                let header = varint_len(length as i32);
+                let mut prefix = &mut dst[start..start + header];
+                put_varint(&mut prefix, length as i32);
+                dst.copy_within(start + FRAME_LENGTH_SIZE.., start + header);
+                dst.truncate(start + header + length);
+            }
+        }
+
+        if let Some(cipher) = &mut self.encryptor {
+            cipher.encrypt(&mut dst[start..]);
+        }
+        Ok(())
+    }
+}
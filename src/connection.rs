@@ -1,134 +1,488 @@
-use aes::{
-    cipher::{AsyncStreamCipher, NewCipher},
-    Aes128,
+use std::{
+    future, mem,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
 };
+
 use anyhow::{anyhow, Result};
-use cfb8::Cfb8;
-use mojang_api::ServerAuthResponse;
 use rand::{RngCore, SeedableRng};
 use serde_json::json;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
+    sync::watch,
+    task::{self, JoinHandle},
+    time::{self, Instant},
 };
 
 use crate::{
+    access::AccessControl,
+    auth::{Authenticator, MojangAuthenticator},
+    capture::{CaptureWriter, Direction},
+    chunk_manager::{ChunkManager, ViewConfig},
+    crypto::KeyPair,
+    handler::{DefaultHandler, Handler},
+    keylog::KeyLog,
+    light::{BlockLight, ChunkLight},
+    limits,
+    outbound::{write_outbound, ConnectionHandle, Outbound, OutboundReceiver},
     player::Player,
     protocol::{
-        ConnectionState, EncryptionRequest, EncryptionResponse, Handshake, LoginStart,
-        LoginSuccess, Reader, StatusPing, StatusPong, StatusRequest, StatusResponse, Writer,
+        play::{
+            clientbound::{
+                game_mode, ClientboundPlayPacket, LoginPlay, SetCenterChunk,
+                SetDefaultSpawnPosition, SynchronizePlayerPosition, UnloadChunk, UpdateTime,
+            },
+            registry_codec::{registry_codec, OVERWORLD},
+            serverbound::ServerboundPlayPacket,
+        },
+        ConnectionState, EncryptionRequest, EncryptionResponse, Handshake, KeepAlive, LoginStart,
+        StatusPing, StatusPong, StatusRequest, StatusResponse, PROTOCOL_VERSION, VERSION_NAME,
     },
-    RSA_KEY_PAIR,
+    registry::{OnlinePlayer, PlayerRegistry, DEFAULT_MAX_PLAYERS, DUPLICATE_LOGIN_MESSAGE},
+    stream::{Packet, PacketReader, PacketStream, PacketWriter},
+    throttle::{Throttle, SERVER_FULL_MESSAGE, THROTTLED_MESSAGE},
+    tick::{Ticker, TICK_DURATION},
+    world::World,
 };
 
-type AesCfb8 = Cfb8<Aes128>;
-
-#[derive(Debug)]
-pub struct Packet {
-    pub id: i32,
-    pub data: Vec<u8>,
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+/// Play packets waiting for the next tick, after which the connection stops reading
+const MAX_QUEUED_INPUT: usize = 256;
+
+/// How long the server waits for a client before giving up on it
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// Waiting for the handshake and status packets
+    pub handshake: Duration,
+    /// Waiting for the next packet during login
+    pub login: Duration,
+    /// Waiting for any packet in the Play state
+    pub play: Duration,
+    /// Time between two Keep Alives
+    pub keep_alive_interval: Duration,
+    /// Time the client has to answer a Keep Alive before it is kicked
+    pub keep_alive: Duration,
 }
-impl TryFrom<Vec<u8>> for Packet {
-    type Error = anyhow::Error;
-
-    fn try_from(data: Vec<u8>) -> std::result::Result<Self, Self::Error> {
-        let mut cursor = Reader::new(&data);
-        let id = cursor.read_varint()?;
-        Ok(Packet {
-            id,
-            data: cursor.get_leftover_bytes().to_vec(),
-        })
+impl Timeouts {
+    pub fn read_timeout(&self, state: ConnectionState) -> Duration {
+        match state {
+            ConnectionState::Handshaking | ConnectionState::Status => self.handshake,
+            ConnectionState::Login => self.login,
+            ConnectionState::Play | ConnectionState::Done => self.play,
+        }
     }
 }
-impl From<Packet> for Vec<u8> {
-    fn from(packet: Packet) -> Self {
-        let mut buffer = Writer::new();
-        buffer.write_varint(packet.id);
-        buffer.write_raw(&packet.data);
-        buffer.into()
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            handshake: Duration::from_secs(5),
+            login: Duration::from_secs(30),
+            play: Duration::from_secs(30),
+            keep_alive_interval: Duration::from_secs(15),
+            keep_alive: Duration::from_secs(30),
+        }
     }
 }
 
+/// The server side of a client's connection. Packets are read by whoever calls [`handle`], and
+/// written by a separate task that is fed through a [`ConnectionHandle`].
+///
+/// [`handle`]: Connection::handle
 pub struct Connection {
-    stream: TcpStream,
+    id: u64,
+    connected_at: SystemTime,
+    reader: PacketReader,
+    peer_addr: SocketAddr,
+    key_pair: Arc<KeyPair>,
     state: ConnectionState,
     player: Option<Player>,
     verify_token: [u8; 4],
     shared_secret: Option<[u8; 16]>,
-    cipher: Option<AesCfb8>,
+    capture: Option<CaptureWriter>,
+    key_log: Option<KeyLog>,
+    timeouts: Timeouts,
+    /// When the last packet was received, the read timeout counts from here
+    last_read: Instant,
+    /// ID and send time of the Keep Alive the client has not answered yet
+    keep_alive: Option<(i64, Instant)>,
+    throttle: Option<Arc<Throttle>>,
+    /// Whether the server had no room left when this connection was accepted
+    full: bool,
+    access: Option<Arc<AccessControl>>,
+    handler: Arc<dyn Handler>,
+    authenticator: Arc<dyn Authenticator>,
+    registry: Option<Arc<PlayerRegistry>>,
+    world: Option<Arc<World>>,
+    ticker: Option<Arc<Ticker>>,
+    view: ViewConfig,
+    /// Chunks the client has, once it is in the world
+    chunks: Option<ChunkManager>,
+    handle: ConnectionHandle,
+    writer: Option<JoinHandle<Result<(PacketWriter, OutboundReceiver)>>>,
 }
 impl Connection {
-    pub async fn new(stream: TcpStream) -> Self {
+    pub async fn new(stream: TcpStream, key_pair: Arc<KeyPair>) -> Result<Self> {
         let mut rng = rand::rngs::StdRng::from_entropy();
         let mut verify_token = [0; 4];
         rng.fill_bytes(&mut verify_token);
 
-        Connection {
-            stream,
+        let stream = PacketStream::new(stream)?;
+        let peer_addr = stream.peer_addr();
+        let (mut reader, writer) = stream.into_split();
+        reader.set_max_packet_size(limits::max_packet_size(ConnectionState::Handshaking));
+
+        let id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+        let (handle, outbound) = ConnectionHandle::new(id);
+        let writer = tokio::spawn(write_outbound(writer, outbound));
+
+        Ok(Connection {
+            id,
+            connected_at: SystemTime::now(),
+            reader,
+            peer_addr,
+            key_pair,
             state: ConnectionState::Handshaking,
             player: None,
             verify_token,
             shared_secret: None,
-            cipher: None,
-        }
+            capture: None,
+            key_log: None,
+            timeouts: Timeouts::default(),
+            last_read: Instant::now(),
+            keep_alive: None,
+            throttle: None,
+            full: false,
+            access: None,
+            handler: Arc::new(DefaultHandler),
+            authenticator: Arc::new(MojangAuthenticator::default()),
+            registry: None,
+            world: None,
+            ticker: None,
+            view: ViewConfig::default(),
+            chunks: None,
+            handle,
+            writer: Some(writer),
+        })
     }
 
-    async fn read_varint(&mut self) -> Result<usize> {
-        let mut read = 0;
-        let mut result = 0;
-        loop {
-            let read_value = self.stream.read_u8().await?;
-            let value = read_value & 0b0111_1111;
-            result |= (value as usize) << (7 * read);
-            read += 1;
-            if read > 5 {
-                return Err(anyhow!("VarInt is too big"));
-            }
-            if (read_value & 0b1000_0000) == 0 {
-                return Ok(result);
-            }
-        }
+    /// Unique ID of this connection within the process
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
-    async fn read_packet(&mut self) -> Result<Packet> {
-        let size = self.read_varint().await?;
-        let mut buffer = vec![0; size];
-        self.stream.read_exact(&mut buffer).await?;
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Record every packet sent and received from now on
+    pub fn record_to(&mut self, capture: CaptureWriter) {
+        // Closed connections have nothing left to record
+        let _ = self.handle.push(Outbound::Record(capture.clone()));
+        self.capture = Some(capture);
+    }
+
+    pub fn capture(&self) -> Option<&CaptureWriter> {
+        self.capture.as_ref()
+    }
+
+    /// Export the shared secret once the connection is encrypted
+    pub fn log_keys_to(&mut self, key_log: KeyLog) {
+        self.key_log = Some(key_log);
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Set how far and how fast chunks are sent around the player
+    pub fn set_view(&mut self, view: ViewConfig) {
+        self.view = view;
+    }
+
+    /// Check logins and status requests against the limits of `throttle`
+    pub fn throttle_with(&mut self, throttle: Arc<Throttle>) {
+        self.throttle = Some(throttle);
+    }
+
+    /// Turn the client away after the handshake, because the server has too many connections
+    pub fn reject_as_full(&mut self) {
+        self.full = true;
+    }
 
-        let buffer = &mut buffer[..];
-        if let Some(cipher) = &mut self.cipher {
-            cipher.decrypt(buffer);
+    /// Check players against the ban lists and whitelist of `access` when they log in
+    pub fn check_access_with(&mut self, access: Arc<AccessControl>) {
+        self.access = Some(access);
+    }
+
+    /// Customise the behaviour of the connection with the hooks of `handler`
+    pub fn handle_with(&mut self, handler: Arc<dyn Handler>) {
+        self.handler = handler;
+    }
+
+    pub fn authenticate_with(&mut self, authenticator: Arc<dyn Authenticator>) {
+        self.authenticator = authenticator;
+    }
+
+    /// Add the player to `registry` once they are logged in
+    pub fn register_with(&mut self, registry: Arc<PlayerRegistry>) {
+        self.registry = Some(registry);
+    }
+
+    pub fn registry(&self) -> Option<&Arc<PlayerRegistry>> {
+        self.registry.as_ref()
+    }
+
+    /// Put players in `world` after they log in, instead of leaving them on the loading screen
+    pub fn join_with(&mut self, world: Arc<World>) {
+        self.world = Some(world);
+    }
+
+    pub fn world(&self) -> Option<&Arc<World>> {
+        self.world.as_ref()
+    }
+
+    /// Handle play packets and send chunks on the ticks of `ticker`, instead of handling packets
+    /// as they arrive and sending chunks on a timer of the connection's own
+    pub fn tick_with(&mut self, ticker: Arc<Ticker>) {
+        self.ticker = Some(ticker);
+    }
+
+    pub fn ticker(&self) -> Option<&Arc<Ticker>> {
+        self.ticker.as_ref()
+    }
+
+    /// Send packets to this connection from other tasks
+    pub fn connection_handle(&self) -> ConnectionHandle {
+        self.handle.clone()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        self.state = state;
+        self.reader
+            .set_max_packet_size(limits::max_packet_size(state));
+        let _ = self.handle.push(Outbound::State(state));
+    }
+
+    pub fn player(&self) -> Option<&Player> {
+        self.player.as_ref()
+    }
+
+    /// Give up the connection once everything queued is written, keeping both halves of the
+    /// stream with their encryption state. Its [`ConnectionHandle`]s stop working.
+    pub async fn into_split(mut self) -> Result<(PacketReader, PacketWriter)> {
+        let (writer, _) = self.close_writer().await?;
+        Ok((self.reader, writer))
+    }
+
+    /// Like [`into_split`](Self::into_split), but with what is still sent through the
+    /// connection's handles, such as kicks, for the caller to write
+    pub(crate) async fn into_relay(
+        mut self,
+    ) -> Result<(PacketReader, PacketWriter, OutboundReceiver)> {
+        let (writer, outbound) = self.close_writer().await?;
+        Ok((self.reader, writer, outbound))
+    }
+
+    /// Stop the writer task after the packets queued so far, and wait for it
+    async fn close_writer(&mut self) -> Result<(PacketWriter, OutboundReceiver)> {
+        let _ = self.handle.push(Outbound::Close);
+        let writer = self
+            .writer
+            .take()
+            .ok_or(anyhow!("Writer is already closed"))?;
+        writer.await?
+    }
+
+    async fn read_packet(&mut self) -> Result<Packet> {
+        let timeout = self.timeouts.read_timeout(self.state);
+        let packet =
+            match time::timeout_at(self.last_read + timeout, self.reader.read_packet()).await {
+                Ok(packet) => packet?,
+                Err(_) => {
+                    let state = self.state;
+                    match state {
+                        ConnectionState::Login => self.kick("Took too long to log in").await?,
+                        ConnectionState::Play => self.kick("Timed out").await?,
+                        _ => {}
+                    }
+                    return Err(anyhow!(
+                        "Timed out after {timeout:?} in the {state:?} state"
+                    ));
+                }
+            };
+        self.last_read = Instant::now();
+        if let Some(capture) = &self.capture {
+            capture.record(Direction::Serverbound, self.state, &packet)?;
         }
+        Ok(packet)
+    }
 
-        Packet::try_from(buffer.to_vec())
+    /// Queue a packet for the writer task
+    pub async fn write_packet(&mut self, packet: Packet) -> Result<()> {
+        self.handle.push(Outbound::Packet(packet))
     }
 
-    async fn write_packet(&mut self, packet: Packet) -> Result<()> {
-        let mut writer = Writer::new();
-        writer.write_varint(packet.id);
-        writer.write_raw(&packet.data);
+    pub async fn write_play_packet(&mut self, packet: ClientboundPlayPacket) -> Result<()> {
+        self.handle.push(Outbound::Play(packet))
+    }
 
-        let mut writer2 = Writer::new();
-        writer2.write_varint(writer.len() as i32);
-        writer2.write_raw(Vec::from(writer).as_slice());
+    pub async fn handle(&mut self) -> Result<()> {
+        let mut result = self.handle_until_done().await;
+        self.leave_registry();
+        if let Err(err) = self.close_writer().await {
+            result = result.and(Err(err));
+        }
+        let handler = self.handler.clone();
+        handler.on_disconnect(self).await;
+        result
+    }
 
-        let buffer = &mut Vec::from(writer2)[..];
-        if let Some(cipher) = &mut self.cipher {
-            cipher.encrypt(buffer);
+    /// Remove the player from the registry, if this connection added them
+    pub fn leave_registry(&mut self) {
+        let uuid = self.player.as_ref().and_then(|player| player.uuid);
+        if let (Some(registry), Some(uuid)) = (&self.registry, uuid) {
+            registry.leave(&uuid, self.id);
         }
+    }
 
-        self.stream.write_all(buffer).await?;
+    async fn handle_until_done(&mut self) -> Result<()> {
+        self.handle_until_play().await?;
+        if self.state == ConnectionState::Play {
+            if let Some(world) = self.world.clone() {
+                self.join_world(&world).await?;
+            }
+        }
+
+        let mut keep_alive = time::interval(self.timeouts.keep_alive_interval);
+        let mut ticks = Ticks::new(self.ticker.as_deref());
+        let handle = self.handle.clone();
+        // Packets go through the ticker's queue, which hands them back here at the start of
+        // the next tick
+        let input = Arc::new(Mutex::new(Vec::new()));
+        let mut queued = 0;
+        while self.state == ConnectionState::Play {
+            tokio::select! {
+                packet = self.read_packet(), if queued < MAX_QUEUED_INPUT => {
+                    match (ServerboundPlayPacket::try_from(packet?)?, &self.ticker) {
+                        // Keep Alives are answered right away, since they measure latency
+                        (packet @ ServerboundPlayPacket::KeepAlive(_), _) | (packet, None) => {
+                            self.handle_play(packet).await?
+                        }
+                        (packet, Some(ticker)) => {
+                            let input = input.clone();
+                            ticker.queue(move || input.lock().unwrap().push(packet));
+                            queued += 1;
+                        }
+                    }
+                }
+                _ = keep_alive.tick() => self.send_keep_alive().await?,
+                _ = ticks.next() => {
+                    let packets = mem::take(&mut *input.lock().unwrap());
+                    queued -= packets.len();
+                    for packet in packets {
+                        if self.state != ConnectionState::Play {
+                            break;
+                        }
+                        self.handle_play(packet).await?;
+                    }
+                    self.send_chunks().await?;
+                }
+                // Kicked from another task, or the client stopped reading
+                _ = handle.closed() => self.set_state(ConnectionState::Done),
+            }
+        }
         Ok(())
     }
 
-    pub async fn handle(&mut self) -> Result<()> {
+    /// Put the player at the spawn point, after which chunks are sent around them every tick
+    async fn join_world(&mut self, world: &World) -> Result<()> {
+        let spawn = world.spawn_point();
+        let max_players = self
+            .registry
+            .as_ref()
+            .map_or(DEFAULT_MAX_PLAYERS, |registry| registry.max_players());
+        self.write_play_packet(ClientboundPlayPacket::LoginPlay(LoginPlay {
+            entity_id: self.id as i32,
+            is_hardcore: false,
+            game_mode: game_mode::SURVIVAL,
+            previous_game_mode: -1,
+            dimension_names: vec![OVERWORLD.to_string()],
+            registry_codec: registry_codec(),
+            dimension_type: OVERWORLD.to_string(),
+            dimension_name: OVERWORLD.to_string(),
+            hashed_seed: 0,
+            max_players: max_players as i32,
+            view_distance: self.view.view_distance,
+            simulation_distance: self.view.view_distance,
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+            is_debug: false,
+            is_flat: true,
+            death_location: None,
+        }))
+        .await?;
+        self.write_play_packet(ClientboundPlayPacket::SetDefaultSpawnPosition(
+            SetDefaultSpawnPosition {
+                location: spawn,
+                angle: 0.0,
+            },
+        ))
+        .await?;
+
+        let (world_age, time_of_day) = world.time();
+        self.write_play_packet(ClientboundPlayPacket::UpdateTime(UpdateTime {
+            world_age,
+            time_of_day,
+        }))
+        .await?;
+
+        // Chunks follow once the client knows where it is, starting with the one it is in
+        let center = (spawn.x >> 4, spawn.z >> 4);
+        self.write_play_packet(ClientboundPlayPacket::SetCenterChunk(SetCenterChunk {
+            x: center.0,
+            z: center.1,
+        }))
+        .await?;
+        self.chunks = Some(ChunkManager::new(center, self.view));
+
+        self.write_play_packet(ClientboundPlayPacket::SynchronizePlayerPosition(
+            SynchronizePlayerPosition {
+                x: spawn.x as f64 + 0.5,
+                y: spawn.y as f64 + 1.0,
+                z: spawn.z as f64 + 0.5,
+                yaw: 0.0,
+                pitch: 0.0,
+                flags: 0,
+                teleport_id: 0,
+            },
+        ))
+        .await
+    }
+
+    /// Disconnect the client with a reason, if its state has a packet for it
+    pub async fn kick(&mut self, reason: &str) -> Result<()> {
+        self.handle.kick(reason);
+        self.set_state(ConnectionState::Done);
+        Ok(())
+    }
+
+    /// Handle the handshake, status and login, returning once the connection is in the Play
+    /// state or done
+    pub async fn handle_until_play(&mut self) -> Result<()> {
         loop {
             match self.state {
                 ConnectionState::Handshaking => self.handle_handshaking().await?,
                 ConnectionState::Status => self.handle_status().await?,
                 ConnectionState::Login => self.handle_login().await?,
-                ConnectionState::Play => self.handle_play().await?,
-                ConnectionState::Done => return Ok(()),
+                ConnectionState::Play | ConnectionState::Done => return Ok(()),
             }
         }
     }
@@ -142,8 +496,70 @@ impl Connection {
         };
 
         println!("< {handshake:?}");
-        self.state = handshake.next_state;
+        self.set_state(handshake.next_state);
+
+        let ip = self.peer_addr().ip();
+        match self.state {
+            ConnectionState::Status => {
+                let throttled = self
+                    .throttle
+                    .as_ref()
+                    .is_some_and(|throttle| !throttle.allow_status(ip));
+                if self.full || throttled {
+                    self.reject_status(if self.full {
+                        SERVER_FULL_MESSAGE
+                    } else {
+                        THROTTLED_MESSAGE
+                    })
+                    .await?;
+                }
+            }
+            ConnectionState::Login => {
+                let throttled = self
+                    .throttle
+                    .as_ref()
+                    .is_some_and(|throttle| !throttle.allow_login(ip));
+                if self.full {
+                    self.kick(SERVER_FULL_MESSAGE).await?;
+                } else if throttled {
+                    self.kick(THROTTLED_MESSAGE).await?;
+                }
+            }
+            _ => {}
+        }
+
+        if self.state != ConnectionState::Done {
+            let handler = self.handler.clone();
+            handler.on_handshake(self, &handshake).await?;
+        }
+        Ok(())
+    }
+
+    /// Answer the status request with `reason` as the description, without a ping
+    async fn reject_status(&mut self, reason: &str) -> Result<()> {
+        let packet = self.read_packet().await?;
+        if packet.id != 0x00 {
+            return Err(anyhow!("Invalid packet id"));
+        }
+        println!("< {:?}", StatusRequest::try_from(packet.data)?);
+
+        let response = StatusResponse::new(json!({
+            "version": {
+                "name": VERSION_NAME,
+                "protocol": PROTOCOL_VERSION
+            },
+            "description": {
+                "text": reason
+            },
+        }));
+        println!("> {response:?}");
+        let packet = Packet {
+            id: 0x00,
+            data: response.try_into()?,
+        };
+        self.write_packet(packet).await?;
 
+        self.set_state(ConnectionState::Done);
         Ok(())
     }
 
@@ -166,26 +582,12 @@ impl Connection {
     async fn handle_status_request(&mut self, request: StatusRequest) -> Result<()> {
         println!("< {request:?}");
 
-        let response = StatusResponse::new(json!({
-                "version": {
-                    "name": "1.19.4",
-                    "protocol": 762
-                },
-                "players": {
-                    "max": 42,
-                    "online": 1,
-                    "sample": [
-                        {
-                            "name": "Player",
-                            "id": "4566e69f-c907-48ee-8d71-d7ba5aa00d20",
-                        }
-                    ]
-                },
-                "description": {
-                    "text": "Hello, world!"
-                },
-            }
-        ));
+        let handler = self.handler.clone();
+        let status = handler.on_status(self).await?;
+        if self.state == ConnectionState::Done {
+            return Ok(());
+        }
+        let response = StatusResponse::try_from(status)?;
         println!("> {response:?}");
         let packet = Packet {
             id: 0x00,
@@ -208,7 +610,7 @@ impl Connection {
         };
         self.write_packet(packet).await?;
 
-        self.state = ConnectionState::Done;
+        self.set_state(ConnectionState::Done);
         Ok(())
     }
 
@@ -233,10 +635,21 @@ impl Connection {
     async fn handle_login_start(&mut self, login_start: LoginStart) -> Result<()> {
         println!("< {login_start:?}");
 
+        let handler = self.handler.clone();
+        handler.on_login_start(self, &login_start).await?;
+        if self.state == ConnectionState::Done {
+            return Ok(());
+        }
+
         self.player = Some(login_start.into());
         println!("{:?}", self.player);
 
-        let response = EncryptionRequest::new(RSA_KEY_PAIR.public_key_to_der()?, self.verify_token);
+        if !self.authenticator.online_mode() {
+            return self.authenticate_player("").await;
+        }
+
+        let response =
+            EncryptionRequest::new(self.key_pair.public_key_der().to_vec(), self.verify_token);
         println!("> {response:?}");
 
         let packet = Packet {
@@ -248,27 +661,68 @@ impl Connection {
         Ok(())
     }
 
-    async fn authenticate_player(&mut self) -> Result<ServerAuthResponse> {
-        let player = self.player.clone().unwrap();
-        let username = player.username.clone();
-        let server_hash = mojang_api::server_hash(
-            "",
-            self.shared_secret.ok_or(anyhow!("Missing shared secret"))?,
-            &RSA_KEY_PAIR.public_key_to_der()?,
-        );
+    /// Look up the player's profile, check whether they are allowed in and finish the login
+    async fn authenticate_player(&mut self, server_hash: &str) -> Result<()> {
+        let username = self
+            .player
+            .as_ref()
+            .ok_or(anyhow!("Missing player"))?
+            .username
+            .clone();
+        let response = match self
+            .authenticator
+            .authenticate(&username, server_hash)
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => {
+                self.kick("Failed to verify username!").await?;
+                return Err(err);
+            }
+        };
+        if let Some(player) = &mut self.player {
+            player.uuid = Some(response.uuid);
+        }
 
-        let client = reqwest::Client::new();
-        let string = client
-            .get("https://sessionserver.mojang.com/session/minecraft/hasJoined")
-            .query(&[("username", username), ("serverId", server_hash)])
-            .send()
-            .await?
-            .text()
-            .await?;
+        if let Some(access) = &self.access {
+            if let Some(reason) = access.check_login(response.uuid, self.peer_addr().ip())? {
+                return self.kick(&reason).await;
+            }
+        }
 
-        let response = serde_json::from_str(&string)?;
+        let handler = self.handler.clone();
+        handler.on_authenticated(self, &response).await?;
+        if self.state == ConnectionState::Done {
+            return Ok(());
+        }
 
-        Ok(response)
+        if let Some(registry) = &self.registry {
+            let player = OnlinePlayer {
+                uuid: response.uuid,
+                name: response.username.clone(),
+                address: self.peer_addr(),
+                handle: self.handle.clone(),
+            };
+            match registry.try_join(player) {
+                Ok(Some(previous)) => {
+                    previous.handle.kick(DUPLICATE_LOGIN_MESSAGE);
+                }
+                Ok(None) => {}
+                Err(_) => return self.kick(SERVER_FULL_MESSAGE).await,
+            }
+        }
+
+        println!("> {response:?}");
+
+        let packet = Packet {
+            id: 0x02,
+            data: response.into(),
+        };
+        self.write_packet(packet).await?;
+
+        self.set_state(ConnectionState::Play);
+
+        Ok(())
     }
 
     async fn handle_encryption_response(
@@ -277,39 +731,163 @@ impl Connection {
     ) -> Result<()> {
         println!("< {encryption_response:?}");
 
-        if encryption_response.decrypt_verify_token()? != self.verify_token {
+        if encryption_response.decrypt_verify_token(&self.key_pair)? != self.verify_token {
             return Err(anyhow!("Invalid verify token"));
         }
 
-        let shared_secret = encryption_response.decrypt_shared_secret()?;
+        let shared_secret = encryption_response.decrypt_shared_secret(&self.key_pair)?;
 
         self.shared_secret = Some(shared_secret);
-        self.cipher = Some(AesCfb8::new_from_slices(&shared_secret, &shared_secret).unwrap());
+        if let Some(key_log) = &self.key_log {
+            key_log.log(self.id, self.peer_addr(), self.connected_at, shared_secret)?;
+        }
+        self.reader.enable_encryption(&shared_secret);
+        self.handle
+            .push(Outbound::EnableEncryption(shared_secret))?;
 
-        let auth_response = self.authenticate_player().await?;
-        println!("{auth_response:?}");
+        let server_hash =
+            mojang_api::server_hash("", shared_secret, self.key_pair.public_key_der());
+        self.authenticate_player(&server_hash).await
+    }
 
-        let response: LoginSuccess = auth_response.into();
-        println!("> {response:?}");
+    async fn handle_play(&mut self, packet: ServerboundPlayPacket) -> Result<()> {
+        match packet {
+            ServerboundPlayPacket::KeepAlive(keep_alive) => {
+                self.handle_keep_alive(keep_alive).await?
+            }
+            packet => {
+                self.follow_player(&packet).await?;
+                let handler = self.handler.clone();
+                handler.on_play_packet(self, packet).await?
+            }
+        }
+        Ok(())
+    }
 
-        let packet = Packet {
-            id: 0x02,
-            data: response.try_into()?,
+    /// Keep the chunks around the player as they move or change their view distance
+    async fn follow_player(&mut self, packet: &ServerboundPlayPacket) -> Result<()> {
+        let Some(chunks) = &mut self.chunks else {
+            return Ok(());
         };
-        self.write_packet(packet).await?;
-
-        self.state = ConnectionState::Play;
+        let (x, z) = match packet {
+            ServerboundPlayPacket::SetPlayerPosition(position) => (position.x, position.z),
+            ServerboundPlayPacket::SetPlayerPositionAndRotation(position) => {
+                (position.x, position.z)
+            }
+            ServerboundPlayPacket::ClientInformation(information) => {
+                let view_distance = (information.view_distance as i32).min(self.view.view_distance);
+                let unload = chunks.set_view_distance(view_distance);
+                return self.unload_chunks(unload).await;
+            }
+            _ => return Ok(()),
+        };
+        let center = ((x.floor() as i32) >> 4, (z.floor() as i32) >> 4);
+        let Some(unload) = chunks.move_to(center) else {
+            return Ok(());
+        };
+        self.write_play_packet(ClientboundPlayPacket::SetCenterChunk(SetCenterChunk {
+            x: center.0,
+            z: center.1,
+        }))
+        .await?;
+        self.unload_chunks(unload).await
+    }
 
-        // TODO: send a valid Login (play) packet here
+    async fn unload_chunks(&mut self, chunks: Vec<(i32, i32)>) -> Result<()> {
+        for (x, z) in chunks {
+            self.write_play_packet(ClientboundPlayPacket::UnloadChunk(UnloadChunk { x, z }))
+                .await?;
+        }
+        Ok(())
+    }
 
+    /// Send the next chunks around the player, up to the limit of a tick
+    async fn send_chunks(&mut self) -> Result<()> {
+        let (Some(chunks), Some(world)) = (&mut self.chunks, self.world.clone()) else {
+            return Ok(());
+        };
+        // More chunks wait until the client has caught up with the ones already queued, so a
+        // slow client is not kicked for falling behind while it loads the world
+        if self.handle.queued() >= self.view.chunks_per_tick {
+            return Ok(());
+        }
+        let batch = chunks.next_chunks();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        // Loading and lighting chunks takes long enough to hold up other tasks, like the ticks
+        let packets = task::spawn_blocking(move || {
+            batch
+                .into_iter()
+                .map(|(x, z)| {
+                    let chunk = world.chunk(x, z);
+                    let light = ChunkLight::calculate(&chunk, &BlockLight);
+                    chunk.to_packet(light.to_light_data())
+                })
+                .collect::<Vec<_>>()
+        })
+        .await?;
+        for packet in packets {
+            self.write_play_packet(ClientboundPlayPacket::ChunkDataAndUpdateLight(packet))
+                .await?;
+        }
         Ok(())
     }
 
-    async fn handle_play(&mut self) -> Result<()> {
-        let packet = self.read_packet().await?;
+    /// Send a new Keep Alive, or kick the client if it did not answer the last one in time
+    async fn send_keep_alive(&mut self) -> Result<()> {
+        if let Some((_, sent_at)) = self.keep_alive {
+            if sent_at.elapsed() >= self.timeouts.keep_alive {
+                return self.kick("Timed out").await;
+            }
+            return Ok(());
+        }
 
-        println!("{packet:?}");
+        let keep_alive = KeepAlive { id: rand::random() };
+        self.keep_alive = Some((keep_alive.id, Instant::now()));
+        self.write_play_packet(ClientboundPlayPacket::KeepAlive(keep_alive))
+            .await
+    }
 
-        Ok(())
+    async fn handle_keep_alive(&mut self, keep_alive: KeepAlive) -> Result<()> {
+        match self.keep_alive {
+            Some((id, sent_at)) if id == keep_alive.id => {
+                self.keep_alive = None;
+                if let Some(player) = &mut self.player {
+                    player.update_latency(sent_at.elapsed());
+                }
+                Ok(())
+            }
+            _ => self.kick("Timed out").await,
+        }
+    }
+}
+
+/// The ticks a connection handles its input and sends chunks on, from the server's ticker or a
+/// timer of its own
+enum Ticks {
+    Server(watch::Receiver<u64>),
+    Timer(time::Interval),
+}
+impl Ticks {
+    fn new(ticker: Option<&Ticker>) -> Ticks {
+        match ticker {
+            Some(ticker) => Ticks::Server(ticker.subscribe()),
+            None => Ticks::Timer(time::interval(TICK_DURATION)),
+        }
+    }
+
+    async fn next(&mut self) {
+        match self {
+            Ticks::Server(ticks) => {
+                // A ticker that was dropped has no more ticks
+                if ticks.changed().await.is_err() {
+                    future::pending::<()>().await;
+                }
+            }
+            Ticks::Timer(interval) => {
+                interval.tick().await;
+            }
+        }
     }
 }
@@ -0,0 +1,97 @@
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use openssl::{
+    pkey::Private,
+    rsa::{Padding, Rsa},
+};
+
+/// Key size used by the vanilla server
+pub const DEFAULT_KEY_SIZE: u32 = 1024;
+
+/// RSA key pair used to exchange the shared secret during login
+pub struct KeyPair {
+    rsa: Rsa<Private>,
+    public_key_der: Vec<u8>,
+}
+impl KeyPair {
+    pub fn generate(bits: u32) -> Result<KeyPair> {
+        KeyPair::from_rsa(Rsa::generate(bits)?)
+    }
+
+    pub fn from_rsa(rsa: Rsa<Private>) -> Result<KeyPair> {
+        let public_key_der = rsa.public_key_to_der()?;
+        Ok(KeyPair {
+            rsa,
+            public_key_der,
+        })
+    }
+
+    /// Parse a PEM-encoded PKCS#1 or PKCS#8 private key
+    pub fn from_pem(pem: &[u8]) -> Result<KeyPair> {
+        KeyPair::from_rsa(Rsa::private_key_from_pem(pem)?)
+    }
+
+    pub fn to_pem(&self) -> Result<Vec<u8>> {
+        Ok(self.rsa.private_key_to_pem()?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<KeyPair> {
+        KeyPair::from_pem(&fs::read(path)?)
+    }
+
+    /// Write the private key to `path`, which only the owner can read on Unix
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut options = OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options.open(path)?.write_all(&self.to_pem()?)?;
+        Ok(())
+    }
+
+    /// Load the key pair from `path`, or generate a new one of `bits` size and save it there
+    pub fn load_or_generate(path: impl AsRef<Path>, bits: u32) -> Result<KeyPair> {
+        let path = path.as_ref();
+        if path.exists() {
+            return KeyPair::load(path);
+        }
+
+        let key_pair = KeyPair::generate(bits)?;
+        key_pair.save(path)?;
+        Ok(key_pair)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.rsa.size() * 8
+    }
+
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.public_key_der
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut buffer = vec![0; self.rsa.size() as usize];
+        let length = self
+            .rsa
+            .private_decrypt(data, &mut buffer, Padding::PKCS1)
+            .map_err(|e| anyhow!("Failed to decrypt with server key: {e}"))?;
+        buffer.truncate(length);
+        Ok(buffer)
+    }
+}
+
+/// Encrypt `data` with a DER-encoded public key, as the client does with the server's key
+pub fn public_encrypt(public_key_der: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let rsa = Rsa::public_key_from_der(public_key_der)?;
+    let mut buffer = vec![0; rsa.size() as usize];
+    let length = rsa.public_encrypt(data, &mut buffer, Padding::PKCS1)?;
+    buffer.truncate(length);
+    Ok(buffer)
+}
@@ -0,0 +1,292 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::Bytes;
+
+use crate::{
+    capture::Direction,
+    pcap::{TcpFlow, TcpSession},
+    protocol::{
+        play::serverbound::ServerboundPlayPacket, ConnectionState, EncryptionRequest,
+        EncryptionResponse, Handshake, LegacyKick, LegacyPing, LoginDisconnect, LoginPluginRequest,
+        LoginStart, LoginSuccess, SetCompression, StatusPing, StatusPong, StatusRequest,
+        StatusResponse,
+    },
+    stream::{Packet, PacketReader},
+};
+
+/// A packet decoded from a raw TCP stream
+#[derive(Debug)]
+pub struct DecodedPacket {
+    /// Time since the start of the session
+    pub timestamp: Duration,
+    pub direction: Direction,
+    pub state: ConnectionState,
+    /// The typed packet if it is known, otherwise the raw [`Packet`]
+    pub description: String,
+}
+
+#[derive(Debug, Default)]
+pub struct DecodedSession {
+    /// Packets of both directions, in the order they were captured
+    pub packets: Vec<DecodedPacket>,
+    /// Anything that stopped the decoding, such as missing keys or truncated data
+    pub notes: Vec<String>,
+}
+
+/// What the clientbound side of the login told us about the serverbound side
+#[derive(Default)]
+struct LoginInfo {
+    encryption_requested: bool,
+    compression_threshold: Option<usize>,
+    plugin_requests: usize,
+}
+
+/// Reads packets from one direction of a session, keeping track of where each one ends
+struct FlowDecoder<'a> {
+    flow: &'a TcpFlow,
+    reader: PacketReader<&'a [u8]>,
+    direction: Direction,
+}
+impl<'a> FlowDecoder<'a> {
+    fn new(flow: &'a TcpFlow, direction: Direction) -> Self {
+        FlowDecoder {
+            flow,
+            reader: PacketReader::new(&flow.data[..]),
+            direction,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.reader.get_ref().len() + self.reader.buffered()
+    }
+
+    /// Read the next packet, or `None` with a note in `session` when the stream is exhausted
+    async fn next(&mut self, session: &mut DecodedSession) -> Option<(Packet, Duration)> {
+        if self.remaining() == 0 {
+            return None;
+        }
+        match self.reader.read_packet().await {
+            Ok(packet) => {
+                let end = self.flow.data.len() - self.remaining();
+                Some((packet, self.flow.timestamp_at(end.saturating_sub(1))))
+            }
+            Err(e) => {
+                session.notes.push(format!(
+                    "{:?}: stopped decoding with {} bytes left: {e}",
+                    self.direction,
+                    self.remaining()
+                ));
+                None
+            }
+        }
+    }
+
+    fn skip_encrypted(&self, session: &mut DecodedSession) {
+        if self.remaining() > 0 {
+            session.notes.push(format!(
+                "{:?}: {} encrypted bytes, no shared secret available",
+                self.direction,
+                self.remaining()
+            ));
+        }
+    }
+}
+
+fn describe<T: std::fmt::Debug>(result: Result<T>, data: &[u8]) -> String {
+    match result {
+        Ok(packet) => format!("{packet:?}"),
+        Err(e) => format!("Invalid packet ({e}): {data:?}"),
+    }
+}
+
+/// Decode the Minecraft protocol from both directions of a TCP session. Encrypted traffic is
+/// only decoded when the `shared_secret` of the session is known, otherwise decoding stops at
+/// the Encryption Response.
+pub async fn decode_session(
+    session: &TcpSession,
+    shared_secret: Option<[u8; 16]>,
+) -> DecodedSession {
+    let mut decoded = DecodedSession::default();
+
+    if session.serverbound.data.first() == Some(&0xFE) {
+        decode_legacy(session, &mut decoded);
+        return decoded;
+    }
+
+    let mut serverbound = FlowDecoder::new(&session.serverbound, Direction::Serverbound);
+    let Some((packet, timestamp)) = serverbound.next(&mut decoded).await else {
+        return decoded;
+    };
+    let handshake = Handshake::try_from(packet.data.clone());
+    let next_state = handshake
+        .as_ref()
+        .ok()
+        .map(|handshake| handshake.next_state);
+    decoded.packets.push(DecodedPacket {
+        timestamp,
+        direction: Direction::Serverbound,
+        state: ConnectionState::Handshaking,
+        description: describe(handshake, &packet.data),
+    });
+    let Some(next_state) = next_state else {
+        return decoded;
+    };
+
+    let login = decode_clientbound(session, next_state, shared_secret, &mut decoded).await;
+    decode_serverbound(serverbound, next_state, login, shared_secret, &mut decoded).await;
+
+    decoded.packets.sort_by_key(|packet| packet.timestamp);
+    decoded
+}
+
+async fn decode_clientbound(
+    session: &TcpSession,
+    mut state: ConnectionState,
+    shared_secret: Option<[u8; 16]>,
+    decoded: &mut DecodedSession,
+) -> LoginInfo {
+    let mut login = LoginInfo::default();
+    let mut clientbound = FlowDecoder::new(&session.clientbound, Direction::Clientbound);
+
+    while let Some((packet, timestamp)) = clientbound.next(decoded).await {
+        let data = packet.data.clone();
+        let packet_state = state;
+        let description = match (state, packet.id) {
+            (ConnectionState::Status, 0x00) => {
+                describe(StatusResponse::try_from(data), &packet.data)
+            }
+            (ConnectionState::Status, 0x01) => describe(StatusPong::try_from(data), &packet.data),
+            (ConnectionState::Login, 0x00) => {
+                describe(LoginDisconnect::try_from(data), &packet.data)
+            }
+            (ConnectionState::Login, 0x01) => {
+                login.encryption_requested = true;
+                describe(EncryptionRequest::try_from(data), &packet.data)
+            }
+            (ConnectionState::Login, 0x02) => {
+                state = ConnectionState::Play;
+                describe(LoginSuccess::try_from(data), &packet.data)
+            }
+            (ConnectionState::Login, 0x03) => {
+                let set_compression = SetCompression::try_from(data);
+                if let Ok(set_compression) = &set_compression {
+                    login.compression_threshold = usize::try_from(set_compression.threshold).ok();
+                    clientbound
+                        .reader
+                        .set_compression(login.compression_threshold);
+                }
+                describe(set_compression, &packet.data)
+            }
+            (ConnectionState::Login, 0x04) => {
+                login.plugin_requests += 1;
+                describe(LoginPluginRequest::try_from(data), &packet.data)
+            }
+            _ => format!("{packet:?}"),
+        };
+        decoded.packets.push(DecodedPacket {
+            timestamp,
+            direction: Direction::Clientbound,
+            state: packet_state,
+            description,
+        });
+
+        // The server enables encryption as soon as it receives the response to this request
+        if packet_state == ConnectionState::Login && packet.id == 0x01 {
+            match shared_secret {
+                Some(shared_secret) => clientbound.reader.enable_encryption(&shared_secret),
+                None => {
+                    clientbound.skip_encrypted(decoded);
+                    break;
+                }
+            }
+        }
+    }
+
+    login
+}
+
+async fn decode_serverbound(
+    mut serverbound: FlowDecoder<'_>,
+    mut state: ConnectionState,
+    login: LoginInfo,
+    shared_secret: Option<[u8; 16]>,
+    decoded: &mut DecodedSession,
+) {
+    // Login Plugin Responses still expected before the client switches to Play
+    let mut plugin_responses = 0;
+
+    while let Some((packet, timestamp)) = serverbound.next(decoded).await {
+        let data = packet.data.clone();
+        let packet_state = state;
+        let description = match (state, packet.id) {
+            (ConnectionState::Status, 0x00) => {
+                describe(StatusRequest::try_from(data), &packet.data)
+            }
+            (ConnectionState::Status, 0x01) => describe(StatusPing::try_from(data), &packet.data),
+            (ConnectionState::Login, 0x00) => {
+                if !login.encryption_requested {
+                    serverbound
+                        .reader
+                        .set_compression(login.compression_threshold);
+                    plugin_responses = login.plugin_requests;
+                    state = ConnectionState::Play;
+                }
+                describe(LoginStart::try_from(data), &packet.data)
+            }
+            (ConnectionState::Login, 0x01) => {
+                serverbound
+                    .reader
+                    .set_compression(login.compression_threshold);
+                plugin_responses = login.plugin_requests;
+                state = ConnectionState::Play;
+                describe(EncryptionResponse::try_from(data), &packet.data)
+            }
+            _ if plugin_responses > 0 => {
+                plugin_responses -= 1;
+                format!("LoginPluginResponse {packet:?}")
+            }
+            (ConnectionState::Play, _) => describe(
+                ServerboundPlayPacket::try_from(packet.clone()),
+                &packet.data,
+            ),
+            _ => format!("{packet:?}"),
+        };
+        decoded.packets.push(DecodedPacket {
+            timestamp,
+            direction: Direction::Serverbound,
+            state: packet_state,
+            description,
+        });
+
+        if packet_state == ConnectionState::Login && packet.id == 0x01 {
+            match shared_secret {
+                Some(shared_secret) => serverbound.reader.enable_encryption(&shared_secret),
+                None => {
+                    serverbound.skip_encrypted(decoded);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn decode_legacy(session: &TcpSession, decoded: &mut DecodedSession) {
+    let ping = LegacyPing::try_from(Bytes::from(session.serverbound.data.clone()));
+    decoded.packets.push(DecodedPacket {
+        timestamp: session.serverbound.timestamp_at(0),
+        direction: Direction::Serverbound,
+        state: ConnectionState::Handshaking,
+        description: describe(ping, &session.serverbound.data),
+    });
+
+    let data = &session.clientbound.data;
+    if !data.is_empty() {
+        decoded.packets.push(DecodedPacket {
+            timestamp: session.clientbound.timestamp_at(0),
+            direction: Direction::Clientbound,
+            state: ConnectionState::Status,
+            description: describe(LegacyKick::try_from(Bytes::from(data.clone())), data),
+        });
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    chunk::{Chunk, BEDROCK, DIRT, GRASS_BLOCK, MIN_Y, STONE},
+    protocol::play::{
+        registry_codec::{BIOMES, PLAINS, THE_VOID},
+        Position,
+    },
+};
+
+/// Creates the chunks of a world when they are first needed
+pub trait Generator: Send + Sync {
+    fn generate(&self, x: i32, z: i32) -> Chunk;
+
+    /// Block the player spawns on top of
+    fn spawn_point(&self) -> Position;
+}
+
+/// ID of a biome in the registry codec
+pub fn biome_id(name: &str) -> Option<u32> {
+    BIOMES
+        .iter()
+        .position(|biome| biome.name == name)
+        .map(|id| id as u32)
+}
+
+/// A superflat world of the same layers everywhere, from the bottom of the world up
+#[derive(Debug, Clone)]
+pub struct FlatGenerator {
+    /// Block states with the number of blocks they are thick
+    layers: Vec<(u32, usize)>,
+    biome: u32,
+}
+impl FlatGenerator {
+    pub fn new(layers: Vec<(u32, usize)>, biome: u32) -> FlatGenerator {
+        FlatGenerator { layers, biome }
+    }
+
+    /// Y of the top of the layers
+    fn surface(&self) -> i32 {
+        MIN_Y
+            + self
+                .layers
+                .iter()
+                .map(|&(_, height)| height as i32)
+                .sum::<i32>()
+    }
+}
+impl Default for FlatGenerator {
+    /// The Classic Flat preset: bedrock, 2 dirt and grass in plains
+    fn default() -> Self {
+        FlatGenerator::new(
+            vec![(BEDROCK, 1), (DIRT, 2), (GRASS_BLOCK, 1)],
+            biome_id(PLAINS).unwrap_or(0),
+        )
+    }
+}
+impl Generator for FlatGenerator {
+    fn generate(&self, x: i32, z: i32) -> Chunk {
+        let mut chunk = Chunk::new(x, z, self.biome);
+        let mut y = MIN_Y;
+        for &(state, height) in &self.layers {
+            for _ in 0..height {
+                for block_z in 0..16 {
+                    for block_x in 0..16 {
+                        chunk.set_block(block_x, y, block_z, state);
+                    }
+                }
+                y += 1;
+            }
+        }
+        chunk
+    }
+
+    fn spawn_point(&self) -> Position {
+        Position {
+            x: 0,
+            y: self.surface() - 1,
+            z: 0,
+        }
+    }
+}
+
+/// An empty world with a platform to spawn on
+#[derive(Debug, Clone)]
+pub struct VoidGenerator {
+    /// Center of the platform
+    spawn: Position,
+    /// Blocks from the center to the edge of the platform, which is not built when negative
+    platform_radius: i32,
+    platform: u32,
+    biome: u32,
+}
+impl VoidGenerator {
+    pub fn new(spawn: Position, platform_radius: i32, platform: u32) -> VoidGenerator {
+        VoidGenerator {
+            spawn,
+            platform_radius,
+            platform,
+            biome: biome_id(THE_VOID).unwrap_or(0),
+        }
+    }
+
+    pub fn biome(mut self, biome: u32) -> Self {
+        self.biome = biome;
+        self
+    }
+}
+impl Default for VoidGenerator {
+    /// A 5x5 stone platform at Y 64
+    fn default() -> Self {
+        VoidGenerator::new(Position { x: 0, y: 64, z: 0 }, 2, STONE)
+    }
+}
+impl Generator for VoidGenerator {
+    fn generate(&self, x: i32, z: i32) -> Chunk {
+        let mut chunk = Chunk::new(x, z, self.biome);
+        for block_z in 0..16 {
+            for block_x in 0..16 {
+                let world_x = x * 16 + block_x as i32;
+                let world_z = z * 16 + block_z as i32;
+                if (world_x - self.spawn.x).abs() <= self.platform_radius
+                    && (world_z - self.spawn.z).abs() <= self.platform_radius
+                {
+                    chunk.set_block(block_x, self.spawn.y, block_z, self.platform);
+                }
+            }
+        }
+        chunk
+    }
+
+    fn spawn_point(&self) -> Position {
+        self.spawn
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::{
+    connection::Connection,
+    protocol::{
+        play::serverbound::ServerboundPlayPacket, Handshake, LoginStart, LoginSuccess,
+        ServerStatus, StatusPlayers, StatusVersion, PROTOCOL_VERSION, VERSION_NAME,
+    },
+    registry::DEFAULT_MAX_PLAYERS,
+};
+
+/// Hooks into the life of a [`Connection`]. Every method has a default, so an implementation
+/// only overrides what it needs.
+///
+/// A hook can turn the client away with [`Connection::kick`], after which the connection stops.
+/// Returning an error closes the connection without a message.
+#[async_trait]
+pub trait Handler: Send + Sync {
+    /// After the handshake, once the connection is in the state the client asked for
+    async fn on_handshake(
+        &self,
+        _connection: &mut Connection,
+        _handshake: &Handshake,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// The status to show in the server list
+    async fn on_status(&self, connection: &mut Connection) -> Result<ServerStatus> {
+        let players = match connection.registry() {
+            Some(registry) => registry.status_players(),
+            None => StatusPlayers {
+                max: DEFAULT_MAX_PLAYERS as i32,
+                online: 0,
+                sample: Vec::new(),
+            },
+        };
+        Ok(ServerStatus {
+            version: StatusVersion {
+                name: VERSION_NAME.to_string(),
+                protocol: PROTOCOL_VERSION,
+            },
+            players: Some(players),
+            description: json!({ "text": "Hello, world!" }),
+            favicon: None,
+            enforces_secure_chat: None,
+        })
+    }
+
+    /// Before the player is authenticated
+    async fn on_login_start(
+        &self,
+        _connection: &mut Connection,
+        _login_start: &LoginStart,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// After the player is authenticated and allowed in, just before Login Success is sent
+    async fn on_authenticated(
+        &self,
+        _connection: &mut Connection,
+        _profile: &LoginSuccess,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Every packet received in the Play state, except Keep Alives
+    async fn on_play_packet(
+        &self,
+        _connection: &mut Connection,
+        packet: ServerboundPlayPacket,
+    ) -> Result<()> {
+        match packet {
+            ServerboundPlayPacket::Unknown(packet) => {
+                println!("Unknown Play packet 0x{:02X}: {packet:?}", packet.id)
+            }
+            packet => println!("{packet:?}"),
+        }
+        Ok(())
+    }
+
+    /// When [`Connection::handle`] is done with the connection, whether it ended cleanly or not
+    async fn on_disconnect(&self, _connection: &mut Connection) {}
+}
+
+/// The behaviour of the server without any customisation
+pub struct DefaultHandler;
+impl Handler for DefaultHandler {}
@@ -0,0 +1,156 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    net::SocketAddr,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use anyhow::{anyhow, Result};
+
+/// Label at the start of every key log line, like `CLIENT_RANDOM` in an `SSLKEYLOGFILE`
+const LABEL: &str = "MC_SHARED_SECRET";
+
+/// The shared secret of one connection, so its encrypted traffic can be decrypted afterwards.
+///
+/// Written as a single line of space-separated fields:
+///
+/// ```text
+/// MC_SHARED_SECRET <connection id> <peer address> <connected at> <logged at> <shared secret>
+/// ```
+///
+/// Both timestamps are Unix time in milliseconds, and the shared secret is hex-encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyLogEntry {
+    pub connection_id: u64,
+    pub peer_addr: SocketAddr,
+    pub connected_at: i64,
+    pub logged_at: i64,
+    pub shared_secret: [u8; 16],
+}
+impl fmt::Display for KeyLogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{LABEL} {} {} {} {} ",
+            self.connection_id, self.peer_addr, self.connected_at, self.logged_at
+        )?;
+        for byte in self.shared_secret {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for KeyLogEntry {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [label, connection_id, peer_addr, connected_at, logged_at, shared_secret] = fields[..]
+        else {
+            return Err(anyhow!("Expected 6 fields, got {}", fields.len()));
+        };
+        if label != LABEL {
+            return Err(anyhow!("Unknown label {label:?}"));
+        }
+        if shared_secret.len() != 32 || !shared_secret.is_ascii() {
+            return Err(anyhow!("Shared secret must be 32 hex characters"));
+        }
+
+        let mut secret = [0; 16];
+        for (i, byte) in secret.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&shared_secret[i * 2..i * 2 + 2], 16)?;
+        }
+
+        Ok(KeyLogEntry {
+            connection_id: connection_id.parse()?,
+            peer_addr: peer_addr.parse()?,
+            connected_at: connected_at.parse()?,
+            logged_at: logged_at.parse()?,
+            shared_secret: secret,
+        })
+    }
+}
+
+/// Appends the shared secret of every encrypted connection to a file, for analysts to decrypt
+/// traffic captured outside the process. Anyone with this file can read the logged sessions, so
+/// it is only written when explicitly enabled.
+#[derive(Clone)]
+pub struct KeyLog {
+    file: Arc<Mutex<File>>,
+}
+impl KeyLog {
+    /// Open the file to append to, creating it readable by the owner only on Unix
+    pub fn open(path: impl AsRef<Path>) -> Result<KeyLog> {
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options.open(path)?;
+        Ok(KeyLog {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn log(
+        &self,
+        connection_id: u64,
+        peer_addr: SocketAddr,
+        connected_at: SystemTime,
+        shared_secret: [u8; 16],
+    ) -> Result<()> {
+        let entry = KeyLogEntry {
+            connection_id,
+            peer_addr,
+            connected_at: unix_millis(connected_at),
+            logged_at: unix_millis(SystemTime::now()),
+            shared_secret,
+        };
+        writeln!(self.file.lock().unwrap(), "{entry}")?;
+        Ok(())
+    }
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Parse a key log, skipping empty lines and `#` comments
+pub fn parse(contents: &str) -> Result<Vec<KeyLogEntry>> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            line.parse()
+                .map_err(|e| anyhow!("Invalid key log line {}: {e}", i + 1))
+        })
+        .collect()
+}
+
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<KeyLogEntry>> {
+    parse(&std::fs::read_to_string(path)?)
+}
+
+/// Find the shared secret for a connection from `peer_addr`, preferring the entry whose connection
+/// time is closest to `connected_at` when the address was reused
+pub fn find_secret(
+    entries: &[KeyLogEntry],
+    peer_addr: SocketAddr,
+    connected_at: i64,
+) -> Option<[u8; 16]> {
+    entries
+        .iter()
+        .filter(|entry| entry.peer_addr == peer_addr)
+        .min_by_key(|entry| (entry.connected_at - connected_at).abs())
+        .map(|entry| entry.shared_secret)
+}
@@ -1,10 +1,31 @@
-use lazy_static::lazy_static;
-use openssl::{pkey::Private, rsa::Rsa};
-
+pub mod access;
+pub mod anvil;
+pub mod auth;
+pub mod blocks;
+pub mod capture;
+pub mod chunk;
+pub mod chunk_manager;
+pub mod client;
+pub mod codec;
 pub mod connection;
+pub mod crypto;
+pub mod dissect;
+pub mod generator;
+pub mod handler;
+pub mod keylog;
+pub mod light;
+pub mod limits;
+pub mod nbt;
+pub mod outbound;
+pub mod pcap;
 pub mod player;
 pub mod protocol;
-
-lazy_static! {
-    pub static ref RSA_KEY_PAIR: Rsa<Private> = Rsa::generate(1024).unwrap();
-}
+pub mod proxy;
+pub mod registries;
+pub mod registry;
+pub mod replay;
+pub mod server;
+pub mod stream;
+pub mod throttle;
+pub mod tick;
+pub mod world;
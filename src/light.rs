@@ -0,0 +1,640 @@
+use std::{collections::VecDeque, sync::OnceLock};
+
+use crate::{
+    blocks::BLOCKS,
+    chunk::{Chunk, AIR, HEIGHT, MIN_Y, SECTION_COUNT},
+    protocol::play::clientbound::LightData,
+};
+
+pub const MAX_LIGHT: u8 = 15;
+/// Bytes of a section's light array, half a byte per block
+pub const LIGHT_ARRAY_LENGTH: usize = 2048;
+/// Sections in light data, including the one below and above the world
+pub const LIGHT_SECTION_COUNT: usize = SECTION_COUNT + 2;
+
+const COLUMN_VOLUME: usize = 16 * 16 * HEIGHT;
+
+/// How blocks interact with light
+pub trait LightProperties {
+    /// Light level a block state gives off
+    fn emission(&self, state: u32) -> u8;
+    /// How much light a block state absorbs on top of the 1 level lost for every block, where
+    /// 15 blocks light completely
+    fn opacity(&self, state: u32) -> u8;
+}
+
+/// Air lets light through and every other block stops it, without any block giving off light
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpaqueBlocks;
+impl LightProperties for OpaqueBlocks {
+    fn emission(&self, _state: u32) -> u8 {
+        0
+    }
+
+    fn opacity(&self, state: u32) -> u8 {
+        if state == AIR {
+            0
+        } else {
+            MAX_LIGHT
+        }
+    }
+}
+
+/// Emission and opacity of block states, from tables of block names and the properties that
+/// change them. Blocks the tables do not list are solid and dark.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockLight;
+impl BlockLight {
+    /// Emission and opacity of every state, indexed by state ID
+    fn table() -> &'static [(u8, u8)] {
+        static TABLE: OnceLock<Vec<(u8, u8)>> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let states: Vec<_> = BLOCKS.iter().flat_map(|block| block.states()).collect();
+            let len = states.iter().map(|state| state.id() as usize + 1).max();
+            let mut table = vec![(0, MAX_LIGHT); len.unwrap_or(0)];
+            for state in states {
+                let name = state.block().name.trim_start_matches("minecraft:");
+                let get = |property: &str| state.get(property);
+                table[state.id() as usize] = (emission(name, get), opacity(name, get));
+            }
+            table
+        })
+    }
+}
+impl LightProperties for BlockLight {
+    fn emission(&self, state: u32) -> u8 {
+        Self::table()
+            .get(state as usize)
+            .map_or(0, |&(emission, _)| emission)
+    }
+
+    fn opacity(&self, state: u32) -> u8 {
+        Self::table()
+            .get(state as usize)
+            .map_or(MAX_LIGHT, |&(_, opacity)| opacity)
+    }
+}
+
+/// Blocks that light passes through, because they are not full blocks or are see-through
+const TRANSPARENT: &[&str] = &[
+    "air",
+    "cave_air",
+    "void_air",
+    "glass",
+    "glass_pane",
+    "iron_bars",
+    "chain",
+    "grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "wither_rose",
+    "torchflower",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "pitcher_plant",
+    "pitcher_crop",
+    "brown_mushroom",
+    "red_mushroom",
+    "crimson_fungus",
+    "warped_fungus",
+    "crimson_roots",
+    "warped_roots",
+    "nether_sprouts",
+    "hanging_roots",
+    "twisting_vines",
+    "twisting_vines_plant",
+    "weeping_vines",
+    "weeping_vines_plant",
+    "vine",
+    "cave_vines",
+    "cave_vines_plant",
+    "glow_lichen",
+    "sculk_vein",
+    "spore_blossom",
+    "azalea",
+    "flowering_azalea",
+    "big_dripleaf",
+    "big_dripleaf_stem",
+    "small_dripleaf",
+    "pink_petals",
+    "sugar_cane",
+    "bamboo",
+    "bamboo_sapling",
+    "cactus",
+    "sweet_berry_bush",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "melon_stem",
+    "pumpkin_stem",
+    "attached_melon_stem",
+    "attached_pumpkin_stem",
+    "torchflower_crop",
+    "nether_wart",
+    "cocoa",
+    "lily_pad",
+    "frogspawn",
+    "sea_pickle",
+    "turtle_egg",
+    "sniffer_egg",
+    "dragon_egg",
+    "chorus_plant",
+    "chorus_flower",
+    "mangrove_propagule",
+    "torch",
+    "wall_torch",
+    "end_rod",
+    "lightning_rod",
+    "lantern",
+    "soul_lantern",
+    "campfire",
+    "soul_campfire",
+    "fire",
+    "soul_fire",
+    "rail",
+    "powered_rail",
+    "detector_rail",
+    "activator_rail",
+    "lever",
+    "tripwire",
+    "tripwire_hook",
+    "redstone_wire",
+    "repeater",
+    "comparator",
+    "daylight_detector",
+    "ladder",
+    "scaffolding",
+    "snow",
+    "flower_pot",
+    "decorated_pot",
+    "cake",
+    "candle_cake",
+    "brewing_stand",
+    "cauldron",
+    "water_cauldron",
+    "lava_cauldron",
+    "powder_snow_cauldron",
+    "hopper",
+    "bell",
+    "anvil",
+    "chipped_anvil",
+    "damaged_anvil",
+    "enchanting_table",
+    "end_portal_frame",
+    "end_portal",
+    "end_gateway",
+    "nether_portal",
+    "grindstone",
+    "stonecutter",
+    "lectern",
+    "composter",
+    "conduit",
+    "barrier",
+    "light",
+    "structure_void",
+    "moving_piston",
+    "piston_head",
+    "chest",
+    "trapped_chest",
+    "ender_chest",
+    "farmland",
+    "dirt_path",
+    "pointed_dripstone",
+    "amethyst_cluster",
+    "large_amethyst_bud",
+    "medium_amethyst_bud",
+    "small_amethyst_bud",
+    "sculk_sensor",
+    "calibrated_sculk_sensor",
+    "sculk_shrieker",
+    "candle",
+];
+/// Endings of the names of families of blocks that light passes through
+const TRANSPARENT_FAMILIES: &[&str] = &[
+    "_slab",
+    "_stairs",
+    "_wall",
+    "_fence",
+    "_fence_gate",
+    "_door",
+    "_trapdoor",
+    "_pressure_plate",
+    "_button",
+    "_sign",
+    "_banner",
+    "_bed",
+    "_carpet",
+    "_candle",
+    "_candle_cake",
+    "_stained_glass",
+    "_glass_pane",
+    "_sapling",
+    "_tulip",
+    "_torch",
+    "_coral",
+    "_coral_fan",
+    "_coral_wall_fan",
+    "_head",
+    "_skull",
+];
+/// Blocks that dim light by a level without stopping it, like water
+const DIMMING: &[&str] = &[
+    "water",
+    "bubble_column",
+    "lava",
+    "ice",
+    "frosted_ice",
+    "cobweb",
+    "slime_block",
+    "honey_block",
+    "spawner",
+    "beacon",
+    "powder_snow",
+    "mangrove_roots",
+    "kelp",
+    "kelp_plant",
+    "seagrass",
+    "tall_seagrass",
+];
+
+/// Light level a block gives off, from its name and the values of its properties
+fn emission<'a>(name: &str, get: impl Fn(&str) -> Option<&'a str>) -> u8 {
+    let is = |property, value| get(property) == Some(value);
+    let number = |property| {
+        get(property)
+            .and_then(|value| value.parse::<u8>().ok())
+            .unwrap_or(0)
+    };
+    let lit = is("lit", "true");
+    match name {
+        "glowstone"
+        | "sea_lantern"
+        | "jack_o_lantern"
+        | "lantern"
+        | "beacon"
+        | "conduit"
+        | "end_portal"
+        | "end_gateway"
+        | "fire"
+        | "lava"
+        | "lava_cauldron"
+        | "shroomlight"
+        | "ochre_froglight"
+        | "verdant_froglight"
+        | "pearlescent_froglight" => 15,
+        "redstone_lamp" | "campfire" if lit => 15,
+        "torch" | "wall_torch" | "end_rod" => 14,
+        "cave_vines" | "cave_vines_plant" if is("berries", "true") => 14,
+        "furnace" | "smoker" | "blast_furnace" if lit => 13,
+        "nether_portal" => 11,
+        "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "crying_obsidian" => 10,
+        "soul_campfire" if lit => 10,
+        "redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+        "enchanting_table" | "ender_chest" | "glow_lichen" => 7,
+        "redstone_torch" | "redstone_wall_torch" if lit => 7,
+        "sculk_catalyst" => 6,
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "small_amethyst_bud" | "brewing_stand" | "brown_mushroom" | "dragon_egg"
+        | "end_portal_frame" | "sculk_sensor" => 1,
+        "light" => number("level"),
+        "respawn_anchor" => [0, 3, 7, 11, 15][number("charges").min(4) as usize],
+        "sea_pickle" if is("waterlogged", "true") => 3 + 3 * number("pickles"),
+        _ if name.ends_with("candle_cake") && lit => 3,
+        _ if name.ends_with("candle") && lit => 3 * number("candles"),
+        _ => 0,
+    }
+}
+
+/// How much light a block absorbs, from its name and the values of its properties: nothing
+/// for blocks light passes through, a level for ones that dim it, and all of it for the rest
+fn opacity<'a>(name: &str, get: impl Fn(&str) -> Option<&'a str>) -> u8 {
+    // Double slabs fill the whole block
+    if get("type") == Some("double") {
+        return MAX_LIGHT;
+    }
+    if DIMMING.contains(&name) || name.ends_with("_leaves") || get("waterlogged") == Some("true") {
+        return 1;
+    }
+    let transparent = TRANSPARENT.contains(&name)
+        || name.starts_with("potted_")
+        || TRANSPARENT_FAMILIES
+            .iter()
+            .any(|family| name.ends_with(family));
+    if transparent {
+        0
+    } else {
+        MAX_LIGHT
+    }
+}
+
+/// Sky and block light of every block in a chunk. Light does not spread in from neighbouring
+/// chunks, which the client fills in at the edges because `trust_edges` is off.
+#[derive(Debug, Clone)]
+pub struct ChunkLight {
+    sky: Vec<u8>,
+    block: Vec<u8>,
+}
+impl ChunkLight {
+    /// Calculate the light of a chunk, with sky light coming down to its highest blocks
+    pub fn calculate(chunk: &Chunk, properties: &impl LightProperties) -> ChunkLight {
+        let mut opacity = vec![0; COLUMN_VOLUME];
+        let mut block = vec![0; COLUMN_VOLUME];
+        for (index, section) in chunk.sections().iter().enumerate() {
+            if section.is_empty() {
+                continue;
+            }
+            for y in 0..16 {
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let state = section.get_block(x, y, z);
+                        let cell = cell_index(x, index * 16 + y, z);
+                        opacity[cell] = properties.opacity(state);
+                        block[cell] = properties.emission(state);
+                    }
+                }
+            }
+        }
+
+        // Sky light falls straight down until something absorbs it
+        let mut sky = vec![0; COLUMN_VOLUME];
+        for z in 0..16 {
+            for x in 0..16 {
+                let mut level = MAX_LIGHT;
+                for y in (0..HEIGHT).rev() {
+                    let cell = cell_index(x, y, z);
+                    level = level.saturating_sub(opacity[cell]);
+                    if level == 0 {
+                        break;
+                    }
+                    sky[cell] = level;
+                }
+            }
+        }
+
+        spread(&mut sky, &opacity);
+        spread(&mut block, &opacity);
+        ChunkLight { sky, block }
+    }
+
+    /// Sky light at X and Z from 0 to 15 and a world Y. Above the world it is always full.
+    pub fn sky_light(&self, x: usize, y: i32, z: usize) -> u8 {
+        match world_cell(x, y, z) {
+            Some(cell) => self.sky[cell],
+            None if y >= MIN_Y => MAX_LIGHT,
+            None => 0,
+        }
+    }
+
+    pub fn block_light(&self, x: usize, y: i32, z: usize) -> u8 {
+        world_cell(x, y, z).map_or(0, |cell| self.block[cell])
+    }
+
+    /// The masks and arrays of Chunk Data. Sections without any light are only marked empty.
+    pub fn to_light_data(&self) -> LightData {
+        // The section below the world is dark, and the one above it fully lit by the sky
+        let mut sky_sections = Vec::with_capacity(LIGHT_SECTION_COUNT);
+        let mut block_sections = Vec::with_capacity(LIGHT_SECTION_COUNT);
+        sky_sections.push(None);
+        block_sections.push(None);
+        for section in 0..SECTION_COUNT {
+            sky_sections.push(section_array(&self.sky, section));
+            block_sections.push(section_array(&self.block, section));
+        }
+        sky_sections.push(Some(vec![0xFF; LIGHT_ARRAY_LENGTH]));
+        block_sections.push(None);
+
+        let (sky_light_mask, empty_sky_light_mask, sky_light) = masks(sky_sections);
+        let (block_light_mask, empty_block_light_mask, block_light) = masks(block_sections);
+        LightData {
+            trust_edges: false,
+            sky_light_mask,
+            block_light_mask,
+            empty_sky_light_mask,
+            empty_block_light_mask,
+            sky_light,
+            block_light,
+        }
+    }
+}
+
+/// Index of a block in the whole column, from the bottom of the world
+fn cell_index(x: usize, y: usize, z: usize) -> usize {
+    (y * 16 + z) * 16 + x
+}
+
+fn world_cell(x: usize, y: i32, z: usize) -> Option<usize> {
+    let y = usize::try_from(y - MIN_Y).ok().filter(|&y| y < HEIGHT)?;
+    Some(cell_index(x, y, z))
+}
+
+/// Spread light from every lit block to its neighbours, losing at least a level every block
+fn spread(levels: &mut [u8], opacity: &[u8]) {
+    // Only blocks next to darker ones have anywhere to spread, which skips most of the open sky
+    let mut queue = (0..levels.len())
+        .filter(|&cell| {
+            let level = levels[cell];
+            level > 1
+                && neighbours(cell).any(|neighbour| {
+                    level.saturating_sub(opacity[neighbour].max(1)) > levels[neighbour]
+                })
+        })
+        .collect::<VecDeque<_>>();
+
+    while let Some(cell) = queue.pop_front() {
+        let level = levels[cell];
+        for neighbour in neighbours(cell) {
+            let new_level = level.saturating_sub(opacity[neighbour].max(1));
+            if new_level > levels[neighbour] {
+                levels[neighbour] = new_level;
+                if new_level > 1 {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+/// Blocks next to a block, inside the chunk
+fn neighbours(cell: usize) -> impl Iterator<Item = usize> {
+    let (x, y, z) = (cell % 16, cell / 256, cell / 16 % 16);
+    [
+        (x > 0).then(|| cell - 1),
+        (x < 15).then(|| cell + 1),
+        (z > 0).then(|| cell - 16),
+        (z < 15).then(|| cell + 16),
+        (y > 0).then(|| cell - 256),
+        (y < HEIGHT - 1).then(|| cell + 256),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// The nibble array of a section, or `None` if it has no light at all
+fn section_array(levels: &[u8], section: usize) -> Option<Vec<u8>> {
+    let levels = &levels[section * 4096..(section + 1) * 4096];
+    if levels.iter().all(|&level| level == 0) {
+        return None;
+    }
+    Some(
+        levels
+            .chunks(2)
+            .map(|pair| pair[0] | pair[1] << 4)
+            .collect(),
+    )
+}
+
+/// The mask of sections with light, the mask of empty ones, and the arrays of the first
+fn masks(sections: Vec<Option<Vec<u8>>>) -> (Vec<i64>, Vec<i64>, Vec<Vec<u8>>) {
+    let mut mask = 0u64;
+    let mut empty_mask = 0u64;
+    let mut arrays = Vec::new();
+    for (index, section) in sections.into_iter().enumerate() {
+        match section {
+            Some(array) => {
+                mask |= 1 << index;
+                arrays.push(array);
+            }
+            None => empty_mask |= 1 << index,
+        }
+    }
+    (vec![mask as i64], vec![empty_mask as i64], arrays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{BEDROCK, DIRT, GRASS_BLOCK, STONE};
+
+    /// State 1 gives off light like a torch, state 2 stops all light
+    struct Torches;
+    impl LightProperties for Torches {
+        fn emission(&self, state: u32) -> u8 {
+            if state == 1 {
+                14
+            } else {
+                0
+            }
+        }
+
+        fn opacity(&self, state: u32) -> u8 {
+            if state == 2 {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn block_light_spreads_around_walls() {
+        let mut chunk = Chunk::new(0, 0, 0);
+        chunk.set_block(8, 0, 8, 1);
+        for z in 0..16 {
+            for y in -4..4 {
+                chunk.set_block(10, y, z, 2);
+            }
+        }
+        let light = ChunkLight::calculate(&chunk, &Torches);
+        assert_eq!(light.block_light(8, 0, 8), 14);
+        assert_eq!(light.block_light(9, 0, 8), 13);
+        assert_eq!(light.block_light(8, 3, 5), 8);
+        assert_eq!(light.block_light(10, 0, 8), 0);
+        // Over the top of the wall, 11 blocks away
+        assert_eq!(light.block_light(11, 0, 8), 14 - 11);
+    }
+
+    #[test]
+    fn sky_light_stops_at_opaque_blocks() {
+        let mut chunk = Chunk::new(0, 0, 0);
+        for z in 0..16 {
+            for x in 0..16 {
+                chunk.set_block(x, 0, z, 2);
+            }
+        }
+        let light = ChunkLight::calculate(&chunk, &Torches);
+        assert_eq!(light.sky_light(3, 1, 3), MAX_LIGHT);
+        assert_eq!(light.sky_light(3, 0, 3), 0);
+        assert_eq!(light.sky_light(3, -1, 3), 0);
+    }
+
+    #[test]
+    fn table_is_indexed_by_state_id() {
+        assert_eq!(BlockLight.opacity(AIR), 0);
+        for state in [STONE, GRASS_BLOCK, DIRT, BEDROCK] {
+            assert_eq!(BlockLight.opacity(state), MAX_LIGHT, "{state}");
+            assert_eq!(BlockLight.emission(state), 0, "{state}");
+        }
+        for block in BLOCKS {
+            for state in block.states() {
+                let name = block.name.trim_start_matches("minecraft:");
+                let get = |property: &str| state.get(property);
+                assert_eq!(BlockLight.opacity(state.id()), opacity(name, get));
+                assert_eq!(BlockLight.emission(state.id()), emission(name, get));
+            }
+        }
+    }
+
+    #[test]
+    fn opacity_of_blocks_by_name() {
+        let none = |_: &str| None;
+        for name in [
+            "stone",
+            "amethyst_block",
+            "tinted_glass",
+            "muddy_mangrove_roots",
+        ] {
+            assert_eq!(opacity(name, none), MAX_LIGHT, "{name}");
+        }
+        for name in [
+            "glass",
+            "farmland",
+            "dirt_path",
+            "oak_fence",
+            "potted_poppy",
+            "torch",
+        ] {
+            assert_eq!(opacity(name, none), 0, "{name}");
+        }
+        for name in ["water", "ice", "oak_leaves"] {
+            assert_eq!(opacity(name, none), 1, "{name}");
+        }
+        let slab = |kind: &'static str| move |property: &str| (property == "type").then_some(kind);
+        assert_eq!(opacity("oak_slab", slab("bottom")), 0);
+        assert_eq!(opacity("oak_slab", slab("double")), MAX_LIGHT);
+        let waterlogged = |property: &str| (property == "waterlogged").then_some("true");
+        assert_eq!(opacity("oak_stairs", waterlogged), 1);
+    }
+
+    #[test]
+    fn emission_of_blocks_by_name() {
+        let none = |_: &str| None;
+        let lit = |lit: &'static str| move |property: &str| (property == "lit").then_some(lit);
+        assert_eq!(emission("glowstone", none), 15);
+        assert_eq!(emission("torch", none), 14);
+        assert_eq!(emission("furnace", lit("true")), 13);
+        assert_eq!(emission("furnace", lit("false")), 0);
+        assert_eq!(emission("stone", none), 0);
+        let candles = |property: &str| match property {
+            "lit" => Some("true"),
+            "candles" => Some("3"),
+            _ => None,
+        };
+        assert_eq!(emission("red_candle", candles), 9);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{codec::MAX_FRAME_SIZE, protocol::ConnectionState};
+
+/// Default maximum length of a string in characters
+pub const MAX_STRING_LENGTH: usize = 32767;
+/// Maximum length of a JSON chat component in characters
+pub const MAX_CHAT_LENGTH: usize = 262144;
+pub const MAX_USERNAME_LENGTH: usize = 16;
+pub const MAX_HOSTNAME_LENGTH: usize = 255;
+/// Maximum number of properties on a game profile
+pub const MAX_PROFILE_PROPERTIES: usize = 16;
+/// Maximum length of data encrypted with the server's RSA key, enough for 4096-bit keys
+pub const MAX_ENCRYPTED_LENGTH: usize = 512;
+/// Maximum payload of a Login Plugin Response
+pub const MAX_LOGIN_PLUGIN_PAYLOAD: usize = 1 << 20;
+
+/// Maximum length of a chat message or command sent by a client
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+/// Maximum number of signed arguments in a Chat Command
+pub const MAX_ARGUMENT_SIGNATURES: usize = 8;
+/// Maximum length of the text typed before asking for command suggestions
+pub const MAX_COMMAND_SUGGESTION_LENGTH: usize = 32500;
+/// Maximum size of the payload of a Plugin Message
+pub const MAX_PLUGIN_MESSAGE_LENGTH: usize = 32767;
+/// Maximum length of a line of text on a sign
+pub const MAX_SIGN_LINE_LENGTH: usize = 384;
+pub const MAX_BOOK_PAGES: usize = 200;
+pub const MAX_BOOK_PAGE_LENGTH: usize = 8192;
+pub const MAX_BOOK_TITLE_LENGTH: usize = 128;
+/// Maximum number of slots changed by one Click Container
+pub const MAX_CHANGED_SLOTS: usize = 128;
+/// Maximum length of the chat session's public key, and of Mojang's signature of it
+pub const MAX_SESSION_KEY_LENGTH: usize = 512;
+pub const MAX_SESSION_KEY_SIGNATURE_LENGTH: usize = 4096;
+
+/// Maximum nesting of compound and list tags in NBT
+pub const MAX_NBT_DEPTH: usize = 512;
+/// Maximum number of bytes an NBT tag may take up in a packet
+pub const MAX_NBT_SIZE: usize = 1 << 21;
+
+/// Largest serverbound packet a client may send in `state`, including the packet ID. Anything
+/// bigger is rejected before its data is read.
+pub fn max_packet_size(state: ConnectionState) -> usize {
+    match state {
+        // Protocol version, hostname, port and next state
+        ConnectionState::Handshaking => 1 + 5 + 3 + MAX_HOSTNAME_LENGTH * 3 + 2 + 1,
+        // Status Ping with its 8-byte payload
+        ConnectionState::Status => 1 + 8,
+        ConnectionState::Login => 1 + 5 + 1 + MAX_LOGIN_PLUGIN_PAYLOAD,
+        ConnectionState::Play | ConnectionState::Done => MAX_FRAME_SIZE,
+    }
+}
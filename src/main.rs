@@ -1,20 +1,53 @@
-use tokio::net::TcpListener;
+use std::env;
 
-use minecraft_protocol::connection::Connection;
+use tokio::signal;
+
+use minecraft_protocol::{
+    generator::FlatGenerator, keylog::KeyLog, proxy::Proxy, server::ServerBuilder,
+};
 
 const ADDRESS: &str = "0.0.0.0:25565";
+const KEY_FILE: &str = "server_key.pem";
+const KEY_SIZE: u32 = minecraft_protocol::crypto::DEFAULT_KEY_SIZE;
+/// Only let players in `whitelist.json` join
+const WHITELIST: bool = false;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let listener = TcpListener::bind(ADDRESS).await.unwrap();
+    let mut builder = ServerBuilder::new()
+        .address(ADDRESS)
+        .key_file(KEY_FILE, KEY_SIZE)
+        .whitelist(WHITELIST)
+        .generator(FlatGenerator::default());
 
-    println!("Listening on {ADDRESS}...");
+    // Setting MC_WORLD loads and saves the vanilla world folder at that path, with superflat
+    // chunks where it has none. Otherwise changes to the world are lost when the server stops.
+    if let Some(dir) = env::var_os("MC_WORLD") {
+        builder = builder.world_dir(dir);
+    }
 
-    while let Ok((stream, address)) = listener.accept().await {
-        println!("New connection from {address}");
-        tokio::spawn(async move {
-            let mut connection = Connection::new(stream).await;
-            connection.handle().await.unwrap();
-        });
+    // Passing an upstream `host:port` relays logged-in players to that offline-mode server
+    if let Some(upstream) = env::args().nth(1) {
+        let (host, port) = upstream.rsplit_once(':').unwrap_or((&upstream, "25565"));
+        // Setting MC_PROXY_VERBOSE logs the raw bytes of every relayed packet
+        let verbose = env::var_os("MC_PROXY_VERBOSE").is_some();
+        builder = builder.proxy(Proxy::new(host, port.parse().unwrap()).verbose(verbose));
+    }
+    // Setting MC_CAPTURE_DIR records every connection to a capture file in that directory
+    if let Some(capture_dir) = env::var_os("MC_CAPTURE_DIR") {
+        builder = builder.capture_dir(capture_dir);
     }
+    // Setting MC_KEYLOGFILE exports the shared secret of every connection, like SSLKEYLOGFILE
+    if let Some(path) = env::var_os("MC_KEYLOGFILE") {
+        builder = builder.key_log(KeyLog::open(path).unwrap());
+    }
+
+    let server = builder.build().await.unwrap();
+    // Stopping with Ctrl+C saves the world before exiting
+    server
+        .run_until(async {
+            signal::ctrl_c().await.unwrap();
+        })
+        .await
+        .unwrap();
 }
@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    limits::{MAX_NBT_DEPTH, MAX_NBT_SIZE},
+    protocol::{Reader, Writer},
+};
+
+/// A Named Binary Tag, the format of item data, block entities, chunks and registries
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+impl Tag {
+    /// ID of the tag's type, written before it
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+}
+
+/// Named tags, sorted by name
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Compound {
+    entries: BTreeMap<String, Tag>,
+}
+impl Compound {
+    pub fn new() -> Compound {
+        Compound::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        self.entries.get(name)
+    }
+
+    /// Set the tag called `name`, replacing the one already there
+    pub fn insert(&mut self, name: &str, tag: Tag) {
+        self.entries.insert(name.to_string(), tag);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Tag> {
+        self.entries.remove(name)
+    }
+
+    /// Builder version of [`Compound::insert`]
+    pub fn with(mut self, name: &str, tag: impl Into<Tag>) -> Compound {
+        self.insert(name, tag.into());
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Tag)> {
+        self.entries.iter().map(|(name, tag)| (name.as_str(), tag))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl From<i8> for Tag {
+    fn from(value: i8) -> Self {
+        Tag::Byte(value)
+    }
+}
+impl From<bool> for Tag {
+    fn from(value: bool) -> Self {
+        Tag::Byte(value as i8)
+    }
+}
+impl From<i16> for Tag {
+    fn from(value: i16) -> Self {
+        Tag::Short(value)
+    }
+}
+impl From<i32> for Tag {
+    fn from(value: i32) -> Self {
+        Tag::Int(value)
+    }
+}
+impl From<i64> for Tag {
+    fn from(value: i64) -> Self {
+        Tag::Long(value)
+    }
+}
+impl From<f32> for Tag {
+    fn from(value: f32) -> Self {
+        Tag::Float(value)
+    }
+}
+impl From<f64> for Tag {
+    fn from(value: f64) -> Self {
+        Tag::Double(value)
+    }
+}
+impl From<&str> for Tag {
+    fn from(value: &str) -> Self {
+        Tag::String(value.to_string())
+    }
+}
+impl From<String> for Tag {
+    fn from(value: String) -> Self {
+        Tag::String(value)
+    }
+}
+impl From<Compound> for Tag {
+    fn from(value: Compound) -> Self {
+        Tag::Compound(value)
+    }
+}
+impl From<Vec<Tag>> for Tag {
+    fn from(value: Vec<Tag>) -> Self {
+        Tag::List(value)
+    }
+}
+
+/// Read a root tag with its name, as sent over the network. `None` is an empty tag, which has
+/// only the End type. The tag may be at most [`MAX_NBT_SIZE`] bytes and [`MAX_NBT_DEPTH`] deep.
+pub fn read_named(cursor: &mut Reader) -> Result<Option<(String, Tag)>> {
+    read_named_limited(cursor, MAX_NBT_SIZE)
+}
+
+/// Read a root tag with its name that may be up to `max_size` bytes, for tags from files
+pub fn read_named_limited(cursor: &mut Reader, max_size: usize) -> Result<Option<(String, Tag)>> {
+    let mut reader = TagReader {
+        start: cursor.remaining(),
+        max_size,
+        cursor,
+        depth: 0,
+    };
+    let id = reader.cursor.read_u8()?;
+    if id == 0 {
+        return Ok(None);
+    }
+    let name = reader.read_string()?;
+    let tag = reader.read_payload(id)?;
+    Ok(Some((name, tag)))
+}
+
+/// Read a root tag that is expected to be a compound, ignoring its name
+pub fn read_compound(cursor: &mut Reader) -> Result<Option<Compound>> {
+    read_compound_limited(cursor, MAX_NBT_SIZE)
+}
+
+/// Read a root compound that may be up to `max_size` bytes, ignoring its name
+pub fn read_compound_limited(cursor: &mut Reader, max_size: usize) -> Result<Option<Compound>> {
+    match read_named_limited(cursor, max_size)? {
+        None => Ok(None),
+        Some((_, Tag::Compound(compound))) => Ok(Some(compound)),
+        Some((_, tag)) => Err(anyhow!("Expected a compound tag, got type {}", tag.id())),
+    }
+}
+
+/// Write a root tag with its name, as sent over the network
+pub fn write_named(writer: &mut Writer, name: &str, tag: &Tag) {
+    writer.write_u8(tag.id());
+    write_string(writer, name);
+    write_payload(writer, tag);
+}
+
+/// Write a root compound with an empty name, or only the End type for `None`
+pub fn write_compound(writer: &mut Writer, compound: Option<&Compound>) {
+    match compound {
+        Some(compound) => {
+            writer.write_u8(10);
+            write_string(writer, "");
+            write_compound_payload(writer, compound);
+        }
+        None => writer.write_u8(0),
+    }
+}
+
+fn write_string(writer: &mut Writer, string: &str) {
+    writer.write_u16(string.len() as u16);
+    writer.write_raw(string.as_bytes());
+}
+
+fn write_compound_payload(writer: &mut Writer, compound: &Compound) {
+    for (name, tag) in compound.iter() {
+        write_named(writer, name, tag);
+    }
+    writer.write_u8(0);
+}
+
+fn write_payload(writer: &mut Writer, tag: &Tag) {
+    match tag {
+        Tag::Byte(value) => writer.write_i8(*value),
+        Tag::Short(value) => writer.write_i16(*value),
+        Tag::Int(value) => writer.write_i32(*value),
+        Tag::Long(value) => writer.write_i64(*value),
+        Tag::Float(value) => writer.write_f32(*value),
+        Tag::Double(value) => writer.write_f64(*value),
+        Tag::ByteArray(array) => {
+            writer.write_i32(array.len() as i32);
+            for value in array {
+                writer.write_i8(*value);
+            }
+        }
+        Tag::String(string) => write_string(writer, string),
+        Tag::List(list) => {
+            // Empty lists are written with the End type, like vanilla does
+            writer.write_u8(list.first().map_or(0, Tag::id));
+            writer.write_i32(list.len() as i32);
+            for tag in list {
+                write_payload(writer, tag);
+            }
+        }
+        Tag::Compound(compound) => write_compound_payload(writer, compound),
+        Tag::IntArray(array) => {
+            writer.write_i32(array.len() as i32);
+            for value in array {
+                writer.write_i32(*value);
+            }
+        }
+        Tag::LongArray(array) => {
+            writer.write_i32(array.len() as i32);
+            for value in array {
+                writer.write_i64(*value);
+            }
+        }
+    }
+}
+
+/// Keeps track of the limits while reading one root tag
+struct TagReader<'a> {
+    cursor: &'a mut Reader,
+    /// Bytes left in the cursor before the root tag
+    start: usize,
+    max_size: usize,
+    depth: usize,
+}
+impl TagReader<'_> {
+    fn check_size(&self, extra: usize) -> Result<()> {
+        let size = self.start - self.cursor.remaining() + extra;
+        if size > self.max_size {
+            return Err(anyhow!(
+                "NBT of {size} bytes exceeds the maximum of {}",
+                self.max_size
+            ));
+        }
+        Ok(())
+    }
+
+    /// Length of an array or list, checking that its elements of `element_size` fit
+    fn read_length(&mut self, element_size: usize) -> Result<usize> {
+        let length = self.cursor.read_i32()?;
+        let length = usize::try_from(length).map_err(|_| anyhow!("Negative length {length}"))?;
+        self.check_size(length.saturating_mul(element_size))?;
+        Ok(length)
+    }
+
+    /// Strings are prefixed with their length in bytes as an unsigned short
+    fn read_string(&mut self) -> Result<String> {
+        let length = self.cursor.read_u16()? as usize;
+        let bytes = self.cursor.read_bytes(length)?;
+        // Java's modified UTF-8 only differs for null characters and surrogate pairs
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn read_payload(&mut self, id: u8) -> Result<Tag> {
+        self.check_size(0)?;
+        let tag = match id {
+            1 => Tag::Byte(self.cursor.read_i8()?),
+            2 => Tag::Short(self.cursor.read_i16()?),
+            3 => Tag::Int(self.cursor.read_i32()?),
+            4 => Tag::Long(self.cursor.read_i64()?),
+            5 => Tag::Float(self.cursor.read_f32()?),
+            6 => Tag::Double(self.cursor.read_f64()?),
+            7 => {
+                let length = self.read_length(1)?;
+                let bytes = self.cursor.read_bytes(length)?;
+                Tag::ByteArray(bytes.iter().map(|&byte| byte as i8).collect())
+            }
+            8 => Tag::String(self.read_string()?),
+            9 => {
+                let element_id = self.cursor.read_u8()?;
+                let length = self.read_length(1)?;
+                if element_id == 0 && length > 0 {
+                    return Err(anyhow!("List of {length} End tags"));
+                }
+                self.enter()?;
+                // Every element takes up at least a byte, so the size check bounds this loop
+                let mut list = Vec::new();
+                for _ in 0..length {
+                    list.push(self.read_payload(element_id)?);
+                }
+                self.depth -= 1;
+                Tag::List(list)
+            }
+            10 => {
+                self.enter()?;
+                let mut compound = Compound::new();
+                loop {
+                    let id = self.cursor.read_u8()?;
+                    if id == 0 {
+                        break;
+                    }
+                    let name = self.read_string()?;
+                    let tag = self.read_payload(id)?;
+                    compound.entries.insert(name, tag);
+                }
+                self.depth -= 1;
+                Tag::Compound(compound)
+            }
+            11 => {
+                let length = self.read_length(4)?;
+                let mut array = Vec::with_capacity(length.min(self.cursor.remaining() / 4));
+                for _ in 0..length {
+                    array.push(self.cursor.read_i32()?);
+                }
+                Tag::IntArray(array)
+            }
+            12 => {
+                let length = self.read_length(8)?;
+                let mut array = Vec::with_capacity(length.min(self.cursor.remaining() / 8));
+                for _ in 0..length {
+                    array.push(self.cursor.read_i64()?);
+                }
+                Tag::LongArray(array)
+            }
+            _ => return Err(anyhow!("Invalid NBT tag type {id}")),
+        };
+        Ok(tag)
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_NBT_DEPTH {
+            return Err(anyhow!(
+                "NBT nested deeper than the maximum of {MAX_NBT_DEPTH}"
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    Notify,
+};
+
+use crate::{
+    capture::{CaptureWriter, Direction},
+    protocol::{
+        play::clientbound::ClientboundPlayPacket, ConnectionState, Disconnect, LoginDisconnect,
+    },
+    stream::{Packet, PacketWriter},
+};
+
+/// Messages a connection may have waiting for its writer task. A client that falls further
+/// behind than this, usually because it stopped reading, is disconnected.
+pub const MAX_QUEUED: usize = 1024;
+
+/// Everything that changes what goes out over a connection, handled in order by its writer task
+pub(crate) enum Outbound {
+    Packet(Packet),
+    /// A Play packet, encoded by the writer task
+    Play(ClientboundPlayPacket),
+    /// The state the following packets are sent in
+    State(ConnectionState),
+    EnableEncryption([u8; 16]),
+    Record(CaptureWriter),
+    /// Send a disconnect packet if the state has one, then close
+    Kick(String),
+    Close,
+}
+
+/// What the handles of a connection send, in order
+pub(crate) struct OutboundReceiver {
+    receiver: mpsc::Receiver<Outbound>,
+    behind: Arc<Notify>,
+}
+impl OutboundReceiver {
+    /// The next message, or an error once the client fell too far behind
+    pub(crate) async fn recv(&mut self) -> Result<Option<Outbound>> {
+        tokio::select! {
+            biased;
+            _ = self.behind.notified() => Err(anyhow!("Client fell too far behind")),
+            message = self.receiver.recv() => Ok(message),
+        }
+    }
+}
+
+/// Sends packets to a [`Connection`](crate::connection::Connection) from any task. Cloning the
+/// handle is cheap, and it keeps working until the connection closes.
+#[derive(Debug, Clone)]
+pub struct ConnectionHandle {
+    id: u64,
+    outbound: mpsc::Sender<Outbound>,
+    /// Notified when the queue is full, to stop the writer task
+    behind: Arc<Notify>,
+}
+impl ConnectionHandle {
+    pub(crate) fn new(id: u64) -> (ConnectionHandle, OutboundReceiver) {
+        let (outbound, receiver) = mpsc::channel(MAX_QUEUED);
+        let behind = Arc::new(Notify::new());
+        let handle = ConnectionHandle {
+            id,
+            outbound,
+            behind: behind.clone(),
+        };
+        (handle, OutboundReceiver { receiver, behind })
+    }
+
+    /// ID of the connection, see [`Connection::id`](crate::connection::Connection::id)
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Queue a packet, returning `false` if the connection is already closed or too far behind
+    pub fn send(&self, packet: Packet) -> bool {
+        self.push(Outbound::Packet(packet)).is_ok()
+    }
+
+    /// Queue a Play packet, returning `false` if the connection is already closed or too far
+    /// behind
+    pub fn send_play(&self, packet: ClientboundPlayPacket) -> bool {
+        self.push(Outbound::Play(packet)).is_ok()
+    }
+
+    /// Disconnect the client with a reason, after the packets queued before it
+    pub fn kick(&self, reason: &str) -> bool {
+        self.push(Outbound::Kick(reason.to_string())).is_ok()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.outbound.is_closed()
+    }
+
+    /// Number of messages waiting to be written
+    pub fn queued(&self) -> usize {
+        MAX_QUEUED - self.outbound.capacity()
+    }
+
+    /// Wait until the connection stops sending
+    pub async fn closed(&self) {
+        self.outbound.closed().await
+    }
+
+    /// Queue a message, closing the connection instead if its queue is full
+    pub(crate) fn push(&self, message: Outbound) -> Result<()> {
+        match self.outbound.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.behind.notify_one();
+                Err(anyhow!("Connection is too far behind"))
+            }
+            Err(TrySendError::Closed(_)) => Err(anyhow!("Connection is closed")),
+        }
+    }
+}
+
+/// The packet that tells a client in `state` why it is disconnected, if that state has one
+pub fn disconnect_packet(state: ConnectionState, reason: &str) -> Result<Option<Packet>> {
+    let reason = json!({ "text": reason });
+    Ok(match state {
+        ConnectionState::Login => {
+            let response = LoginDisconnect::new(reason);
+            println!("> {response:?}");
+            Some(Packet {
+                id: 0x00,
+                data: response.try_into()?,
+            })
+        }
+        ConnectionState::Play => {
+            let response = Disconnect::new(reason);
+            println!("> {response:?}");
+            Some(ClientboundPlayPacket::Disconnect(response).try_into()?)
+        }
+        _ => None,
+    })
+}
+
+/// Write everything sent to a connection until it is closed or kicked, giving back the writer
+/// and the receiver of its handles, so they can be wired to a relay. Stops with an error as soon as the
+/// client falls too far behind, even while a write is waiting on it.
+pub(crate) async fn write_outbound(
+    mut writer: PacketWriter,
+    mut outbound: OutboundReceiver,
+) -> Result<(PacketWriter, OutboundReceiver)> {
+    let behind = outbound.behind.clone();
+    tokio::select! {
+        result = write_messages(&mut writer, &mut outbound.receiver) => result?,
+        _ = behind.notified() => return Err(anyhow!("Client fell too far behind")),
+    }
+    Ok((writer, outbound))
+}
+
+async fn write_messages(
+    writer: &mut PacketWriter,
+    outbound: &mut mpsc::Receiver<Outbound>,
+) -> Result<()> {
+    let mut state = ConnectionState::Handshaking;
+    let mut capture = None;
+
+    while let Some(message) = outbound.recv().await {
+        match message {
+            Outbound::Packet(packet) => write(writer, state, &capture, packet).await?,
+            Outbound::Play(packet) => write(writer, state, &capture, packet.try_into()?).await?,
+            Outbound::State(new_state) => state = new_state,
+            Outbound::EnableEncryption(shared_secret) => writer.enable_encryption(&shared_secret),
+            Outbound::Record(new_capture) => capture = Some(new_capture),
+            Outbound::Kick(reason) => {
+                if let Some(packet) = disconnect_packet(state, &reason)? {
+                    write(writer, state, &capture, packet).await?;
+                }
+                // Closing the receiver is what tells the connection it was kicked
+                outbound.close();
+                break;
+            }
+            Outbound::Close => break,
+        }
+    }
+    Ok(())
+}
+
+async fn write(
+    writer: &mut PacketWriter,
+    state: ConnectionState,
+    capture: &Option<CaptureWriter>,
+    packet: Packet,
+) -> Result<()> {
+    if let Some(capture) = capture {
+        capture.record(Direction::Clientbound, state, &packet)?;
+    }
+    writer.write_packet(packet).await
+}
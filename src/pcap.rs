@@ -0,0 +1,354 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_ACK: u8 = 0x10;
+
+/// One direction of a reassembled TCP connection
+#[derive(Debug, Default)]
+pub struct TcpFlow {
+    /// Payload bytes in sequence order, without retransmissions
+    pub data: Vec<u8>,
+    /// Capture time of each segment, by the offset in `data` it starts at
+    pub timestamps: Vec<(usize, Duration)>,
+    base_seq: Option<u32>,
+    pending: BTreeMap<u32, (Vec<u8>, Duration)>,
+}
+impl TcpFlow {
+    /// Capture time of the segment containing the byte at `offset`
+    pub fn timestamp_at(&self, offset: usize) -> Duration {
+        match self
+            .timestamps
+            .binary_search_by_key(&offset, |(start, _)| *start)
+        {
+            Ok(i) => self.timestamps[i].1,
+            Err(0) => Duration::ZERO,
+            Err(i) => self.timestamps[i - 1].1,
+        }
+    }
+
+    fn add_segment(&mut self, seq: u32, flags: u8, payload: &[u8], timestamp: Duration) {
+        if flags & TCP_SYN != 0 {
+            self.base_seq = Some(seq.wrapping_add(1));
+        }
+        if payload.is_empty() {
+            return;
+        }
+        let base = *self.base_seq.get_or_insert(seq);
+        let relative = seq.wrapping_sub(base);
+
+        self.pending
+            .entry(relative)
+            .or_insert_with(|| (payload.to_vec(), timestamp));
+        // Append every pending segment that continues the data, skipping what was already seen
+        while let Some((&relative, _)) = self.pending.first_key_value() {
+            let length = self.data.len() as u32;
+            if relative > length {
+                break;
+            }
+            let (payload, timestamp) = self.pending.remove(&relative).unwrap();
+            let skip = (length - relative) as usize;
+            if skip < payload.len() {
+                self.timestamps.push((self.data.len(), timestamp));
+                self.data.extend_from_slice(&payload[skip..]);
+            }
+        }
+    }
+}
+
+/// Both directions of a TCP connection found in a capture
+#[derive(Debug)]
+pub struct TcpSession {
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    /// Capture time of the first segment, since the Unix epoch
+    pub started: Duration,
+    pub serverbound: TcpFlow,
+    pub clientbound: TcpFlow,
+    pub closed: bool,
+}
+
+/// Read a capture in the classic libpcap format, as written by `tcpdump -w`
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<TcpSession>> {
+    read_sessions(&std::fs::read(path)?)
+}
+
+/// Reassemble all TCP connections in a pcap capture, in the order they started
+pub fn read_sessions(bytes: &[u8]) -> Result<Vec<TcpSession>> {
+    if bytes.len() < 24 {
+        return Err(anyhow!("File is too short for a pcap header"));
+    }
+    let magic: [u8; 4] = bytes[0..4].try_into()?;
+    let (big_endian, nanoseconds) = match magic {
+        [0xd4, 0xc3, 0xb2, 0xa1] => (false, false),
+        [0xa1, 0xb2, 0xc3, 0xd4] => (true, false),
+        [0x4d, 0x3c, 0xb2, 0xa1] => (false, true),
+        [0xa1, 0xb2, 0x3c, 0x4d] => (true, true),
+        [0x0a, 0x0d, 0x0d, 0x0a] => {
+            return Err(anyhow!(
+                "pcapng is not supported, convert it with `editcap -F pcap`"
+            ))
+        }
+        _ => return Err(anyhow!("Not a pcap file")),
+    };
+    let read_u32 = |offset: usize| -> u32 {
+        let value: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(value)
+        } else {
+            u32::from_le_bytes(value)
+        }
+    };
+    let link_type = read_u32(20) & 0xffff;
+
+    let mut sessions: Vec<TcpSession> = Vec::new();
+    let mut indices: HashMap<(SocketAddr, SocketAddr), usize> = HashMap::new();
+
+    let mut offset = 24;
+    while offset + 16 <= bytes.len() {
+        let seconds = read_u32(offset) as u64;
+        let fraction = read_u32(offset + 4);
+        let captured_length = read_u32(offset + 8) as usize;
+        let timestamp = Duration::from_secs(seconds)
+            + match nanoseconds {
+                true => Duration::from_nanos(fraction as u64),
+                false => Duration::from_micros(fraction as u64),
+            };
+        offset += 16;
+        let frame = bytes
+            .get(offset..offset + captured_length)
+            .ok_or(anyhow!("Truncated packet at offset {offset}"))?;
+        offset += captured_length;
+
+        let Some(segment) = parse_frame(link_type, frame) else {
+            continue;
+        };
+
+        let key = if segment.source < segment.destination {
+            (segment.source, segment.destination)
+        } else {
+            (segment.destination, segment.source)
+        };
+        // Every SYN starts a new connection, even when an earlier one used the same ports. A
+        // retransmitted SYN has the sequence number of the first one.
+        if segment.flags & TCP_SYN != 0 && segment.flags & TCP_ACK == 0 {
+            let retransmitted = indices.get(&key).is_some_and(|&index| {
+                let session = &sessions[index];
+                session.client == segment.source
+                    && session.serverbound.base_seq == Some(segment.seq.wrapping_add(1))
+            });
+            if !retransmitted {
+                indices.remove(&key);
+            }
+        }
+        let index = *indices.entry(key).or_insert_with(|| {
+            // The side sending the first SYN is the client, otherwise assume it is whoever
+            // spoke first
+            let is_syn_ack = segment.flags & TCP_SYN != 0 && segment.flags & TCP_ACK != 0;
+            let (client, server) = match is_syn_ack {
+                true => (segment.destination, segment.source),
+                false => (segment.source, segment.destination),
+            };
+            sessions.push(TcpSession {
+                client,
+                server,
+                started: timestamp,
+                serverbound: TcpFlow::default(),
+                clientbound: TcpFlow::default(),
+                closed: false,
+            });
+            sessions.len() - 1
+        });
+
+        let session = &mut sessions[index];
+        let flow = match segment.source == session.client {
+            true => &mut session.serverbound,
+            false => &mut session.clientbound,
+        };
+        flow.add_segment(
+            segment.seq,
+            segment.flags,
+            segment.payload,
+            timestamp.saturating_sub(session.started),
+        );
+        if segment.flags & TCP_FIN != 0 {
+            session.closed = true;
+        }
+    }
+
+    Ok(sessions)
+}
+
+struct TcpSegment<'a> {
+    source: SocketAddr,
+    destination: SocketAddr,
+    seq: u32,
+    flags: u8,
+    payload: &'a [u8],
+}
+
+fn parse_frame(link_type: u32, frame: &[u8]) -> Option<TcpSegment<'_>> {
+    let (ethertype, packet) = match link_type {
+        // BSD loopback, with the address family in host byte order
+        0 => {
+            let family = u32::from_le_bytes(frame.get(0..4)?.try_into().ok()?);
+            let family = match family > 0xffff {
+                true => family.swap_bytes(),
+                false => family,
+            };
+            match family {
+                2 => (0x0800, frame.get(4..)?),
+                24 | 28 | 30 => (0x86dd, frame.get(4..)?),
+                _ => return None,
+            }
+        }
+        // Ethernet, optionally with a VLAN tag
+        1 => {
+            let mut ethertype = u16::from_be_bytes(frame.get(12..14)?.try_into().ok()?);
+            let mut start = 14;
+            if ethertype == 0x8100 {
+                ethertype = u16::from_be_bytes(frame.get(16..18)?.try_into().ok()?);
+                start = 18;
+            }
+            (ethertype, frame.get(start..)?)
+        }
+        // Raw IP
+        12 | 101 => match frame.first()? >> 4 {
+            4 => (0x0800, frame),
+            6 => (0x86dd, frame),
+            _ => return None,
+        },
+        228 => (0x0800, frame),
+        229 => (0x86dd, frame),
+        // Linux cooked capture v1 and v2
+        113 => (
+            u16::from_be_bytes(frame.get(14..16)?.try_into().ok()?),
+            frame.get(16..)?,
+        ),
+        276 => (
+            u16::from_be_bytes(frame.get(0..2)?.try_into().ok()?),
+            frame.get(20..)?,
+        ),
+        _ => return None,
+    };
+
+    let (source, destination, segment) = match ethertype {
+        0x0800 => {
+            let header_length = ((packet.first()? & 0x0f) as usize) * 4;
+            let total_length = u16::from_be_bytes(packet.get(2..4)?.try_into().ok()?) as usize;
+            if *packet.get(9)? != 6 {
+                return None;
+            }
+            let source: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+            (
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+                // Ethernet frames can be padded past the end of the IP packet
+                packet.get(header_length..total_length.min(packet.len()))?,
+            )
+        }
+        0x86dd => {
+            let payload_length = u16::from_be_bytes(packet.get(4..6)?.try_into().ok()?) as usize;
+            if *packet.get(6)? != 6 {
+                return None;
+            }
+            let source: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+            (
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+                packet.get(40..(40 + payload_length).min(packet.len()))?,
+            )
+        }
+        _ => return None,
+    };
+
+    let source_port = u16::from_be_bytes(segment.get(0..2)?.try_into().ok()?);
+    let destination_port = u16::from_be_bytes(segment.get(2..4)?.try_into().ok()?);
+    let seq = u32::from_be_bytes(segment.get(4..8)?.try_into().ok()?);
+    let data_offset = ((segment.get(12)? >> 4) as usize) * 4;
+    let flags = *segment.get(13)?;
+
+    Some(TcpSegment {
+        source: SocketAddr::new(source, source_port),
+        destination: SocketAddr::new(destination, destination_port),
+        seq,
+        flags,
+        payload: segment.get(data_offset..)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 1];
+    const SERVER: [u8; 4] = [10, 0, 0, 2];
+
+    /// A raw IPv4 frame with a TCP segment from the client's port 50000 to the server's port
+    /// 25565, or the other way around
+    fn frame(serverbound: bool, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let (source, destination, ports) = match serverbound {
+            true => (CLIENT, SERVER, [50000u16, 25565]),
+            false => (SERVER, CLIENT, [25565, 50000]),
+        };
+        let mut frame = vec![0x45, 0];
+        frame.extend(((20 + 20 + payload.len()) as u16).to_be_bytes());
+        frame.extend([0, 0, 0, 0, 64, 6, 0, 0]);
+        frame.extend(source);
+        frame.extend(destination);
+        frame.extend(ports[0].to_be_bytes());
+        frame.extend(ports[1].to_be_bytes());
+        frame.extend(seq.to_be_bytes());
+        frame.extend([0, 0, 0, 0, 5 << 4, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        frame.extend(payload);
+        frame
+    }
+
+    fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        bytes.extend([0; 8]);
+        bytes.extend(65535u32.to_le_bytes());
+        bytes.extend(101u32.to_le_bytes());
+        for (i, frame) in frames.iter().enumerate() {
+            bytes.extend((i as u32).to_le_bytes());
+            bytes.extend(0u32.to_le_bytes());
+            bytes.extend((frame.len() as u32).to_le_bytes());
+            bytes.extend((frame.len() as u32).to_le_bytes());
+            bytes.extend(frame);
+        }
+        bytes
+    }
+
+    #[test]
+    fn connections_on_the_same_ports_are_separate_sessions() {
+        let mut frames = Vec::new();
+        for (seq, payload) in [(1000, b"first"), (5000, b"again")] {
+            frames.push(frame(true, seq, TCP_SYN, &[]));
+            frames.push(frame(true, seq, TCP_SYN, &[]));
+            frames.push(frame(false, 9000, TCP_SYN | TCP_ACK, &[]));
+            frames.push(frame(true, seq + 1, TCP_ACK, payload));
+            frames.push(frame(false, 9001, TCP_ACK, b"reply"));
+            frames.push(frame(true, seq + 6, TCP_FIN | TCP_ACK, &[]));
+        }
+
+        let sessions = read_sessions(&pcap(&frames)).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].serverbound.data, b"first");
+        assert_eq!(sessions[1].serverbound.data, b"again");
+        for session in &sessions {
+            assert_eq!(session.client.port(), 50000);
+            assert_eq!(session.clientbound.data, b"reply");
+            assert!(session.closed);
+        }
+        assert_eq!(sessions[1].started, Duration::from_secs(6));
+    }
+}
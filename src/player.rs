@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use openssl::hash::{hash, MessageDigest};
 use uuid::Uuid;
 
 use crate::protocol::LoginStart;
@@ -6,12 +9,38 @@ use crate::protocol::LoginStart;
 pub struct Player {
     pub username: String,
     pub uuid: Option<Uuid>,
+    /// Round-trip time of Keep Alives, averaged like the vanilla server does. `None` until the
+    /// first one is answered.
+    pub latency: Option<Duration>,
+}
+impl Player {
+    /// Add a new Keep Alive round trip to the average latency
+    pub fn update_latency(&mut self, round_trip: Duration) {
+        self.latency = Some(match self.latency {
+            Some(latency) => (latency * 3 + round_trip) / 4,
+            None => round_trip,
+        });
+    }
 }
 impl From<LoginStart> for Player {
     fn from(login_start: LoginStart) -> Self {
         Player {
             username: login_start.username,
             uuid: login_start.uuid,
+            latency: None,
         }
     }
 }
+
+/// The UUID an offline-mode server assigns, a version 3 UUID of `OfflinePlayer:<username>`
+pub fn offline_uuid(username: &str) -> Uuid {
+    let digest = hash(
+        MessageDigest::md5(),
+        format!("OfflinePlayer:{username}").as_bytes(),
+    )
+    .unwrap();
+    let mut bytes: [u8; 16] = digest.as_ref().try_into().unwrap();
+    bytes[6] = (bytes[6] & 0x0f) | 0x30;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(bytes)
+}
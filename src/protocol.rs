@@ -1,99 +1,243 @@
 use anyhow::{anyhow, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use mojang_api::{ProfileProperty, ServerAuthResponse};
-use openssl::rsa::Padding;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
-use crate::RSA_KEY_PAIR;
+use crate::{
+    crypto::{self, KeyPair},
+    limits::{
+        MAX_CHAT_LENGTH, MAX_ENCRYPTED_LENGTH, MAX_HOSTNAME_LENGTH, MAX_PROFILE_PROPERTIES,
+        MAX_STRING_LENGTH, MAX_USERNAME_LENGTH,
+    },
+};
+
+pub mod play;
+
+pub const PROTOCOL_VERSION: i32 = 762;
+pub const VERSION_NAME: &str = "1.19.4";
 
 // TODO: clear difference between clientbound and serverbound packets
 
-pub struct Reader<'a> {
-    bytes: &'a [u8],
-    index: usize,
+/// Reads protocol types from the front of a buffer. Byte arrays are returned as slices of the
+/// same buffer, without copying.
+pub struct Reader {
+    bytes: Bytes,
 }
-impl Reader<'_> {
-    pub fn new(bytes: &[u8]) -> Reader {
-        Reader { bytes, index: 0 }
+impl Reader {
+    pub fn new(bytes: Bytes) -> Reader {
+        Reader { bytes }
+    }
+
+    fn need(&self, length: usize) -> Result<()> {
+        if self.bytes.remaining() < length {
+            return Err(anyhow!(
+                "Unexpected end of data, needed {length} bytes but {} are left",
+                self.bytes.remaining()
+            ));
+        }
+        Ok(())
     }
 
     pub fn read_varint(&mut self) -> Result<i32> {
-        let mut read = 0;
         let mut result = 0;
-        loop {
-            let read_value = self.bytes[self.index];
+        for read in 0..5 {
+            let read_value = self.read_u8()?;
             let value = read_value & 0b0111_1111;
-            result |= (value as i32) << (7 * read);
-            read += 1;
-            if read > 5 {
-                return Err(anyhow!("VarInt is too big"));
+            result |= (value as u32) << (7 * read);
+            if (read_value & 0b1000_0000) == 0 {
+                return Ok(result as i32);
             }
-            self.index += 1;
+        }
+        Err(anyhow!("VarInt is too big"))
+    }
+
+    pub fn read_varlong(&mut self) -> Result<i64> {
+        let mut result = 0;
+        for read in 0..10 {
+            let read_value = self.read_u8()?;
+            let value = read_value & 0b0111_1111;
+            result |= (value as u64) << (7 * read);
             if (read_value & 0b1000_0000) == 0 {
-                return Ok(result);
+                return Ok(result as i64);
             }
         }
+        Err(anyhow!("VarLong is too big"))
+    }
+
+    /// Length prefix of a string or array
+    fn read_length(&mut self) -> Result<usize> {
+        let length = self.read_varint()?;
+        usize::try_from(length).map_err(|_| anyhow!("Negative length {length}"))
     }
 
     pub fn read_string(&mut self) -> Result<String> {
-        let length = self.read_varint()? as usize;
-        let string = String::from_utf8(self.bytes[self.index..self.index + length].to_vec())?;
-        self.index += length;
+        self.read_string_max(MAX_STRING_LENGTH)
+    }
+
+    /// Read a string of at most `max_length` characters, checking the length in bytes before
+    /// reading it
+    pub fn read_string_max(&mut self, max_length: usize) -> Result<String> {
+        let length = self.read_length()?;
+        if length > max_length * 4 {
+            return Err(anyhow!(
+                "String of {length} bytes exceeds the maximum of {max_length} characters"
+            ));
+        }
+        let bytes = self.read_bytes(length)?;
+        let string = String::from_utf8(bytes.to_vec())?;
+        let chars = string.encode_utf16().count();
+        if chars > max_length {
+            return Err(anyhow!(
+                "String of {chars} characters exceeds the maximum of {max_length}"
+            ));
+        }
         Ok(string)
     }
 
+    /// Length prefix of an array of at most `max_length` elements
+    pub fn read_array_length(&mut self, max_length: usize) -> Result<usize> {
+        let length = self.read_length()?;
+        if length > max_length {
+            return Err(anyhow!(
+                "Array of {length} elements exceeds the maximum of {max_length}"
+            ));
+        }
+        Ok(length)
+    }
+
+    /// Read `length` bytes, sharing the underlying buffer
+    pub fn read_bytes(&mut self, length: usize) -> Result<Bytes> {
+        self.need(length)?;
+        Ok(self.bytes.split_to(length))
+    }
+
     pub fn read_byte_array(&mut self) -> Result<Vec<u8>> {
-        let length = self.read_varint()? as usize;
-        let array = self.bytes[self.index..self.index + length].to_vec();
-        self.index += length;
-        Ok(array)
+        let length = self.read_length()?;
+        Ok(self.read_bytes(length)?.to_vec())
+    }
+
+    pub fn read_byte_array_max(&mut self, max_length: usize) -> Result<Vec<u8>> {
+        let length = self.read_array_length(max_length)?;
+        Ok(self.read_bytes(length)?.to_vec())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        self.need(1)?;
+        Ok(self.bytes.get_u8())
     }
 
     pub fn read_bool(&mut self) -> Result<bool> {
-        let value = self.bytes[self.index] != 0;
-        self.index += 1;
-        Ok(value)
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_i8(&mut self) -> Result<i8> {
+        self.need(1)?;
+        Ok(self.bytes.get_i8())
     }
 
     pub fn read_u16(&mut self) -> Result<u16> {
-        let value = u16::from_be_bytes(self.bytes[self.index..self.index + 2].try_into()?);
-        self.index += 2;
-        Ok(value)
+        self.need(2)?;
+        Ok(self.bytes.get_u16())
+    }
+    pub fn read_i16(&mut self) -> Result<i16> {
+        self.need(2)?;
+        Ok(self.bytes.get_i16())
+    }
+    pub fn read_i32(&mut self) -> Result<i32> {
+        self.need(4)?;
+        Ok(self.bytes.get_i32())
     }
     pub fn read_i64(&mut self) -> Result<i64> {
-        let value = i64::from_be_bytes(self.bytes[self.index..self.index + 8].try_into()?);
-        self.index += 8;
-        Ok(value)
+        self.need(8)?;
+        Ok(self.bytes.get_i64())
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32> {
+        self.need(4)?;
+        Ok(self.bytes.get_f32())
+    }
+    pub fn read_f64(&mut self) -> Result<f64> {
+        self.need(8)?;
+        Ok(self.bytes.get_f64())
     }
 
     pub fn read_uuid(&mut self) -> Result<Uuid> {
-        let value = Uuid::from_slice(&self.bytes[self.index..self.index + 16])?;
-        self.index += 16;
-        Ok(value)
+        self.need(16)?;
+        Ok(Uuid::from_u128(self.bytes.get_u128()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.bytes.has_remaining()
+    }
+
+    /// Number of bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.bytes.remaining()
+    }
+
+    pub fn get_leftover_bytes(&self) -> Bytes {
+        self.bytes.clone()
     }
+}
 
-    pub fn get_leftover_bytes(&self) -> &[u8] {
-        &self.bytes[self.index..]
+/// Number of bytes `value` takes up as a VarInt
+pub fn varint_len(value: i32) -> usize {
+    match value as u32 {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        0x4000..=0x1f_ffff => 3,
+        0x20_0000..=0xfff_ffff => 4,
+        _ => 5,
+    }
+}
+
+pub fn put_varint(buffer: &mut impl BufMut, value: i32) {
+    let mut value = value as u32;
+    loop {
+        let mut temp = (value & 0b0111_1111) as u8;
+        value >>= 7;
+        if value != 0 {
+            temp |= 0b1000_0000;
+        }
+        buffer.put_u8(temp);
+        if value == 0 {
+            break;
+        }
     }
 }
 
+/// Builds a buffer of protocol types, which is frozen into [`Bytes`] when done
 pub struct Writer {
-    bytes: Vec<u8>,
+    bytes: BytesMut,
 }
 impl Writer {
     pub fn new() -> Writer {
-        Writer { bytes: Vec::new() }
+        Writer {
+            bytes: BytesMut::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Writer {
+        Writer {
+            bytes: BytesMut::with_capacity(capacity),
+        }
     }
 
     pub fn write_varint(&mut self, value: i32) {
-        let mut value = value;
+        put_varint(&mut self.bytes, value);
+    }
+
+    pub fn write_varlong(&mut self, value: i64) {
+        let mut value = value as u64;
         loop {
             let mut temp = (value & 0b0111_1111) as u8;
             value >>= 7;
             if value != 0 {
                 temp |= 0b1000_0000;
             }
-            self.bytes.push(temp);
+            self.bytes.put_u8(temp);
             if value == 0 {
                 break;
             }
@@ -109,15 +253,35 @@ impl Writer {
         self.bytes.extend_from_slice(uuid.as_bytes());
     }
 
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.put_u8(value);
+    }
+    pub fn write_i8(&mut self, value: i8) {
+        self.bytes.put_i8(value);
+    }
+
     pub fn write_u16(&mut self, value: u16) {
-        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self.bytes.put_u16(value);
+    }
+    pub fn write_i16(&mut self, value: i16) {
+        self.bytes.put_i16(value);
+    }
+    pub fn write_i32(&mut self, value: i32) {
+        self.bytes.put_i32(value);
     }
     pub fn write_i64(&mut self, value: i64) {
-        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self.bytes.put_i64(value);
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.bytes.put_f32(value);
+    }
+    pub fn write_f64(&mut self, value: f64) {
+        self.bytes.put_f64(value);
     }
 
     pub fn write_bool(&mut self, value: bool) {
-        self.bytes.push(value as u8);
+        self.bytes.put_u8(value as u8);
     }
 
     pub fn write_raw(&mut self, bytes: &[u8]) {
@@ -136,9 +300,9 @@ impl Default for Writer {
         Self::new()
     }
 }
-impl From<Writer> for Vec<u8> {
+impl From<Writer> for Bytes {
     fn from(val: Writer) -> Self {
-        val.bytes
+        val.bytes.freeze()
     }
 }
 
@@ -149,13 +313,13 @@ pub struct Handshake {
     pub server_port: u16,
     pub next_state: ConnectionState,
 }
-impl TryFrom<Vec<u8>> for Handshake {
+impl TryFrom<Bytes> for Handshake {
     type Error = anyhow::Error;
 
-    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Reader::new(&bytes);
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
         let protocol_version = cursor.read_varint()?;
-        let server_address = cursor.read_string()?;
+        let server_address = cursor.read_string_max(MAX_HOSTNAME_LENGTH)?;
         let server_port = cursor.read_u16()?;
         let next_state = match cursor.read_varint()? {
             1 => ConnectionState::Status,
@@ -170,16 +334,114 @@ impl TryFrom<Vec<u8>> for Handshake {
         })
     }
 }
+impl TryInto<Bytes> for Handshake {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Bytes, Self::Error> {
+        let mut writer = Writer::new();
+        writer.write_varint(self.protocol_version);
+        writer.write_string(&self.server_address);
+        writer.write_u16(self.server_port);
+        writer.write_varint(match self.next_state {
+            ConnectionState::Status => 1,
+            ConnectionState::Login => 2,
+            _ => return Err(anyhow!("Invalid next state")),
+        });
+        Ok(writer.into())
+    }
+}
+
+/// Server List Ping from clients before 1.7, which starts with `0xFE` instead of a packet length
+#[derive(Debug)]
+pub struct LegacyPing {
+    /// Always 1 since 1.4, missing before that
+    pub payload: Option<u8>,
+    /// Only sent since 1.6, along with the address the client connected to
+    pub protocol_version: Option<u8>,
+    pub hostname: Option<String>,
+    pub port: Option<i32>,
+}
+impl TryFrom<Bytes> for LegacyPing {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        if cursor.read_u8()? != 0xFE {
+            return Err(anyhow!("Not a legacy ping"));
+        }
+        let mut ping = LegacyPing {
+            payload: None,
+            protocol_version: None,
+            hostname: None,
+            port: None,
+        };
+        if cursor.is_empty() {
+            return Ok(ping);
+        }
+        ping.payload = Some(cursor.read_u8()?);
+        if cursor.is_empty() || cursor.read_u8()? != 0xFA {
+            return Ok(ping);
+        }
+
+        let channel = read_utf16_string(&mut cursor, MAX_STRING_LENGTH)?;
+        if channel != "MC|PingHost" {
+            return Err(anyhow!("Invalid legacy ping channel {channel:?}"));
+        }
+        let _length = cursor.read_u16()?;
+        ping.protocol_version = Some(cursor.read_u8()?);
+        ping.hostname = Some(read_utf16_string(&mut cursor, MAX_HOSTNAME_LENGTH)?);
+        ping.port = Some(cursor.read_i32()?);
+        Ok(ping)
+    }
+}
+
+/// Response to a [`LegacyPing`], a Disconnect packet of the old protocol with the status as its
+/// reason
+#[derive(Debug)]
+pub struct LegacyKick {
+    pub reason: String,
+}
+impl TryFrom<Bytes> for LegacyKick {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        if cursor.read_u8()? != 0xFF {
+            return Err(anyhow!("Not a legacy kick"));
+        }
+        let reason = read_utf16_string(&mut cursor, MAX_STRING_LENGTH)?;
+        Ok(LegacyKick { reason })
+    }
+}
+
+/// String prefixed by its length in characters, as used before 1.7
+pub fn read_utf16_string(cursor: &mut Reader, max_length: usize) -> Result<String> {
+    let length = cursor.read_u16()?;
+    if length as usize > max_length {
+        return Err(anyhow!(
+            "String of {length} characters exceeds the maximum of {max_length}"
+        ));
+    }
+    let units = (0..length)
+        .map(|_| cursor.read_u16())
+        .collect::<Result<Vec<u16>>>()?;
+    Ok(String::from_utf16(&units)?)
+}
 
 #[derive(Debug)]
 pub struct StatusRequest;
-impl TryFrom<Vec<u8>> for StatusRequest {
+impl TryFrom<Bytes> for StatusRequest {
     type Error = anyhow::Error;
 
-    fn try_from(_bytes: Vec<u8>) -> Result<Self, Self::Error> {
+    fn try_from(_bytes: Bytes) -> Result<Self, Self::Error> {
         Ok(StatusRequest)
     }
 }
+impl From<StatusRequest> for Bytes {
+    fn from(_request: StatusRequest) -> Self {
+        Bytes::new()
+    }
+}
 
 #[derive(Debug)]
 pub struct StatusResponse {
@@ -189,11 +451,31 @@ impl StatusResponse {
     pub fn new(json: Value) -> StatusResponse {
         StatusResponse { json }
     }
+
+    pub fn status(&self) -> Result<ServerStatus> {
+        Ok(serde_json::from_value(self.json.clone())?)
+    }
+}
+impl TryFrom<ServerStatus> for StatusResponse {
+    type Error = anyhow::Error;
+
+    fn try_from(status: ServerStatus) -> Result<Self, Self::Error> {
+        Ok(StatusResponse::new(serde_json::to_value(status)?))
+    }
 }
-impl TryInto<Vec<u8>> for StatusResponse {
+impl TryFrom<Bytes> for StatusResponse {
     type Error = anyhow::Error;
 
-    fn try_into(self) -> Result<Vec<u8>, Self::Error> {
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let json = serde_json::from_str(&cursor.read_string()?)?;
+        Ok(StatusResponse { json })
+    }
+}
+impl TryInto<Bytes> for StatusResponse {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Bytes, Self::Error> {
         let mut writer = Writer::new();
 
         let json = serde_json::to_string(&self.json)?;
@@ -203,19 +485,60 @@ impl TryInto<Vec<u8>> for StatusResponse {
     }
 }
 
+/// Typed contents of the JSON in a [`StatusResponse`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub version: StatusVersion,
+    pub players: Option<StatusPlayers>,
+    /// Chat component, either a plain string or an object
+    #[serde(default)]
+    pub description: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    #[serde(rename = "enforcesSecureChat", skip_serializing_if = "Option::is_none")]
+    pub enforces_secure_chat: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusVersion {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusPlayers {
+    pub max: i32,
+    pub online: i32,
+    #[serde(default)]
+    pub sample: Vec<StatusPlayerSample>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusPlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
 #[derive(Debug)]
 pub struct StatusPing {
     pub payload: i64,
 }
-impl TryFrom<Vec<u8>> for StatusPing {
+impl TryFrom<Bytes> for StatusPing {
     type Error = anyhow::Error;
 
-    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Reader::new(&bytes);
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
         let payload = cursor.read_i64()?;
         Ok(StatusPing { payload })
     }
 }
+impl From<StatusPing> for Bytes {
+    fn from(ping: StatusPing) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i64(ping.payload);
+        writer.into()
+    }
+}
 
 #[derive(Debug)]
 pub struct StatusPong {
@@ -228,10 +551,19 @@ impl From<StatusPing> for StatusPong {
         }
     }
 }
-impl TryInto<Vec<u8>> for StatusPong {
+impl TryFrom<Bytes> for StatusPong {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let payload = cursor.read_i64()?;
+        Ok(StatusPong { payload })
+    }
+}
+impl TryInto<Bytes> for StatusPong {
     type Error = anyhow::Error;
 
-    fn try_into(self) -> Result<Vec<u8>, Self::Error> {
+    fn try_into(self) -> Result<Bytes, Self::Error> {
         let mut writer = Writer::new();
         writer.write_i64(self.payload);
         Ok(writer.into())
@@ -243,12 +575,12 @@ pub struct LoginStart {
     pub username: String,
     pub uuid: Option<Uuid>,
 }
-impl TryFrom<Vec<u8>> for LoginStart {
+impl TryFrom<Bytes> for LoginStart {
     type Error = anyhow::Error;
 
-    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Reader::new(&bytes);
-        let username = cursor.read_string()?;
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let username = cursor.read_string_max(MAX_USERNAME_LENGTH)?;
         let uuid = if cursor.read_bool()? {
             let uuid = cursor.read_uuid()?;
             Some(uuid)
@@ -258,6 +590,17 @@ impl TryFrom<Vec<u8>> for LoginStart {
         Ok(LoginStart { username, uuid })
     }
 }
+impl From<LoginStart> for Bytes {
+    fn from(login_start: LoginStart) -> Self {
+        let mut writer = Writer::new();
+        writer.write_string(&login_start.username);
+        writer.write_bool(login_start.uuid.is_some());
+        if let Some(uuid) = login_start.uuid {
+            writer.write_uuid(&uuid);
+        }
+        writer.into()
+    }
+}
 
 #[derive(Debug)]
 pub struct EncryptionRequest {
@@ -274,10 +617,30 @@ impl EncryptionRequest {
         }
     }
 }
-impl TryInto<Vec<u8>> for EncryptionRequest {
+impl TryFrom<Bytes> for EncryptionRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let server_id = cursor.read_string()?;
+        let public_key = cursor.read_byte_array()?;
+        let verify_token = cursor.read_byte_array()?;
+        let verify_token = verify_token
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Invalid verify token length: {}", verify_token.len()))?;
+
+        Ok(EncryptionRequest {
+            server_id,
+            public_key,
+            verify_token,
+        })
+    }
+}
+impl TryInto<Bytes> for EncryptionRequest {
     type Error = anyhow::Error;
 
-    fn try_into(self) -> Result<Vec<u8>, Self::Error> {
+    fn try_into(self) -> Result<Bytes, Self::Error> {
         let mut writer = Writer::new();
         writer.write_string(&self.server_id);
         writer.write_varint(self.public_key.len() as i32);
@@ -293,13 +656,13 @@ pub struct EncryptionResponse {
     pub shared_secret: Vec<u8>,
     pub verify_token: Vec<u8>,
 }
-impl TryFrom<Vec<u8>> for EncryptionResponse {
+impl TryFrom<Bytes> for EncryptionResponse {
     type Error = anyhow::Error;
 
-    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut cursor = Reader::new(&bytes);
-        let shared_secret = cursor.read_byte_array()?;
-        let verify_token = cursor.read_byte_array()?;
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let shared_secret = cursor.read_byte_array_max(MAX_ENCRYPTED_LENGTH)?;
+        let verify_token = cursor.read_byte_array_max(MAX_ENCRYPTED_LENGTH)?;
 
         Ok(EncryptionResponse {
             shared_secret,
@@ -307,17 +670,42 @@ impl TryFrom<Vec<u8>> for EncryptionResponse {
         })
     }
 }
+impl From<EncryptionResponse> for Bytes {
+    fn from(encryption_response: EncryptionResponse) -> Self {
+        let mut writer = Writer::new();
+        writer.write_varint(encryption_response.shared_secret.len() as i32);
+        writer.write_raw(&encryption_response.shared_secret);
+        writer.write_varint(encryption_response.verify_token.len() as i32);
+        writer.write_raw(&encryption_response.verify_token);
+        writer.into()
+    }
+}
 impl EncryptionResponse {
-    pub fn decrypt_shared_secret(&self) -> Result<[u8; 16], anyhow::Error> {
-        let mut shared_secret = [0; 128];
-        RSA_KEY_PAIR.private_decrypt(&self.shared_secret, &mut shared_secret, Padding::PKCS1)?;
-        Ok(shared_secret[..16].try_into()?)
+    /// Encrypt the client's shared secret and the server's verify token with the server's public key
+    pub fn encrypt(
+        request: &EncryptionRequest,
+        shared_secret: &[u8; 16],
+    ) -> Result<EncryptionResponse, anyhow::Error> {
+        Ok(EncryptionResponse {
+            shared_secret: crypto::public_encrypt(&request.public_key, shared_secret)?,
+            verify_token: crypto::public_encrypt(&request.public_key, &request.verify_token)?,
+        })
+    }
+
+    pub fn decrypt_shared_secret(&self, key_pair: &KeyPair) -> Result<[u8; 16], anyhow::Error> {
+        let shared_secret = key_pair.decrypt(&self.shared_secret)?;
+        shared_secret
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Invalid shared secret length: {}", shared_secret.len()))
     }
 
-    pub fn decrypt_verify_token(&self) -> Result<[u8; 4], anyhow::Error> {
-        let mut verify_token = [0; 128];
-        RSA_KEY_PAIR.private_decrypt(&self.verify_token, &mut verify_token, Padding::PKCS1)?;
-        Ok(verify_token[..4].try_into()?)
+    pub fn decrypt_verify_token(&self, key_pair: &KeyPair) -> Result<[u8; 4], anyhow::Error> {
+        let verify_token = key_pair.decrypt(&self.verify_token)?;
+        verify_token
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("Invalid verify token length: {}", verify_token.len()))
     }
 }
 
@@ -336,7 +724,36 @@ impl From<ServerAuthResponse> for LoginSuccess {
         }
     }
 }
-impl From<LoginSuccess> for Vec<u8> {
+impl TryFrom<Bytes> for LoginSuccess {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let uuid = cursor.read_uuid()?;
+        let username = cursor.read_string_max(MAX_USERNAME_LENGTH)?;
+        let mut properties = Vec::new();
+        for _ in 0..cursor.read_array_length(MAX_PROFILE_PROPERTIES)? {
+            let name = cursor.read_string()?;
+            let value = cursor.read_string()?;
+            let signature = if cursor.read_bool()? {
+                cursor.read_string()?
+            } else {
+                String::new()
+            };
+            properties.push(ProfileProperty {
+                name,
+                value,
+                signature,
+            });
+        }
+        Ok(LoginSuccess {
+            uuid,
+            username,
+            properties,
+        })
+    }
+}
+impl From<LoginSuccess> for Bytes {
     fn from(login_success: LoginSuccess) -> Self {
         let mut writer = Writer::new();
         writer.write_uuid(&login_success.uuid);
@@ -361,10 +778,19 @@ impl LoginDisconnect {
         LoginDisconnect { reason }
     }
 }
-impl TryInto<Vec<u8>> for LoginDisconnect {
+impl TryFrom<Bytes> for LoginDisconnect {
     type Error = anyhow::Error;
 
-    fn try_into(self) -> Result<Vec<u8>, Self::Error> {
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let reason = serde_json::from_str(&cursor.read_string_max(MAX_CHAT_LENGTH)?)?;
+        Ok(LoginDisconnect { reason })
+    }
+}
+impl TryInto<Bytes> for LoginDisconnect {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Bytes, Self::Error> {
         let mut writer = Writer::new();
 
         let json = serde_json::to_string(&self.reason)?;
@@ -375,6 +801,127 @@ impl TryInto<Vec<u8>> for LoginDisconnect {
 }
 
 #[derive(Debug)]
+pub struct SetCompression {
+    pub threshold: i32,
+}
+impl TryFrom<Bytes> for SetCompression {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let threshold = cursor.read_varint()?;
+        Ok(SetCompression { threshold })
+    }
+}
+impl From<SetCompression> for Bytes {
+    fn from(set_compression: SetCompression) -> Self {
+        let mut writer = Writer::new();
+        writer.write_varint(set_compression.threshold);
+        writer.into()
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginPluginRequest {
+    pub message_id: i32,
+    pub channel: String,
+    pub data: Vec<u8>,
+}
+impl TryFrom<Bytes> for LoginPluginRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let message_id = cursor.read_varint()?;
+        let channel = cursor.read_string()?;
+        let data = cursor.get_leftover_bytes().to_vec();
+        Ok(LoginPluginRequest {
+            message_id,
+            channel,
+            data,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginPluginResponse {
+    pub message_id: i32,
+    pub data: Option<Vec<u8>>,
+}
+impl LoginPluginResponse {
+    /// Response for a channel the client does not understand
+    pub fn not_understood(request: &LoginPluginRequest) -> LoginPluginResponse {
+        LoginPluginResponse {
+            message_id: request.message_id,
+            data: None,
+        }
+    }
+}
+impl From<LoginPluginResponse> for Bytes {
+    fn from(response: LoginPluginResponse) -> Self {
+        let mut writer = Writer::new();
+        writer.write_varint(response.message_id);
+        writer.write_bool(response.data.is_some());
+        if let Some(data) = response.data {
+            writer.write_raw(&data);
+        }
+        writer.into()
+    }
+}
+
+/// Keep Alive in the Play state. The server sends one with a new ID every few seconds, and the
+/// client answers with the same ID.
+#[derive(Debug)]
+pub struct KeepAlive {
+    pub id: i64,
+}
+impl TryFrom<Bytes> for KeepAlive {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let id = cursor.read_i64()?;
+        Ok(KeepAlive { id })
+    }
+}
+impl From<KeepAlive> for Bytes {
+    fn from(keep_alive: KeepAlive) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i64(keep_alive.id);
+        writer.into()
+    }
+}
+
+/// Disconnect in the Play state
+#[derive(Debug)]
+pub struct Disconnect {
+    pub reason: Value,
+}
+impl Disconnect {
+    pub fn new(reason: Value) -> Disconnect {
+        Disconnect { reason }
+    }
+}
+impl TryFrom<Bytes> for Disconnect {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let reason = serde_json::from_str(&cursor.read_string_max(MAX_CHAT_LENGTH)?)?;
+        Ok(Disconnect { reason })
+    }
+}
+impl TryInto<Bytes> for Disconnect {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Bytes, Self::Error> {
+        let mut writer = Writer::new();
+        writer.write_string(&serde_json::to_string(&self.reason)?);
+        Ok(writer.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionState {
     Handshaking,
     Status,
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+
+use super::Reader;
+use crate::{
+    nbt::{self, Compound},
+    registries::ITEMS,
+};
+
+pub mod clientbound;
+pub mod registry_codec;
+pub mod serverbound;
+
+/// A block position, packed into a single long with 26 bits for X and Z, and 12 for Y
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+impl Position {
+    pub fn read(cursor: &mut Reader) -> Result<Position> {
+        let value = cursor.read_i64()?;
+        Ok(Position {
+            x: (value >> 38) as i32,
+            y: (value << 52 >> 52) as i32,
+            z: (value << 26 >> 38) as i32,
+        })
+    }
+
+    pub fn to_i64(self) -> i64 {
+        ((self.x as i64 & 0x3FF_FFFF) << 38)
+            | ((self.z as i64 & 0x3FF_FFFF) << 12)
+            | (self.y as i64 & 0xFFF)
+    }
+}
+
+/// A stack of items in an inventory slot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    pub item_id: i32,
+    pub count: i8,
+    pub nbt: Option<Compound>,
+}
+impl Slot {
+    /// Read a slot, which is `None` when it is empty
+    pub fn read(cursor: &mut Reader) -> Result<Option<Slot>> {
+        if !cursor.read_bool()? {
+            return Ok(None);
+        }
+        let item_id = cursor.read_varint()?;
+        let count = cursor.read_i8()?;
+        let nbt = nbt::read_compound(cursor)?;
+        Ok(Some(Slot {
+            item_id,
+            count,
+            nbt,
+        }))
+    }
+
+    /// Identifier of the item, like `minecraft:stone`, if the item registry knows it
+    pub fn item_name(&self) -> Option<&'static str> {
+        ITEMS.get(u32::try_from(self.item_id).ok()?)
+    }
+}
+
+/// Which hand a player uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hand {
+    Main,
+    Off,
+}
+impl Hand {
+    pub fn read(cursor: &mut Reader) -> Result<Hand> {
+        match cursor.read_varint()? {
+            0 => Ok(Hand::Main),
+            1 => Ok(Hand::Off),
+            hand => Err(anyhow!("Invalid hand {hand}")),
+        }
+    }
+}
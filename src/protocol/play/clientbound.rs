@@ -0,0 +1,819 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use mojang_api::ProfileProperty;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::Position;
+use crate::{
+    limits::{
+        MAX_CHAT_LENGTH, MAX_PROFILE_PROPERTIES, MAX_SESSION_KEY_LENGTH,
+        MAX_SESSION_KEY_SIGNATURE_LENGTH, MAX_USERNAME_LENGTH,
+    },
+    nbt::{self, Compound},
+    protocol::{Disconnect, KeepAlive, Reader, Writer},
+    stream::Packet,
+};
+
+/// The Play packets the server sends, which know their own packet ID
+#[derive(Debug)]
+pub enum ClientboundPlayPacket {
+    Disconnect(Disconnect),
+    UnloadChunk(UnloadChunk),
+    GameEvent(GameEvent),
+    KeepAlive(KeepAlive),
+    ChunkDataAndUpdateLight(ChunkDataAndUpdateLight),
+    LoginPlay(LoginPlay),
+    PlayerInfoUpdate(PlayerInfoUpdate),
+    SynchronizePlayerPosition(SynchronizePlayerPosition),
+    SetHeldItem(SetHeldItem),
+    SetCenterChunk(SetCenterChunk),
+    SetDefaultSpawnPosition(SetDefaultSpawnPosition),
+    UpdateTime(UpdateTime),
+    SystemChatMessage(SystemChatMessage),
+}
+impl ClientboundPlayPacket {
+    pub fn id(&self) -> i32 {
+        match self {
+            ClientboundPlayPacket::Disconnect(_) => 0x1A,
+            ClientboundPlayPacket::UnloadChunk(_) => 0x1E,
+            ClientboundPlayPacket::GameEvent(_) => 0x1F,
+            ClientboundPlayPacket::KeepAlive(_) => 0x23,
+            ClientboundPlayPacket::ChunkDataAndUpdateLight(_) => 0x24,
+            ClientboundPlayPacket::LoginPlay(_) => 0x28,
+            ClientboundPlayPacket::PlayerInfoUpdate(_) => 0x3A,
+            ClientboundPlayPacket::SynchronizePlayerPosition(_) => 0x3C,
+            ClientboundPlayPacket::SetHeldItem(_) => 0x4D,
+            ClientboundPlayPacket::SetCenterChunk(_) => 0x4E,
+            ClientboundPlayPacket::SetDefaultSpawnPosition(_) => 0x50,
+            ClientboundPlayPacket::UpdateTime(_) => 0x5E,
+            ClientboundPlayPacket::SystemChatMessage(_) => 0x64,
+        }
+    }
+}
+impl TryFrom<ClientboundPlayPacket> for Packet {
+    type Error = anyhow::Error;
+
+    fn try_from(packet: ClientboundPlayPacket) -> Result<Self, Self::Error> {
+        let id = packet.id();
+        let data = match packet {
+            ClientboundPlayPacket::Disconnect(packet) => packet.try_into()?,
+            ClientboundPlayPacket::UnloadChunk(packet) => packet.into(),
+            ClientboundPlayPacket::GameEvent(packet) => packet.into(),
+            ClientboundPlayPacket::KeepAlive(packet) => packet.into(),
+            ClientboundPlayPacket::ChunkDataAndUpdateLight(packet) => packet.into(),
+            ClientboundPlayPacket::LoginPlay(packet) => packet.into(),
+            ClientboundPlayPacket::PlayerInfoUpdate(packet) => packet.try_into()?,
+            ClientboundPlayPacket::SynchronizePlayerPosition(packet) => packet.into(),
+            ClientboundPlayPacket::SetHeldItem(packet) => packet.into(),
+            ClientboundPlayPacket::SetCenterChunk(packet) => packet.into(),
+            ClientboundPlayPacket::SetDefaultSpawnPosition(packet) => packet.into(),
+            ClientboundPlayPacket::UpdateTime(packet) => packet.into(),
+            ClientboundPlayPacket::SystemChatMessage(packet) => packet.try_into()?,
+        };
+        Ok(Packet { id, data })
+    }
+}
+impl TryFrom<Packet> for ClientboundPlayPacket {
+    type Error = anyhow::Error;
+
+    /// Decode a packet from the server, failing for IDs without a variant here
+    fn try_from(packet: Packet) -> Result<Self, Self::Error> {
+        use ClientboundPlayPacket as P;
+        let data = packet.data;
+        Ok(match packet.id {
+            0x1A => P::Disconnect(data.try_into()?),
+            0x1E => P::UnloadChunk(data.try_into()?),
+            0x1F => P::GameEvent(data.try_into()?),
+            0x23 => P::KeepAlive(data.try_into()?),
+            0x24 => P::ChunkDataAndUpdateLight(data.try_into()?),
+            0x28 => P::LoginPlay(data.try_into()?),
+            0x3A => P::PlayerInfoUpdate(data.try_into()?),
+            0x3C => P::SynchronizePlayerPosition(data.try_into()?),
+            0x4D => P::SetHeldItem(data.try_into()?),
+            0x4E => P::SetCenterChunk(data.try_into()?),
+            0x50 => P::SetDefaultSpawnPosition(data.try_into()?),
+            0x5E => P::UpdateTime(data.try_into()?),
+            0x64 => P::SystemChatMessage(data.try_into()?),
+            id => return Err(anyhow!("Unknown clientbound Play packet {id:#04x}")),
+        })
+    }
+}
+
+/// Game modes, as sent in [`LoginPlay`] and [`PlayerInfoUpdate`]
+pub mod game_mode {
+    pub const SURVIVAL: u8 = 0;
+    pub const CREATIVE: u8 = 1;
+    pub const ADVENTURE: u8 = 2;
+    pub const SPECTATOR: u8 = 3;
+}
+
+/// Puts the player in a world, the first packet sent in the Play state
+#[derive(Debug)]
+pub struct LoginPlay {
+    pub entity_id: i32,
+    pub is_hardcore: bool,
+    pub game_mode: u8,
+    /// -1 when there is none
+    pub previous_game_mode: i8,
+    pub dimension_names: Vec<String>,
+    /// Dimension types, biomes, chat types and damage types, see
+    /// [`registry_codec`](super::registry_codec::registry_codec)
+    pub registry_codec: Compound,
+    pub dimension_type: String,
+    pub dimension_name: String,
+    /// First 8 bytes of the SHA-256 hash of the world's seed
+    pub hashed_seed: i64,
+    /// Ignored by the client
+    pub max_players: i32,
+    pub view_distance: i32,
+    pub simulation_distance: i32,
+    pub reduced_debug_info: bool,
+    pub enable_respawn_screen: bool,
+    pub is_debug: bool,
+    pub is_flat: bool,
+    /// Dimension and position where the player last died
+    pub death_location: Option<(String, Position)>,
+}
+impl TryFrom<Bytes> for LoginPlay {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let entity_id = cursor.read_i32()?;
+        let is_hardcore = cursor.read_bool()?;
+        let game_mode = cursor.read_u8()?;
+        let previous_game_mode = cursor.read_i8()?;
+        let mut dimension_names = Vec::new();
+        for _ in 0..cursor.read_length()? {
+            dimension_names.push(cursor.read_string()?);
+        }
+        let registry_codec = nbt::read_compound(&mut cursor)?.unwrap_or_default();
+        Ok(LoginPlay {
+            entity_id,
+            is_hardcore,
+            game_mode,
+            previous_game_mode,
+            dimension_names,
+            registry_codec,
+            dimension_type: cursor.read_string()?,
+            dimension_name: cursor.read_string()?,
+            hashed_seed: cursor.read_i64()?,
+            max_players: cursor.read_varint()?,
+            view_distance: cursor.read_varint()?,
+            simulation_distance: cursor.read_varint()?,
+            reduced_debug_info: cursor.read_bool()?,
+            enable_respawn_screen: cursor.read_bool()?,
+            is_debug: cursor.read_bool()?,
+            is_flat: cursor.read_bool()?,
+            death_location: match cursor.read_bool()? {
+                true => Some((cursor.read_string()?, Position::read(&mut cursor)?)),
+                false => None,
+            },
+        })
+    }
+}
+impl From<LoginPlay> for Bytes {
+    fn from(login: LoginPlay) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i32(login.entity_id);
+        writer.write_bool(login.is_hardcore);
+        writer.write_u8(login.game_mode);
+        writer.write_i8(login.previous_game_mode);
+        writer.write_varint(login.dimension_names.len() as i32);
+        for name in &login.dimension_names {
+            writer.write_string(name);
+        }
+        nbt::write_compound(&mut writer, Some(&login.registry_codec));
+        writer.write_string(&login.dimension_type);
+        writer.write_string(&login.dimension_name);
+        writer.write_i64(login.hashed_seed);
+        writer.write_varint(login.max_players);
+        writer.write_varint(login.view_distance);
+        writer.write_varint(login.simulation_distance);
+        writer.write_bool(login.reduced_debug_info);
+        writer.write_bool(login.enable_respawn_screen);
+        writer.write_bool(login.is_debug);
+        writer.write_bool(login.is_flat);
+        writer.write_bool(login.death_location.is_some());
+        if let Some((dimension, position)) = &login.death_location {
+            writer.write_string(dimension);
+            writer.write_i64(position.to_i64());
+        }
+        writer.into()
+    }
+}
+
+/// Teleports the player. The client answers with a Confirm Teleportation of the same ID.
+#[derive(Debug)]
+pub struct SynchronizePlayerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Bit mask of which of X, Y, Z, yaw and pitch are relative to the current position
+    pub flags: u8,
+    pub teleport_id: i32,
+}
+impl TryFrom<Bytes> for SynchronizePlayerPosition {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SynchronizePlayerPosition {
+            x: cursor.read_f64()?,
+            y: cursor.read_f64()?,
+            z: cursor.read_f64()?,
+            yaw: cursor.read_f32()?,
+            pitch: cursor.read_f32()?,
+            flags: cursor.read_u8()?,
+            teleport_id: cursor.read_varint()?,
+        })
+    }
+}
+impl From<SynchronizePlayerPosition> for Bytes {
+    fn from(position: SynchronizePlayerPosition) -> Self {
+        let mut writer = Writer::new();
+        writer.write_f64(position.x);
+        writer.write_f64(position.y);
+        writer.write_f64(position.z);
+        writer.write_f32(position.yaw);
+        writer.write_f32(position.pitch);
+        writer.write_u8(position.flags);
+        writer.write_varint(position.teleport_id);
+        writer.into()
+    }
+}
+
+/// Where compasses point, and where the player spawns until a position is synchronized
+#[derive(Debug)]
+pub struct SetDefaultSpawnPosition {
+    pub location: Position,
+    pub angle: f32,
+}
+impl TryFrom<Bytes> for SetDefaultSpawnPosition {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetDefaultSpawnPosition {
+            location: Position::read(&mut cursor)?,
+            angle: cursor.read_f32()?,
+        })
+    }
+}
+impl From<SetDefaultSpawnPosition> for Bytes {
+    fn from(spawn: SetDefaultSpawnPosition) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i64(spawn.location.to_i64());
+        writer.write_f32(spawn.angle);
+        writer.into()
+    }
+}
+
+/// Makes the client forget a chunk, once it is out of view
+#[derive(Debug)]
+pub struct UnloadChunk {
+    pub x: i32,
+    pub z: i32,
+}
+impl TryFrom<Bytes> for UnloadChunk {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(UnloadChunk {
+            x: cursor.read_i32()?,
+            z: cursor.read_i32()?,
+        })
+    }
+}
+impl From<UnloadChunk> for Bytes {
+    fn from(unload: UnloadChunk) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i32(unload.x);
+        writer.write_i32(unload.z);
+        writer.into()
+    }
+}
+
+/// The chunk the player is in. The client ignores chunks outside its view distance from it.
+#[derive(Debug)]
+pub struct SetCenterChunk {
+    pub x: i32,
+    pub z: i32,
+}
+impl TryFrom<Bytes> for SetCenterChunk {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetCenterChunk {
+            x: cursor.read_varint()?,
+            z: cursor.read_varint()?,
+        })
+    }
+}
+impl From<SetCenterChunk> for Bytes {
+    fn from(center: SetCenterChunk) -> Self {
+        let mut writer = Writer::new();
+        writer.write_varint(center.x);
+        writer.write_varint(center.z);
+        writer.into()
+    }
+}
+
+/// A block entity in [`ChunkDataAndUpdateLight`], with its position inside the chunk
+#[derive(Debug, Clone)]
+pub struct ChunkBlockEntity {
+    /// From 0 to 15
+    pub x: u8,
+    pub y: i16,
+    /// From 0 to 15
+    pub z: u8,
+    pub kind: i32,
+    pub data: Option<Compound>,
+}
+
+/// Sky and block light of the sections of a chunk, including the sections just below and above
+/// the world
+#[derive(Debug, Clone, Default)]
+pub struct LightData {
+    pub trust_edges: bool,
+    pub sky_light_mask: Vec<i64>,
+    pub block_light_mask: Vec<i64>,
+    pub empty_sky_light_mask: Vec<i64>,
+    pub empty_block_light_mask: Vec<i64>,
+    /// 2048 bytes of half-byte light levels for every bit set in `sky_light_mask`
+    pub sky_light: Vec<Vec<u8>>,
+    pub block_light: Vec<Vec<u8>>,
+}
+impl LightData {
+    fn read(cursor: &mut Reader) -> Result<LightData> {
+        let trust_edges = cursor.read_bool()?;
+        let mut masks = Vec::new();
+        for _ in 0..4 {
+            let mut mask = Vec::new();
+            for _ in 0..cursor.read_length()? {
+                mask.push(cursor.read_i64()?);
+            }
+            masks.push(mask);
+        }
+        let mut arrays = Vec::new();
+        for _ in 0..2 {
+            let mut light = Vec::new();
+            for _ in 0..cursor.read_length()? {
+                light.push(cursor.read_byte_array()?);
+            }
+            arrays.push(light);
+        }
+        let [sky_light_mask, block_light_mask, empty_sky_light_mask, empty_block_light_mask] =
+            <[_; 4]>::try_from(masks).unwrap();
+        let [sky_light, block_light] = <[_; 2]>::try_from(arrays).unwrap();
+        Ok(LightData {
+            trust_edges,
+            sky_light_mask,
+            block_light_mask,
+            empty_sky_light_mask,
+            empty_block_light_mask,
+            sky_light,
+            block_light,
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bool(self.trust_edges);
+        for mask in [
+            &self.sky_light_mask,
+            &self.block_light_mask,
+            &self.empty_sky_light_mask,
+            &self.empty_block_light_mask,
+        ] {
+            writer.write_varint(mask.len() as i32);
+            for &long in mask {
+                writer.write_i64(long);
+            }
+        }
+        for arrays in [&self.sky_light, &self.block_light] {
+            writer.write_varint(arrays.len() as i32);
+            for array in arrays {
+                writer.write_varint(array.len() as i32);
+                writer.write_raw(array);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ChunkDataAndUpdateLight {
+    pub x: i32,
+    pub z: i32,
+    pub heightmaps: Compound,
+    /// The encoded chunk sections
+    pub data: Bytes,
+    pub block_entities: Vec<ChunkBlockEntity>,
+    pub light: LightData,
+}
+impl TryFrom<Bytes> for ChunkDataAndUpdateLight {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let x = cursor.read_i32()?;
+        let z = cursor.read_i32()?;
+        let heightmaps = nbt::read_compound(&mut cursor)?.unwrap_or_default();
+        let length = cursor.read_length()?;
+        let data = cursor.read_bytes(length)?;
+        let mut block_entities = Vec::new();
+        for _ in 0..cursor.read_length()? {
+            let xz = cursor.read_u8()?;
+            block_entities.push(ChunkBlockEntity {
+                x: xz >> 4,
+                y: cursor.read_i16()?,
+                z: xz & 0xF,
+                kind: cursor.read_varint()?,
+                data: nbt::read_compound(&mut cursor)?,
+            });
+        }
+        Ok(ChunkDataAndUpdateLight {
+            x,
+            z,
+            heightmaps,
+            data,
+            block_entities,
+            light: LightData::read(&mut cursor)?,
+        })
+    }
+}
+impl From<ChunkDataAndUpdateLight> for Bytes {
+    fn from(chunk: ChunkDataAndUpdateLight) -> Self {
+        let mut writer = Writer::with_capacity(chunk.data.len() + 1024);
+        writer.write_i32(chunk.x);
+        writer.write_i32(chunk.z);
+        nbt::write_compound(&mut writer, Some(&chunk.heightmaps));
+        writer.write_varint(chunk.data.len() as i32);
+        writer.write_raw(&chunk.data);
+        writer.write_varint(chunk.block_entities.len() as i32);
+        for block_entity in &chunk.block_entities {
+            writer.write_u8((block_entity.x & 0xF) << 4 | (block_entity.z & 0xF));
+            writer.write_i16(block_entity.y);
+            writer.write_varint(block_entity.kind);
+            nbt::write_compound(&mut writer, block_entity.data.as_ref());
+        }
+        chunk.light.write(&mut writer);
+        writer.into()
+    }
+}
+
+/// Changes to the game state, like the weather or game mode
+#[derive(Debug)]
+pub struct GameEvent {
+    pub event: u8,
+    pub value: f32,
+}
+impl GameEvent {
+    pub const BEGIN_RAINING: u8 = 1;
+    pub const END_RAINING: u8 = 2;
+    pub const CHANGE_GAME_MODE: u8 = 3;
+    pub const WIN_GAME: u8 = 4;
+    pub const RAIN_LEVEL_CHANGE: u8 = 7;
+    pub const THUNDER_LEVEL_CHANGE: u8 = 8;
+    pub const ENABLE_RESPAWN_SCREEN: u8 = 11;
+}
+impl TryFrom<Bytes> for GameEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(GameEvent {
+            event: cursor.read_u8()?,
+            value: cursor.read_f32()?,
+        })
+    }
+}
+impl From<GameEvent> for Bytes {
+    fn from(event: GameEvent) -> Self {
+        let mut writer = Writer::new();
+        writer.write_u8(event.event);
+        writer.write_f32(event.value);
+        writer.into()
+    }
+}
+
+/// A player in the tab list
+#[derive(Debug, Clone)]
+pub struct PlayerInfo {
+    pub uuid: Uuid,
+    pub name: String,
+    pub properties: Vec<ProfileProperty>,
+    pub game_mode: u8,
+    pub listed: bool,
+    /// Round-trip time in milliseconds
+    pub latency: i32,
+    pub display_name: Option<Value>,
+}
+
+/// Adds players to the tab list, or updates them. Only the fields selected by `actions` are
+/// sent for each player.
+#[derive(Debug)]
+pub struct PlayerInfoUpdate {
+    pub actions: u8,
+    pub players: Vec<PlayerInfo>,
+}
+impl PlayerInfoUpdate {
+    pub const ADD_PLAYER: u8 = 0x01;
+    pub const INITIALIZE_CHAT: u8 = 0x02;
+    pub const UPDATE_GAME_MODE: u8 = 0x04;
+    pub const UPDATE_LISTED: u8 = 0x08;
+    pub const UPDATE_LATENCY: u8 = 0x10;
+    pub const UPDATE_DISPLAY_NAME: u8 = 0x20;
+}
+impl TryFrom<Bytes> for PlayerInfoUpdate {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let actions = cursor.read_u8()?;
+        let mut players = Vec::new();
+        for _ in 0..cursor.read_length()? {
+            let mut player = PlayerInfo {
+                uuid: cursor.read_uuid()?,
+                name: String::new(),
+                properties: Vec::new(),
+                game_mode: 0,
+                listed: false,
+                latency: 0,
+                display_name: None,
+            };
+            if actions & Self::ADD_PLAYER != 0 {
+                player.name = cursor.read_string_max(MAX_USERNAME_LENGTH)?;
+                for _ in 0..cursor.read_array_length(MAX_PROFILE_PROPERTIES)? {
+                    let name = cursor.read_string()?;
+                    let value = cursor.read_string()?;
+                    let signature = match cursor.read_bool()? {
+                        true => cursor.read_string()?,
+                        false => String::new(),
+                    };
+                    player.properties.push(ProfileProperty {
+                        name,
+                        value,
+                        signature,
+                    });
+                }
+            }
+            if actions & Self::INITIALIZE_CHAT != 0 && cursor.read_bool()? {
+                // The chat session is skipped, like it is when writing
+                cursor.read_uuid()?;
+                cursor.read_i64()?;
+                cursor.read_byte_array_max(MAX_SESSION_KEY_LENGTH)?;
+                cursor.read_byte_array_max(MAX_SESSION_KEY_SIGNATURE_LENGTH)?;
+            }
+            if actions & Self::UPDATE_GAME_MODE != 0 {
+                player.game_mode = cursor.read_varint()? as u8;
+            }
+            if actions & Self::UPDATE_LISTED != 0 {
+                player.listed = cursor.read_bool()?;
+            }
+            if actions & Self::UPDATE_LATENCY != 0 {
+                player.latency = cursor.read_varint()?;
+            }
+            if actions & Self::UPDATE_DISPLAY_NAME != 0 && cursor.read_bool()? {
+                let display_name = cursor.read_string_max(MAX_CHAT_LENGTH)?;
+                player.display_name = Some(serde_json::from_str(&display_name)?);
+            }
+            players.push(player);
+        }
+        Ok(PlayerInfoUpdate { actions, players })
+    }
+}
+impl TryInto<Bytes> for PlayerInfoUpdate {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Bytes, Self::Error> {
+        let mut writer = Writer::new();
+        writer.write_u8(self.actions);
+        writer.write_varint(self.players.len() as i32);
+        for player in &self.players {
+            writer.write_uuid(&player.uuid);
+            if self.actions & Self::ADD_PLAYER != 0 {
+                writer.write_string(&player.name);
+                writer.write_varint(player.properties.len() as i32);
+                for property in &player.properties {
+                    writer.write_string(&property.name);
+                    writer.write_string(&property.value);
+                    writer.write_bool(!property.signature.is_empty());
+                    if !property.signature.is_empty() {
+                        writer.write_string(&property.signature);
+                    }
+                }
+            }
+            if self.actions & Self::INITIALIZE_CHAT != 0 {
+                // Chat sessions are not forwarded, so messages show up as unsigned
+                writer.write_bool(false);
+            }
+            if self.actions & Self::UPDATE_GAME_MODE != 0 {
+                writer.write_varint(player.game_mode as i32);
+            }
+            if self.actions & Self::UPDATE_LISTED != 0 {
+                writer.write_bool(player.listed);
+            }
+            if self.actions & Self::UPDATE_LATENCY != 0 {
+                writer.write_varint(player.latency);
+            }
+            if self.actions & Self::UPDATE_DISPLAY_NAME != 0 {
+                writer.write_bool(player.display_name.is_some());
+                if let Some(display_name) = &player.display_name {
+                    writer.write_string(&serde_json::to_string(display_name)?);
+                }
+            }
+        }
+        Ok(writer.into())
+    }
+}
+
+/// Selects a hotbar slot
+#[derive(Debug)]
+pub struct SetHeldItem {
+    /// From 0 to 8
+    pub slot: i8,
+}
+impl TryFrom<Bytes> for SetHeldItem {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetHeldItem {
+            slot: cursor.read_i8()?,
+        })
+    }
+}
+impl From<SetHeldItem> for Bytes {
+    fn from(held_item: SetHeldItem) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i8(held_item.slot);
+        writer.into()
+    }
+}
+
+#[derive(Debug)]
+pub struct UpdateTime {
+    /// Ticks since the world was created
+    pub world_age: i64,
+    /// Ticks since the start of the day, where 0 is sunrise and 24000 a full day. Negative stops
+    /// the client from advancing it.
+    pub time_of_day: i64,
+}
+impl TryFrom<Bytes> for UpdateTime {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(UpdateTime {
+            world_age: cursor.read_i64()?,
+            time_of_day: cursor.read_i64()?,
+        })
+    }
+}
+impl From<UpdateTime> for Bytes {
+    fn from(time: UpdateTime) -> Self {
+        let mut writer = Writer::new();
+        writer.write_i64(time.world_age);
+        writer.write_i64(time.time_of_day);
+        writer.into()
+    }
+}
+
+/// A message in chat from the server itself, not signed by a player
+#[derive(Debug)]
+pub struct SystemChatMessage {
+    pub content: Value,
+    /// Show the message above the hotbar instead of in chat
+    pub overlay: bool,
+}
+impl SystemChatMessage {
+    pub fn new(content: Value) -> SystemChatMessage {
+        SystemChatMessage {
+            content,
+            overlay: false,
+        }
+    }
+}
+impl TryFrom<Bytes> for SystemChatMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SystemChatMessage {
+            content: serde_json::from_str(&cursor.read_string_max(MAX_CHAT_LENGTH)?)?,
+            overlay: cursor.read_bool()?,
+        })
+    }
+}
+impl TryInto<Bytes> for SystemChatMessage {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Bytes, Self::Error> {
+        let mut writer = Writer::new();
+        writer.write_string(&serde_json::to_string(&self.content)?);
+        writer.write_bool(self.overlay);
+        Ok(writer.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::protocol::play::registry_codec::registry_codec;
+
+    /// Encode `packet`, decode it again and check that it encodes to the same bytes
+    fn round_trip(packet: ClientboundPlayPacket) {
+        let encoded = Packet::try_from(packet).unwrap();
+        let decoded = ClientboundPlayPacket::try_from(encoded.clone()).unwrap();
+        assert_eq!(decoded.id(), encoded.id);
+        assert_eq!(Packet::try_from(decoded).unwrap().data, encoded.data);
+    }
+
+    #[test]
+    fn packets_decode_to_what_was_encoded() {
+        let position = Position {
+            x: -5,
+            y: 70,
+            z: 12,
+        };
+        round_trip(ClientboundPlayPacket::LoginPlay(LoginPlay {
+            entity_id: 7,
+            is_hardcore: false,
+            game_mode: game_mode::CREATIVE,
+            previous_game_mode: -1,
+            dimension_names: vec!["minecraft:overworld".to_string()],
+            registry_codec: registry_codec(),
+            dimension_type: "minecraft:overworld".to_string(),
+            dimension_name: "minecraft:overworld".to_string(),
+            hashed_seed: 42,
+            max_players: 20,
+            view_distance: 10,
+            simulation_distance: 8,
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+            is_debug: false,
+            is_flat: true,
+            death_location: Some(("minecraft:overworld".to_string(), position)),
+        }));
+        round_trip(ClientboundPlayPacket::ChunkDataAndUpdateLight(
+            ChunkDataAndUpdateLight {
+                x: 3,
+                z: -2,
+                heightmaps: Compound::new(),
+                data: Bytes::from_static(&[1, 2, 3]),
+                block_entities: vec![ChunkBlockEntity {
+                    x: 4,
+                    y: -60,
+                    z: 15,
+                    kind: 1,
+                    data: None,
+                }],
+                light: LightData {
+                    trust_edges: true,
+                    sky_light_mask: vec![0b10],
+                    empty_block_light_mask: vec![0b11],
+                    sky_light: vec![vec![0xFF; 2048]],
+                    ..LightData::default()
+                },
+            },
+        ));
+        round_trip(ClientboundPlayPacket::PlayerInfoUpdate(PlayerInfoUpdate {
+            actions: 0x3F,
+            players: vec![PlayerInfo {
+                uuid: Uuid::from_u128(1),
+                name: "Steve".to_string(),
+                properties: Vec::new(),
+                game_mode: game_mode::SURVIVAL,
+                listed: true,
+                latency: 25,
+                display_name: Some(json!({"text": "Steve"})),
+            }],
+        }));
+        round_trip(ClientboundPlayPacket::SetDefaultSpawnPosition(
+            SetDefaultSpawnPosition {
+                location: position,
+                angle: 90.0,
+            },
+        ));
+        round_trip(ClientboundPlayPacket::SystemChatMessage(
+            SystemChatMessage::new(json!({"text": "hello"})),
+        ));
+        round_trip(ClientboundPlayPacket::KeepAlive(KeepAlive { id: -3 }));
+    }
+
+    #[test]
+    fn unknown_packets_do_not_decode() {
+        let packet = Packet {
+            id: 0x00,
+            data: Bytes::new(),
+        };
+        assert!(ClientboundPlayPacket::try_from(packet).is_err());
+    }
+}
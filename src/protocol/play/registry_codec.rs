@@ -0,0 +1,201 @@
+use crate::nbt::{Compound, Tag};
+
+pub const OVERWORLD: &str = "minecraft:overworld";
+pub const PLAINS: &str = "minecraft:plains";
+pub const THE_VOID: &str = "minecraft:the_void";
+
+include!(concat!(env!("OUT_DIR"), "/biomes.rs"));
+
+/// What clients need to know of a biome, generated from `data/minecraft/worldgen/biome`
+#[derive(Debug)]
+pub struct Biome {
+    pub name: &'static str,
+    pub has_precipitation: bool,
+    pub temperature: f32,
+    pub downfall: f32,
+    pub sky_color: i32,
+    pub fog_color: i32,
+    pub water_color: i32,
+    pub water_fog_color: i32,
+    /// Sound played now and then in dark places
+    pub mood_sound: Option<&'static str>,
+}
+
+/// Every damage type of 1.19.4 with its message ID, scaling and exhaustion. The client looks
+/// these up by name when an entity takes damage, so none of them may be missing.
+const DAMAGE_TYPES: [(&str, &str, &str, f32); 42] = [
+    ("arrow", "arrow", LIVING, 0.1),
+    ("bad_respawn_point", "badRespawnPoint", ALWAYS, 0.1),
+    ("cactus", "cactus", LIVING, 0.1),
+    ("cramming", "cramming", LIVING, 0.0),
+    ("dragon_breath", "dragonBreath", LIVING, 0.0),
+    ("drown", "drown", LIVING, 0.0),
+    ("dry_out", "dryout", LIVING, 0.1),
+    ("explosion", "explosion", ALWAYS, 0.1),
+    ("fall", "fall", LIVING, 0.0),
+    ("falling_anvil", "anvil", LIVING, 0.1),
+    ("falling_block", "fallingBlock", LIVING, 0.1),
+    ("falling_stalactite", "fallingStalactite", LIVING, 0.1),
+    ("fireball", "fireball", LIVING, 0.1),
+    ("fireworks", "fireworks", LIVING, 0.1),
+    ("fly_into_wall", "flyIntoWall", LIVING, 0.0),
+    ("freeze", "freeze", LIVING, 0.0),
+    ("generic", "generic", LIVING, 0.0),
+    ("hot_floor", "hotFloor", LIVING, 0.1),
+    ("in_fire", "inFire", LIVING, 0.1),
+    ("in_wall", "inWall", LIVING, 0.0),
+    ("indirect_magic", "indirectMagic", LIVING, 0.0),
+    ("lava", "lava", LIVING, 0.1),
+    ("lightning_bolt", "lightningBolt", LIVING, 0.1),
+    ("magic", "magic", LIVING, 0.0),
+    ("mob_attack", "mob", LIVING, 0.1),
+    ("mob_attack_no_aggro", "mob", LIVING, 0.1),
+    ("mob_projectile", "mob", LIVING, 0.1),
+    ("on_fire", "onFire", LIVING, 0.0),
+    ("out_of_world", "outOfWorld", LIVING, 0.0),
+    ("player_attack", "player", LIVING, 0.1),
+    ("player_explosion", "explosion.player", ALWAYS, 0.1),
+    ("sonic_boom", "sonic_boom", ALWAYS, 0.0),
+    ("stalagmite", "stalagmite", LIVING, 0.0),
+    ("starve", "starve", LIVING, 0.0),
+    ("sting", "sting", LIVING, 0.1),
+    ("sweet_berry_bush", "sweetBerryBush", LIVING, 0.1),
+    ("thorns", "thorns", LIVING, 0.1),
+    ("thrown", "thrown", LIVING, 0.1),
+    ("trident", "trident", LIVING, 0.1),
+    ("unattributed_fireball", "onFire", LIVING, 0.1),
+    ("wither", "wither", LIVING, 0.0),
+    ("wither_skull", "witherSkull", LIVING, 0.1),
+];
+const LIVING: &str = "when_caused_by_living_non_player";
+const ALWAYS: &str = "always";
+
+/// The registries a client needs in Login (play): the overworld dimension type, [`BIOMES`], the
+/// player chat type, and all damage types
+pub fn registry_codec() -> Compound {
+    Compound::new()
+        .with(
+            "minecraft:dimension_type",
+            registry("minecraft:dimension_type", vec![(OVERWORLD, overworld())]),
+        )
+        .with(
+            "minecraft:worldgen/biome",
+            registry(
+                "minecraft:worldgen/biome",
+                BIOMES
+                    .iter()
+                    .map(|biome| (biome.name, biome_element(biome)))
+                    .collect(),
+            ),
+        )
+        .with(
+            "minecraft:chat_type",
+            registry("minecraft:chat_type", vec![("minecraft:chat", chat())]),
+        )
+        .with(
+            "minecraft:damage_type",
+            registry(
+                "minecraft:damage_type",
+                DAMAGE_TYPES
+                    .iter()
+                    .map(|&(name, message_id, scaling, exhaustion)| {
+                        let element = Compound::new()
+                            .with("message_id", message_id)
+                            .with("scaling", scaling)
+                            .with("exhaustion", exhaustion);
+                        (name, element)
+                    })
+                    .collect(),
+            ),
+        )
+}
+
+/// A registry with its elements numbered in order
+fn registry(kind: &str, elements: Vec<(&str, Compound)>) -> Compound {
+    let value = elements
+        .into_iter()
+        .enumerate()
+        .map(|(id, (name, element))| {
+            let name = if name.contains(':') {
+                name.to_string()
+            } else {
+                format!("minecraft:{name}")
+            };
+            Tag::Compound(
+                Compound::new()
+                    .with("name", name)
+                    .with("id", id as i32)
+                    .with("element", element),
+            )
+        })
+        .collect::<Vec<_>>();
+    Compound::new().with("type", kind).with("value", value)
+}
+
+fn overworld() -> Compound {
+    Compound::new()
+        .with("piglin_safe", false)
+        .with("natural", true)
+        .with("ambient_light", 0.0f32)
+        .with("infiniburn", "#minecraft:infiniburn_overworld")
+        .with("respawn_anchor_works", false)
+        .with("has_skylight", true)
+        .with("bed_works", true)
+        .with("effects", OVERWORLD)
+        .with("has_raids", true)
+        .with("min_y", -64)
+        .with("height", 384)
+        .with("logical_height", 384)
+        .with("coordinate_scale", 1.0f64)
+        .with("ultrawarm", false)
+        .with("has_ceiling", false)
+        .with(
+            "monster_spawn_light_level",
+            Compound::new().with("type", "minecraft:uniform").with(
+                "value",
+                Compound::new()
+                    .with("min_inclusive", 0)
+                    .with("max_inclusive", 7),
+            ),
+        )
+        .with("monster_spawn_block_light_limit", 0)
+}
+
+fn biome_element(biome: &Biome) -> Compound {
+    let mut effects = Compound::new()
+        .with("sky_color", biome.sky_color)
+        .with("water_fog_color", biome.water_fog_color)
+        .with("fog_color", biome.fog_color)
+        .with("water_color", biome.water_color);
+    if let Some(sound) = biome.mood_sound {
+        effects.insert(
+            "mood_sound",
+            Tag::Compound(
+                Compound::new()
+                    .with("tick_delay", 6000)
+                    .with("offset", 2.0f64)
+                    .with("sound", sound)
+                    .with("block_search_extent", 8),
+            ),
+        );
+    }
+    Compound::new()
+        .with("has_precipitation", biome.has_precipitation)
+        .with("temperature", biome.temperature)
+        .with("downfall", biome.downfall)
+        .with("effects", effects)
+}
+
+fn chat() -> Compound {
+    let decoration = |translation_key: &str| {
+        Compound::new()
+            .with("translation_key", translation_key)
+            .with(
+                "parameters",
+                vec![Tag::from("sender"), Tag::from("content")],
+            )
+    };
+    Compound::new()
+        .with("chat", decoration("chat.type.text"))
+        .with("narration", decoration("chat.type.text.narrate"))
+}
@@ -0,0 +1,1232 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use uuid::Uuid;
+
+use super::{Hand, Position, Slot};
+use crate::{
+    limits::{
+        MAX_ARGUMENT_SIGNATURES, MAX_BOOK_PAGES, MAX_BOOK_PAGE_LENGTH, MAX_BOOK_TITLE_LENGTH,
+        MAX_CHANGED_SLOTS, MAX_CHAT_MESSAGE_LENGTH, MAX_COMMAND_SUGGESTION_LENGTH,
+        MAX_PLUGIN_MESSAGE_LENGTH, MAX_SESSION_KEY_LENGTH, MAX_SESSION_KEY_SIGNATURE_LENGTH,
+        MAX_SIGN_LINE_LENGTH, MAX_USERNAME_LENGTH,
+    },
+    protocol::{KeepAlive, Reader},
+    stream::Packet,
+};
+
+/// Length of a chat message signature
+const SIGNATURE_LENGTH: usize = 256;
+/// Bytes in the bit set of the last 20 messages a client acknowledges
+const ACKNOWLEDGED_LENGTH: usize = 3;
+
+/// Every packet a client can send in the Play state, decoded by its ID
+#[derive(Debug)]
+pub enum ServerboundPlayPacket {
+    ConfirmTeleportation(ConfirmTeleportation),
+    QueryBlockEntityTag(QueryBlockEntityTag),
+    ChangeDifficulty(ChangeDifficulty),
+    MessageAcknowledgment(MessageAcknowledgment),
+    ChatCommand(ChatCommand),
+    ChatMessage(ChatMessage),
+    PlayerSession(PlayerSession),
+    ClientCommand(ClientCommand),
+    ClientInformation(ClientInformation),
+    CommandSuggestionsRequest(CommandSuggestionsRequest),
+    ClickContainerButton(ClickContainerButton),
+    ClickContainer(ClickContainer),
+    CloseContainer(CloseContainer),
+    PluginMessage(PluginMessage),
+    EditBook(EditBook),
+    QueryEntityTag(QueryEntityTag),
+    Interact(Interact),
+    JigsawGenerate(JigsawGenerate),
+    KeepAlive(KeepAlive),
+    LockDifficulty(LockDifficulty),
+    SetPlayerPosition(SetPlayerPosition),
+    SetPlayerPositionAndRotation(SetPlayerPositionAndRotation),
+    SetPlayerRotation(SetPlayerRotation),
+    SetPlayerOnGround(SetPlayerOnGround),
+    MoveVehicle(MoveVehicle),
+    PaddleBoat(PaddleBoat),
+    PickItem(PickItem),
+    PlaceRecipe(PlaceRecipe),
+    PlayerAbilities(PlayerAbilities),
+    PlayerAction(PlayerAction),
+    PlayerCommand(PlayerCommand),
+    PlayerInput(PlayerInput),
+    Pong(Pong),
+    ChangeRecipeBookSettings(ChangeRecipeBookSettings),
+    SetSeenRecipe(SetSeenRecipe),
+    RenameItem(RenameItem),
+    ResourcePack(ResourcePack),
+    SeenAdvancements(SeenAdvancements),
+    SelectTrade(SelectTrade),
+    SetBeaconEffect(SetBeaconEffect),
+    SetHeldItem(SetHeldItem),
+    ProgramCommandBlock(ProgramCommandBlock),
+    ProgramCommandBlockMinecart(ProgramCommandBlockMinecart),
+    SetCreativeModeSlot(SetCreativeModeSlot),
+    ProgramJigsawBlock(ProgramJigsawBlock),
+    ProgramStructureBlock(ProgramStructureBlock),
+    UpdateSign(UpdateSign),
+    SwingArm(SwingArm),
+    TeleportToEntity(TeleportToEntity),
+    UseItemOn(UseItemOn),
+    UseItem(UseItem),
+    /// An ID that 1.19.4 does not use, kept as it was received
+    Unknown(Packet),
+}
+impl TryFrom<Packet> for ServerboundPlayPacket {
+    type Error = anyhow::Error;
+
+    fn try_from(packet: Packet) -> Result<Self, Self::Error> {
+        use ServerboundPlayPacket as P;
+
+        let data = packet.data.clone();
+        Ok(match packet.id {
+            0x00 => P::ConfirmTeleportation(data.try_into()?),
+            0x01 => P::QueryBlockEntityTag(data.try_into()?),
+            0x02 => P::ChangeDifficulty(data.try_into()?),
+            0x03 => P::MessageAcknowledgment(data.try_into()?),
+            0x04 => P::ChatCommand(data.try_into()?),
+            0x05 => P::ChatMessage(data.try_into()?),
+            0x06 => P::PlayerSession(data.try_into()?),
+            0x07 => P::ClientCommand(data.try_into()?),
+            0x08 => P::ClientInformation(data.try_into()?),
+            0x09 => P::CommandSuggestionsRequest(data.try_into()?),
+            0x0A => P::ClickContainerButton(data.try_into()?),
+            0x0B => P::ClickContainer(data.try_into()?),
+            0x0C => P::CloseContainer(data.try_into()?),
+            0x0D => P::PluginMessage(data.try_into()?),
+            0x0E => P::EditBook(data.try_into()?),
+            0x0F => P::QueryEntityTag(data.try_into()?),
+            0x10 => P::Interact(data.try_into()?),
+            0x11 => P::JigsawGenerate(data.try_into()?),
+            0x12 => P::KeepAlive(data.try_into()?),
+            0x13 => P::LockDifficulty(data.try_into()?),
+            0x14 => P::SetPlayerPosition(data.try_into()?),
+            0x15 => P::SetPlayerPositionAndRotation(data.try_into()?),
+            0x16 => P::SetPlayerRotation(data.try_into()?),
+            0x17 => P::SetPlayerOnGround(data.try_into()?),
+            0x18 => P::MoveVehicle(data.try_into()?),
+            0x19 => P::PaddleBoat(data.try_into()?),
+            0x1A => P::PickItem(data.try_into()?),
+            0x1B => P::PlaceRecipe(data.try_into()?),
+            0x1C => P::PlayerAbilities(data.try_into()?),
+            0x1D => P::PlayerAction(data.try_into()?),
+            0x1E => P::PlayerCommand(data.try_into()?),
+            0x1F => P::PlayerInput(data.try_into()?),
+            0x20 => P::Pong(data.try_into()?),
+            0x21 => P::ChangeRecipeBookSettings(data.try_into()?),
+            0x22 => P::SetSeenRecipe(data.try_into()?),
+            0x23 => P::RenameItem(data.try_into()?),
+            0x24 => P::ResourcePack(data.try_into()?),
+            0x25 => P::SeenAdvancements(data.try_into()?),
+            0x26 => P::SelectTrade(data.try_into()?),
+            0x27 => P::SetBeaconEffect(data.try_into()?),
+            0x28 => P::SetHeldItem(data.try_into()?),
+            0x29 => P::ProgramCommandBlock(data.try_into()?),
+            0x2A => P::ProgramCommandBlockMinecart(data.try_into()?),
+            0x2B => P::SetCreativeModeSlot(data.try_into()?),
+            0x2C => P::ProgramJigsawBlock(data.try_into()?),
+            0x2D => P::ProgramStructureBlock(data.try_into()?),
+            0x2E => P::UpdateSign(data.try_into()?),
+            0x2F => P::SwingArm(data.try_into()?),
+            0x30 => P::TeleportToEntity(data.try_into()?),
+            0x31 => P::UseItemOn(data.try_into()?),
+            0x32 => P::UseItem(data.try_into()?),
+            _ => P::Unknown(packet),
+        })
+    }
+}
+
+/// Signature of a signed chat message or command argument
+fn read_signature(cursor: &mut Reader) -> Result<Vec<u8>> {
+    Ok(cursor.read_bytes(SIGNATURE_LENGTH)?.to_vec())
+}
+
+fn read_identifier(cursor: &mut Reader) -> Result<String> {
+    cursor.read_string()
+}
+
+/// Sent after the client accepts a Synchronize Player Position
+#[derive(Debug)]
+pub struct ConfirmTeleportation {
+    pub teleport_id: i32,
+}
+impl TryFrom<Bytes> for ConfirmTeleportation {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ConfirmTeleportation {
+            teleport_id: cursor.read_varint()?,
+        })
+    }
+}
+
+/// Sent when pressing F3+I on a block entity
+#[derive(Debug)]
+pub struct QueryBlockEntityTag {
+    pub transaction_id: i32,
+    pub location: Position,
+}
+impl TryFrom<Bytes> for QueryBlockEntityTag {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(QueryBlockEntityTag {
+            transaction_id: cursor.read_varint()?,
+            location: Position::read(&mut cursor)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ChangeDifficulty {
+    /// 0: peaceful, 1: easy, 2: normal, 3: hard
+    pub difficulty: u8,
+}
+impl TryFrom<Bytes> for ChangeDifficulty {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ChangeDifficulty {
+            difficulty: cursor.read_u8()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MessageAcknowledgment {
+    pub message_count: i32,
+}
+impl TryFrom<Bytes> for MessageAcknowledgment {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(MessageAcknowledgment {
+            message_count: cursor.read_varint()?,
+        })
+    }
+}
+
+/// Signature of one argument of a [`ChatCommand`]
+#[derive(Debug)]
+pub struct ArgumentSignature {
+    pub name: String,
+    pub signature: Vec<u8>,
+}
+
+/// A command typed in chat, without the leading `/`
+#[derive(Debug)]
+pub struct ChatCommand {
+    pub command: String,
+    pub timestamp: i64,
+    pub salt: i64,
+    pub argument_signatures: Vec<ArgumentSignature>,
+    pub message_count: i32,
+    pub acknowledged: [u8; ACKNOWLEDGED_LENGTH],
+}
+impl TryFrom<Bytes> for ChatCommand {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let command = cursor.read_string_max(MAX_CHAT_MESSAGE_LENGTH)?;
+        let timestamp = cursor.read_i64()?;
+        let salt = cursor.read_i64()?;
+        let length = cursor.read_array_length(MAX_ARGUMENT_SIGNATURES)?;
+        let mut argument_signatures = Vec::with_capacity(length);
+        for _ in 0..length {
+            argument_signatures.push(ArgumentSignature {
+                name: cursor.read_string_max(MAX_USERNAME_LENGTH)?,
+                signature: read_signature(&mut cursor)?,
+            });
+        }
+        let message_count = cursor.read_varint()?;
+        let acknowledged = read_acknowledged(&mut cursor)?;
+        Ok(ChatCommand {
+            command,
+            timestamp,
+            salt,
+            argument_signatures,
+            message_count,
+            acknowledged,
+        })
+    }
+}
+
+fn read_acknowledged(cursor: &mut Reader) -> Result<[u8; ACKNOWLEDGED_LENGTH]> {
+    let bytes = cursor.read_bytes(ACKNOWLEDGED_LENGTH)?;
+    Ok([bytes[0], bytes[1], bytes[2]])
+}
+
+#[derive(Debug)]
+pub struct ChatMessage {
+    pub message: String,
+    pub timestamp: i64,
+    pub salt: i64,
+    pub signature: Option<Vec<u8>>,
+    pub message_count: i32,
+    pub acknowledged: [u8; ACKNOWLEDGED_LENGTH],
+}
+impl TryFrom<Bytes> for ChatMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let message = cursor.read_string_max(MAX_CHAT_MESSAGE_LENGTH)?;
+        let timestamp = cursor.read_i64()?;
+        let salt = cursor.read_i64()?;
+        let signature = if cursor.read_bool()? {
+            Some(read_signature(&mut cursor)?)
+        } else {
+            None
+        };
+        let message_count = cursor.read_varint()?;
+        let acknowledged = read_acknowledged(&mut cursor)?;
+        Ok(ChatMessage {
+            message,
+            timestamp,
+            salt,
+            signature,
+            message_count,
+            acknowledged,
+        })
+    }
+}
+
+/// The key a client signs its chat messages with, signed by Mojang
+#[derive(Debug)]
+pub struct PlayerSession {
+    pub session_id: Uuid,
+    /// Milliseconds since the Unix epoch
+    pub expires_at: i64,
+    pub public_key: Vec<u8>,
+    pub key_signature: Vec<u8>,
+}
+impl TryFrom<Bytes> for PlayerSession {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PlayerSession {
+            session_id: cursor.read_uuid()?,
+            expires_at: cursor.read_i64()?,
+            public_key: cursor.read_byte_array_max(MAX_SESSION_KEY_LENGTH)?,
+            key_signature: cursor.read_byte_array_max(MAX_SESSION_KEY_SIGNATURE_LENGTH)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientCommand {
+    /// 0: respawn, 1: request statistics
+    pub action: i32,
+}
+impl TryFrom<Bytes> for ClientCommand {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ClientCommand {
+            action: cursor.read_varint()?,
+        })
+    }
+}
+
+/// The client's settings, sent when joining and whenever they change
+#[derive(Debug)]
+pub struct ClientInformation {
+    pub locale: String,
+    pub view_distance: i8,
+    /// 0: enabled, 1: commands only, 2: hidden
+    pub chat_mode: i32,
+    pub chat_colors: bool,
+    /// Bit mask of cape, jacket, left sleeve, right sleeve, left and right pants leg, and hat
+    pub displayed_skin_parts: u8,
+    /// 0: left, 1: right
+    pub main_hand: i32,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+impl TryFrom<Bytes> for ClientInformation {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ClientInformation {
+            locale: cursor.read_string_max(16)?,
+            view_distance: cursor.read_i8()?,
+            chat_mode: cursor.read_varint()?,
+            chat_colors: cursor.read_bool()?,
+            displayed_skin_parts: cursor.read_u8()?,
+            main_hand: cursor.read_varint()?,
+            enable_text_filtering: cursor.read_bool()?,
+            allow_server_listings: cursor.read_bool()?,
+        })
+    }
+}
+
+/// Sent while typing a command, to get tab completions
+#[derive(Debug)]
+pub struct CommandSuggestionsRequest {
+    pub transaction_id: i32,
+    pub text: String,
+}
+impl TryFrom<Bytes> for CommandSuggestionsRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(CommandSuggestionsRequest {
+            transaction_id: cursor.read_varint()?,
+            text: cursor.read_string_max(MAX_COMMAND_SUGGESTION_LENGTH)?,
+        })
+    }
+}
+
+/// Clicking a button in an enchantment table, lectern, loom or stonecutter
+#[derive(Debug)]
+pub struct ClickContainerButton {
+    pub window_id: i8,
+    pub button_id: i8,
+}
+impl TryFrom<Bytes> for ClickContainerButton {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ClickContainerButton {
+            window_id: cursor.read_i8()?,
+            button_id: cursor.read_i8()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ClickContainer {
+    pub window_id: u8,
+    pub state_id: i32,
+    /// -999 when clicking outside the window
+    pub slot: i16,
+    pub button: i8,
+    pub mode: i32,
+    /// The slots the client expects to be changed, and their new contents
+    pub changed_slots: Vec<(i16, Option<Slot>)>,
+    pub carried_item: Option<Slot>,
+}
+impl TryFrom<Bytes> for ClickContainer {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let window_id = cursor.read_u8()?;
+        let state_id = cursor.read_varint()?;
+        let slot = cursor.read_i16()?;
+        let button = cursor.read_i8()?;
+        let mode = cursor.read_varint()?;
+        let length = cursor.read_array_length(MAX_CHANGED_SLOTS)?;
+        let mut changed_slots = Vec::with_capacity(length);
+        for _ in 0..length {
+            changed_slots.push((cursor.read_i16()?, Slot::read(&mut cursor)?));
+        }
+        let carried_item = Slot::read(&mut cursor)?;
+        Ok(ClickContainer {
+            window_id,
+            state_id,
+            slot,
+            button,
+            mode,
+            changed_slots,
+            carried_item,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CloseContainer {
+    pub window_id: u8,
+}
+impl TryFrom<Bytes> for CloseContainer {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(CloseContainer {
+            window_id: cursor.read_u8()?,
+        })
+    }
+}
+
+/// Custom data on a channel, like `minecraft:brand` with the name of the client
+#[derive(Debug)]
+pub struct PluginMessage {
+    pub channel: String,
+    pub data: Vec<u8>,
+}
+impl TryFrom<Bytes> for PluginMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let channel = read_identifier(&mut cursor)?;
+        let data = cursor.get_leftover_bytes();
+        if data.len() > MAX_PLUGIN_MESSAGE_LENGTH {
+            return Err(anyhow!(
+                "Plugin message of {} bytes exceeds the maximum of {MAX_PLUGIN_MESSAGE_LENGTH}",
+                data.len()
+            ));
+        }
+        Ok(PluginMessage {
+            channel,
+            data: data.to_vec(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct EditBook {
+    pub slot: i32,
+    pub pages: Vec<String>,
+    /// Set when the book is signed
+    pub title: Option<String>,
+}
+impl TryFrom<Bytes> for EditBook {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let slot = cursor.read_varint()?;
+        let length = cursor.read_array_length(MAX_BOOK_PAGES)?;
+        let mut pages = Vec::with_capacity(length);
+        for _ in 0..length {
+            pages.push(cursor.read_string_max(MAX_BOOK_PAGE_LENGTH)?);
+        }
+        let title = if cursor.read_bool()? {
+            Some(cursor.read_string_max(MAX_BOOK_TITLE_LENGTH)?)
+        } else {
+            None
+        };
+        Ok(EditBook { slot, pages, title })
+    }
+}
+
+/// Sent when pressing F3+I on an entity
+#[derive(Debug)]
+pub struct QueryEntityTag {
+    pub transaction_id: i32,
+    pub entity_id: i32,
+}
+impl TryFrom<Bytes> for QueryEntityTag {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(QueryEntityTag {
+            transaction_id: cursor.read_varint()?,
+            entity_id: cursor.read_varint()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum InteractAction {
+    Interact(Hand),
+    Attack,
+    InteractAt { x: f32, y: f32, z: f32, hand: Hand },
+}
+
+/// Right or left clicking an entity
+#[derive(Debug)]
+pub struct Interact {
+    pub entity_id: i32,
+    pub action: InteractAction,
+    pub sneaking: bool,
+}
+impl TryFrom<Bytes> for Interact {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let entity_id = cursor.read_varint()?;
+        let action = match cursor.read_varint()? {
+            0 => InteractAction::Interact(Hand::read(&mut cursor)?),
+            1 => InteractAction::Attack,
+            2 => InteractAction::InteractAt {
+                x: cursor.read_f32()?,
+                y: cursor.read_f32()?,
+                z: cursor.read_f32()?,
+                hand: Hand::read(&mut cursor)?,
+            },
+            action => return Err(anyhow!("Invalid interact action {action}")),
+        };
+        let sneaking = cursor.read_bool()?;
+        Ok(Interact {
+            entity_id,
+            action,
+            sneaking,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct JigsawGenerate {
+    pub location: Position,
+    pub levels: i32,
+    pub keep_jigsaws: bool,
+}
+impl TryFrom<Bytes> for JigsawGenerate {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(JigsawGenerate {
+            location: Position::read(&mut cursor)?,
+            levels: cursor.read_varint()?,
+            keep_jigsaws: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct LockDifficulty {
+    pub locked: bool,
+}
+impl TryFrom<Bytes> for LockDifficulty {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(LockDifficulty {
+            locked: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetPlayerPosition {
+    pub x: f64,
+    /// Position of the player's feet
+    pub y: f64,
+    pub z: f64,
+    pub on_ground: bool,
+}
+impl TryFrom<Bytes> for SetPlayerPosition {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetPlayerPosition {
+            x: cursor.read_f64()?,
+            y: cursor.read_f64()?,
+            z: cursor.read_f64()?,
+            on_ground: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetPlayerPositionAndRotation {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+impl TryFrom<Bytes> for SetPlayerPositionAndRotation {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetPlayerPositionAndRotation {
+            x: cursor.read_f64()?,
+            y: cursor.read_f64()?,
+            z: cursor.read_f64()?,
+            yaw: cursor.read_f32()?,
+            pitch: cursor.read_f32()?,
+            on_ground: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetPlayerRotation {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+impl TryFrom<Bytes> for SetPlayerRotation {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetPlayerRotation {
+            yaw: cursor.read_f32()?,
+            pitch: cursor.read_f32()?,
+            on_ground: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetPlayerOnGround {
+    pub on_ground: bool,
+}
+impl TryFrom<Bytes> for SetPlayerOnGround {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetPlayerOnGround {
+            on_ground: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MoveVehicle {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+impl TryFrom<Bytes> for MoveVehicle {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(MoveVehicle {
+            x: cursor.read_f64()?,
+            y: cursor.read_f64()?,
+            z: cursor.read_f64()?,
+            yaw: cursor.read_f32()?,
+            pitch: cursor.read_f32()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct PaddleBoat {
+    pub left_turning: bool,
+    pub right_turning: bool,
+}
+impl TryFrom<Bytes> for PaddleBoat {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PaddleBoat {
+            left_turning: cursor.read_bool()?,
+            right_turning: cursor.read_bool()?,
+        })
+    }
+}
+
+/// Middle clicking a block in creative mode
+#[derive(Debug)]
+pub struct PickItem {
+    pub slot: i32,
+}
+impl TryFrom<Bytes> for PickItem {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PickItem {
+            slot: cursor.read_varint()?,
+        })
+    }
+}
+
+/// Clicking a recipe in the recipe book
+#[derive(Debug)]
+pub struct PlaceRecipe {
+    pub window_id: i8,
+    pub recipe: String,
+    pub make_all: bool,
+}
+impl TryFrom<Bytes> for PlaceRecipe {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PlaceRecipe {
+            window_id: cursor.read_i8()?,
+            recipe: read_identifier(&mut cursor)?,
+            make_all: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct PlayerAbilities {
+    /// 0x02 when flying
+    pub flags: i8,
+}
+impl TryFrom<Bytes> for PlayerAbilities {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PlayerAbilities {
+            flags: cursor.read_i8()?,
+        })
+    }
+}
+
+/// Digging, dropping items and swapping hands
+#[derive(Debug)]
+pub struct PlayerAction {
+    /// 0: started digging, 1: cancelled digging, 2: finished digging, 3: drop item stack,
+    /// 4: drop item, 5: shoot arrow or finish eating, 6: swap item in hand
+    pub status: i32,
+    pub location: Position,
+    pub face: i8,
+    pub sequence: i32,
+}
+impl TryFrom<Bytes> for PlayerAction {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PlayerAction {
+            status: cursor.read_varint()?,
+            location: Position::read(&mut cursor)?,
+            face: cursor.read_i8()?,
+            sequence: cursor.read_varint()?,
+        })
+    }
+}
+
+/// Sneaking, sprinting, leaving a bed and horse jumps
+#[derive(Debug)]
+pub struct PlayerCommand {
+    pub entity_id: i32,
+    pub action: i32,
+    /// From 0 to 100 when jumping with a horse
+    pub jump_boost: i32,
+}
+impl TryFrom<Bytes> for PlayerCommand {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PlayerCommand {
+            entity_id: cursor.read_varint()?,
+            action: cursor.read_varint()?,
+            jump_boost: cursor.read_varint()?,
+        })
+    }
+}
+
+/// Movement input while riding a vehicle
+#[derive(Debug)]
+pub struct PlayerInput {
+    pub sideways: f32,
+    pub forward: f32,
+    /// 0x01 to jump, 0x02 to unmount
+    pub flags: u8,
+}
+impl TryFrom<Bytes> for PlayerInput {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(PlayerInput {
+            sideways: cursor.read_f32()?,
+            forward: cursor.read_f32()?,
+            flags: cursor.read_u8()?,
+        })
+    }
+}
+
+/// Answer to a Ping from the server
+#[derive(Debug)]
+pub struct Pong {
+    pub id: i32,
+}
+impl TryFrom<Bytes> for Pong {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(Pong {
+            id: cursor.read_i32()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ChangeRecipeBookSettings {
+    /// 0: crafting, 1: furnace, 2: blast furnace, 3: smoker
+    pub book_id: i32,
+    pub book_open: bool,
+    pub filter_active: bool,
+}
+impl TryFrom<Bytes> for ChangeRecipeBookSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ChangeRecipeBookSettings {
+            book_id: cursor.read_varint()?,
+            book_open: cursor.read_bool()?,
+            filter_active: cursor.read_bool()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetSeenRecipe {
+    pub recipe: String,
+}
+impl TryFrom<Bytes> for SetSeenRecipe {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetSeenRecipe {
+            recipe: read_identifier(&mut cursor)?,
+        })
+    }
+}
+
+/// Typing a name in an anvil
+#[derive(Debug)]
+pub struct RenameItem {
+    pub name: String,
+}
+impl TryFrom<Bytes> for RenameItem {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(RenameItem {
+            name: cursor.read_string()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ResourcePack {
+    /// 0: loaded, 1: declined, 2: failed to download, 3: accepted
+    pub result: i32,
+}
+impl TryFrom<Bytes> for ResourcePack {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ResourcePack {
+            result: cursor.read_varint()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum SeenAdvancements {
+    OpenedTab(String),
+    ClosedScreen,
+}
+impl TryFrom<Bytes> for SeenAdvancements {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        match cursor.read_varint()? {
+            0 => Ok(SeenAdvancements::OpenedTab(read_identifier(&mut cursor)?)),
+            1 => Ok(SeenAdvancements::ClosedScreen),
+            action => Err(anyhow!("Invalid advancements action {action}")),
+        }
+    }
+}
+
+/// Selecting a trade of a villager
+#[derive(Debug)]
+pub struct SelectTrade {
+    pub slot: i32,
+}
+impl TryFrom<Bytes> for SelectTrade {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SelectTrade {
+            slot: cursor.read_varint()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetBeaconEffect {
+    pub primary_effect: Option<i32>,
+    pub secondary_effect: Option<i32>,
+}
+impl TryFrom<Bytes> for SetBeaconEffect {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        let primary_effect = if cursor.read_bool()? {
+            Some(cursor.read_varint()?)
+        } else {
+            None
+        };
+        let secondary_effect = if cursor.read_bool()? {
+            Some(cursor.read_varint()?)
+        } else {
+            None
+        };
+        Ok(SetBeaconEffect {
+            primary_effect,
+            secondary_effect,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SetHeldItem {
+    /// Hotbar slot from 0 to 8
+    pub slot: i16,
+}
+impl TryFrom<Bytes> for SetHeldItem {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetHeldItem {
+            slot: cursor.read_i16()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramCommandBlock {
+    pub location: Position,
+    pub command: String,
+    /// 0: sequence, 1: auto, 2: redstone
+    pub mode: i32,
+    /// 0x01: track output, 0x02: conditional, 0x04: always active
+    pub flags: i8,
+}
+impl TryFrom<Bytes> for ProgramCommandBlock {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ProgramCommandBlock {
+            location: Position::read(&mut cursor)?,
+            command: cursor.read_string()?,
+            mode: cursor.read_varint()?,
+            flags: cursor.read_i8()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramCommandBlockMinecart {
+    pub entity_id: i32,
+    pub command: String,
+    pub track_output: bool,
+}
+impl TryFrom<Bytes> for ProgramCommandBlockMinecart {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ProgramCommandBlockMinecart {
+            entity_id: cursor.read_varint()?,
+            command: cursor.read_string()?,
+            track_output: cursor.read_bool()?,
+        })
+    }
+}
+
+/// Any change to the inventory in creative mode, which the server has to trust
+#[derive(Debug)]
+pub struct SetCreativeModeSlot {
+    pub slot: i16,
+    pub clicked_item: Option<Slot>,
+}
+impl TryFrom<Bytes> for SetCreativeModeSlot {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SetCreativeModeSlot {
+            slot: cursor.read_i16()?,
+            clicked_item: Slot::read(&mut cursor)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramJigsawBlock {
+    pub location: Position,
+    pub name: String,
+    pub target: String,
+    pub pool: String,
+    pub final_state: String,
+    /// `rollable` or `aligned`
+    pub joint_type: String,
+}
+impl TryFrom<Bytes> for ProgramJigsawBlock {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ProgramJigsawBlock {
+            location: Position::read(&mut cursor)?,
+            name: read_identifier(&mut cursor)?,
+            target: read_identifier(&mut cursor)?,
+            pool: read_identifier(&mut cursor)?,
+            final_state: cursor.read_string()?,
+            joint_type: cursor.read_string()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgramStructureBlock {
+    pub location: Position,
+    /// 0: update data, 1: save, 2: load, 3: detect size
+    pub action: i32,
+    /// 0: save, 1: load, 2: corner, 3: data
+    pub mode: i32,
+    pub name: String,
+    pub offset: [i8; 3],
+    pub size: [i8; 3],
+    pub mirror: i32,
+    pub rotation: i32,
+    pub metadata: String,
+    pub integrity: f32,
+    pub seed: i64,
+    /// 0x01: ignore entities, 0x02: show air, 0x04: show bounding box
+    pub flags: i8,
+}
+impl TryFrom<Bytes> for ProgramStructureBlock {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(ProgramStructureBlock {
+            location: Position::read(&mut cursor)?,
+            action: cursor.read_varint()?,
+            mode: cursor.read_varint()?,
+            name: cursor.read_string()?,
+            offset: [cursor.read_i8()?, cursor.read_i8()?, cursor.read_i8()?],
+            size: [cursor.read_i8()?, cursor.read_i8()?, cursor.read_i8()?],
+            mirror: cursor.read_varint()?,
+            rotation: cursor.read_varint()?,
+            metadata: cursor.read_string_max(128)?,
+            integrity: cursor.read_f32()?,
+            seed: cursor.read_varlong()?,
+            flags: cursor.read_i8()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct UpdateSign {
+    pub location: Position,
+    pub lines: [String; 4],
+}
+impl TryFrom<Bytes> for UpdateSign {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(UpdateSign {
+            location: Position::read(&mut cursor)?,
+            lines: [
+                cursor.read_string_max(MAX_SIGN_LINE_LENGTH)?,
+                cursor.read_string_max(MAX_SIGN_LINE_LENGTH)?,
+                cursor.read_string_max(MAX_SIGN_LINE_LENGTH)?,
+                cursor.read_string_max(MAX_SIGN_LINE_LENGTH)?,
+            ],
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SwingArm {
+    pub hand: Hand,
+}
+impl TryFrom<Bytes> for SwingArm {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(SwingArm {
+            hand: Hand::read(&mut cursor)?,
+        })
+    }
+}
+
+/// Teleporting to a player in spectator mode
+#[derive(Debug)]
+pub struct TeleportToEntity {
+    pub target: Uuid,
+}
+impl TryFrom<Bytes> for TeleportToEntity {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(TeleportToEntity {
+            target: cursor.read_uuid()?,
+        })
+    }
+}
+
+/// Right clicking a block, to place a block or use an item on it
+#[derive(Debug)]
+pub struct UseItemOn {
+    pub hand: Hand,
+    pub location: Position,
+    pub face: i32,
+    /// Position on the face that was clicked, from 0 to 1
+    pub cursor: [f32; 3],
+    pub inside_block: bool,
+    pub sequence: i32,
+}
+impl TryFrom<Bytes> for UseItemOn {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(UseItemOn {
+            hand: Hand::read(&mut cursor)?,
+            location: Position::read(&mut cursor)?,
+            face: cursor.read_varint()?,
+            cursor: [cursor.read_f32()?, cursor.read_f32()?, cursor.read_f32()?],
+            inside_block: cursor.read_bool()?,
+            sequence: cursor.read_varint()?,
+        })
+    }
+}
+
+/// Right clicking with an item in the air
+#[derive(Debug)]
+pub struct UseItem {
+    pub hand: Hand,
+    pub sequence: i32,
+}
+impl TryFrom<Bytes> for UseItem {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        let mut cursor = Reader::new(bytes);
+        Ok(UseItem {
+            hand: Hand::read(&mut cursor)?,
+            sequence: cursor.read_varint()?,
+        })
+    }
+}
@@ -0,0 +1,184 @@
+use std::{future, io::ErrorKind};
+
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{
+    capture::{CaptureWriter, Direction},
+    client::{Account, Client},
+    connection::Connection,
+    outbound::{disconnect_packet, Outbound, OutboundReceiver},
+    protocol::{
+        play::{clientbound::ClientboundPlayPacket, serverbound::ServerboundPlayPacket},
+        ConnectionState,
+    },
+    stream::{Packet, PacketReader, PacketWriter},
+};
+
+/// Relays logged-in players to an upstream offline-mode server, logging every packet in plaintext.
+///
+/// The client's side is handled by a [`Connection`], which performs the handshake, status and
+/// encrypted login itself. Only once the player is authenticated, the proxy logs in to the
+/// upstream server with the same username, and relays packets in both directions. Each side
+/// keeps its own encryption and compression state.
+///
+/// Packets are logged decoded, or by their ID and length if they can't be, unless
+/// [`verbose`](Self::verbose) logs the raw bytes of all of them.
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    host: String,
+    port: u16,
+    verbose: bool,
+}
+impl Proxy {
+    pub fn new(host: &str, port: u16) -> Proxy {
+        Proxy {
+            host: host.to_string(),
+            port,
+            verbose: false,
+        }
+    }
+
+    /// Log the raw bytes of every relayed packet
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub async fn handle(&self, mut connection: Connection) -> Result<()> {
+        connection.handle_until_play().await?;
+        if connection.state() != ConnectionState::Play {
+            return Ok(());
+        }
+
+        let player = connection
+            .player()
+            .cloned()
+            .ok_or(anyhow!("Missing player"))?;
+        println!(
+            "Connecting {} to {}:{}",
+            player.username, self.host, self.port
+        );
+
+        // The connection is given up for the relay, so leave the registry in its place
+        let registry = connection.registry().cloned();
+        let connection_id = connection.id();
+        let result = self.relay_to_upstream(connection, &player.username).await;
+        if let (Some(registry), Some(uuid)) = (registry, player.uuid) {
+            registry.leave(&uuid, connection_id);
+        }
+        result
+    }
+
+    async fn relay_to_upstream(&self, connection: Connection, username: &str) -> Result<()> {
+        let mut upstream = Client::connect(&self.host, self.port).await?;
+        upstream.login(&Account::offline(username)).await?;
+
+        let capture = connection.capture().cloned();
+        // Packets and kicks sent through the connection's handles, like when the player logs in
+        // again elsewhere, still reach the client
+        let (client_reader, client_writer, outbound) = connection.into_relay().await?;
+        let (server_reader, server_writer) = upstream.into_stream().into_split();
+
+        let verbose = self.verbose;
+        tokio::select! {
+            result = relay(client_reader, server_writer, Direction::Serverbound, capture.clone(), verbose, None) => result,
+            result = relay(server_reader, client_writer, Direction::Clientbound, capture, verbose, Some(outbound)) => result,
+        }
+    }
+}
+
+/// Forward packets from `reader` to `writer` until the reading side closes, along with the ones
+/// from `outbound` until it kicks
+async fn relay<R, W>(
+    mut reader: PacketReader<R>,
+    mut writer: PacketWriter<W>,
+    direction: Direction,
+    capture: Option<CaptureWriter>,
+    verbose: bool,
+    mut outbound: Option<OutboundReceiver>,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let label = match direction {
+        Direction::Serverbound => "C->S",
+        Direction::Clientbound => "S->C",
+    };
+    loop {
+        let read = tokio::select! {
+            read = reader.read_packet() => read,
+            message = next_message(&mut outbound) => {
+                let packet = match message? {
+                    Outbound::Packet(packet) => packet,
+                    Outbound::Play(packet) => packet.try_into()?,
+                    Outbound::Kick(reason) => {
+                        if let Some(packet) = disconnect_packet(ConnectionState::Play, &reason)? {
+                            writer.write_packet(packet).await?;
+                        }
+                        println!("{label} kicked: {reason}");
+                        return Ok(());
+                    }
+                    // The rest only come from the connection itself, which is gone
+                    _ => continue,
+                };
+                Ok(packet)
+            }
+        };
+        let packet = match read {
+            Ok(packet) => packet,
+            Err(e) => {
+                return match e.downcast_ref::<std::io::Error>() {
+                    Some(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                        println!("{label} closed");
+                        Ok(())
+                    }
+                    _ => Err(e),
+                }
+            }
+        };
+        log_packet(label, direction, &packet, verbose);
+        if let Some(capture) = &capture {
+            capture.record(direction, ConnectionState::Play, &packet)?;
+        }
+        writer.write_packet(packet).await?;
+    }
+}
+
+fn log_packet(label: &str, direction: Direction, packet: &Packet, verbose: bool) {
+    if verbose {
+        println!("{label} {packet:?}");
+        return;
+    }
+    let decoded = match direction {
+        Direction::Serverbound => ServerboundPlayPacket::try_from(packet.clone())
+            .ok()
+            .map(|decoded| format!("{decoded:?}")),
+        Direction::Clientbound => match ClientboundPlayPacket::try_from(packet.clone()) {
+            // The sections and light of a chunk would flood the log
+            Ok(ClientboundPlayPacket::ChunkDataAndUpdateLight(chunk)) => Some(format!(
+                "ChunkDataAndUpdateLight {{ x: {}, z: {}, .. }} ({} bytes)",
+                chunk.x,
+                chunk.z,
+                packet.data.len()
+            )),
+            decoded => decoded.ok().map(|decoded| format!("{decoded:?}")),
+        },
+    };
+    match decoded {
+        Some(decoded) => println!("{label} {decoded}"),
+        None => println!("{label} 0x{:02X} ({} bytes)", packet.id, packet.data.len()),
+    }
+}
+
+/// The next message sent through the handles, or never if there is nothing to receive from
+async fn next_message(outbound: &mut Option<OutboundReceiver>) -> Result<Outbound> {
+    if let Some(receiver) = outbound {
+        if let Some(message) = receiver.recv().await? {
+            return Ok(message);
+        }
+        *outbound = None;
+    }
+    future::pending().await
+}
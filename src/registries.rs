@@ -0,0 +1,76 @@
+//! The game's built-in registries, like items and entity types, generated from
+//! `registries.json`. Not to be confused with [`crate::registry`], which keeps the online
+//! players.
+
+include!(concat!(env!("OUT_DIR"), "/registries.rs"));
+
+/// Identifiers of a registry, by their protocol ID
+#[derive(Debug)]
+pub struct Registry {
+    name: &'static str,
+    entries: &'static [&'static str],
+    /// Indices into `entries` in the order of their names
+    by_name: &'static [u32],
+}
+impl Registry {
+    /// Look a registry up by its identifier, like `minecraft:item`
+    pub fn by_name(name: &str) -> Option<&'static Registry> {
+        REGISTRIES
+            .iter()
+            .copied()
+            .find(|registry| registry.name == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Identifier of an entry from its protocol ID
+    pub fn get(&self, id: u32) -> Option<&'static str> {
+        self.entries.get(id as usize).copied()
+    }
+
+    /// Protocol ID of an entry from its identifier
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.by_name
+            .binary_search_by(|&index| self.entries[index as usize].cmp(name))
+            .ok()
+            .map(|position| self.by_name[position])
+    }
+
+    /// Identifiers in the order of their protocol IDs
+    pub fn entries(&self) -> &'static [&'static str] {
+        self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::play::Slot;
+
+    #[test]
+    fn items_have_their_protocol_ids() {
+        assert_eq!(ITEMS.id("minecraft:stone"), Some(1));
+        assert_eq!(ITEMS.get(0), Some("minecraft:air"));
+        assert_eq!(
+            Registry::by_name("minecraft:item").unwrap().name(),
+            ITEMS.name()
+        );
+
+        let slot = Slot {
+            item_id: 1,
+            count: 64,
+            nbt: None,
+        };
+        assert_eq!(slot.item_name(), Some("minecraft:stone"));
+    }
+}
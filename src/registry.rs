@@ -0,0 +1,101 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Mutex};
+
+use uuid::Uuid;
+
+use crate::{
+    outbound::ConnectionHandle,
+    protocol::{StatusPlayerSample, StatusPlayers},
+};
+
+pub const DEFAULT_MAX_PLAYERS: usize = 20;
+/// Number of players listed when hovering over the player count in the server list
+pub const STATUS_SAMPLE_SIZE: usize = 12;
+pub const DUPLICATE_LOGIN_MESSAGE: &str = "You logged in from another location";
+
+/// A player in the Play state of one of the server's connections
+#[derive(Debug, Clone)]
+pub struct OnlinePlayer {
+    pub uuid: Uuid,
+    pub name: String,
+    pub address: SocketAddr,
+    pub handle: ConnectionHandle,
+}
+
+/// Every player online on a server, shared by all its connections
+#[derive(Debug)]
+pub struct PlayerRegistry {
+    max_players: usize,
+    players: Mutex<HashMap<Uuid, OnlinePlayer>>,
+}
+impl PlayerRegistry {
+    pub fn new(max_players: usize) -> PlayerRegistry {
+        PlayerRegistry {
+            max_players,
+            players: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_players(&self) -> usize {
+        self.max_players
+    }
+
+    pub fn len(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<OnlinePlayer> {
+        self.players.lock().unwrap().get(uuid).cloned()
+    }
+
+    pub fn players(&self) -> Vec<OnlinePlayer> {
+        self.players.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Add a player, returning the connection already online with the same UUID, which should
+    /// be kicked. Fails if the server is full, unless the player replaces themselves.
+    pub fn try_join(&self, player: OnlinePlayer) -> Result<Option<OnlinePlayer>, OnlinePlayer> {
+        let mut players = self.players.lock().unwrap();
+        if players.len() >= self.max_players && !players.contains_key(&player.uuid) {
+            return Err(player);
+        }
+        Ok(players.insert(player.uuid, player))
+    }
+
+    /// Remove a player, if they are still registered by the connection with `connection_id`.
+    /// A connection replaced by a new login leaves without removing its replacement.
+    pub fn leave(&self, uuid: &Uuid, connection_id: u64) {
+        let mut players = self.players.lock().unwrap();
+        if players
+            .get(uuid)
+            .is_some_and(|player| player.handle.id() == connection_id)
+        {
+            players.remove(uuid);
+        }
+    }
+
+    /// The player count and sample for a status response
+    pub fn status_players(&self) -> StatusPlayers {
+        let players = self.players.lock().unwrap();
+        StatusPlayers {
+            max: self.max_players as i32,
+            online: players.len() as i32,
+            sample: players
+                .values()
+                .take(STATUS_SAMPLE_SIZE)
+                .map(|player| StatusPlayerSample {
+                    name: player.name.clone(),
+                    id: player.uuid.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+impl Default for PlayerRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PLAYERS)
+    }
+}
@@ -0,0 +1,165 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use rand::{RngCore, SeedableRng};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::{sleep_until, timeout, Instant},
+};
+
+use crate::{
+    auth::Authenticator,
+    capture::{Capture, Direction},
+    connection::Connection,
+    crypto::KeyPair,
+    protocol::{ConnectionState, EncryptionRequest, EncryptionResponse, KeepAlive, SetCompression},
+    stream::{Packet, PacketStream},
+};
+
+/// How long to wait for each recorded clientbound packet before giving up
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const CLIENTBOUND_KEEP_ALIVE: i32 = 0x23;
+const SERVERBOUND_KEEP_ALIVE: i32 = 0x12;
+
+/// Acts as the client of a capture, sending its serverbound packets to a server and comparing
+/// the responses to the recorded clientbound packets.
+///
+/// The recorded Encryption Response can't be reused because it was encrypted for the original
+/// server's key, so a new shared secret is sent in response to the live Encryption Request
+/// instead. Everything after it is sent with the new secret. Keep Alive responses are likewise
+/// sent with the ID of the last Keep Alive the live server sent.
+pub struct Replay {
+    capture: Capture,
+    realtime: bool,
+}
+impl Replay {
+    pub fn new(capture: Capture) -> Replay {
+        Replay {
+            capture,
+            realtime: false,
+        }
+    }
+
+    /// Wait between packets as long as in the original session, instead of sending them at once
+    pub fn realtime(mut self, realtime: bool) -> Replay {
+        self.realtime = realtime;
+        self
+    }
+
+    /// Replay against an in-process [`Connection`] using `key_pair`, which checks the login with
+    /// `authenticator`
+    pub async fn replay_through_connection(
+        &self,
+        key_pair: Arc<KeyPair>,
+        authenticator: Arc<dyn Authenticator>,
+    ) -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut connection = Connection::new(stream, key_pair).await?;
+            connection.authenticate_with(authenticator);
+            connection.handle().await
+        });
+
+        self.replay_to(address).await?;
+        if let Err(e) = server.await? {
+            println!("Connection ended with: {e}");
+        }
+        Ok(())
+    }
+
+    pub async fn replay_to(&self, address: SocketAddr) -> Result<()> {
+        let mut stream = PacketStream::new(TcpStream::connect(address).await?)?;
+        let start = Instant::now();
+        let mut encryption_request = None;
+        let mut keep_alive = None;
+
+        for record in &self.capture.records {
+            match record.direction {
+                Direction::Serverbound => {
+                    if self.realtime {
+                        sleep_until(start + record.timestamp).await;
+                    }
+
+                    if record.state == ConnectionState::Login && record.packet.id == 0x01 {
+                        let request: EncryptionRequest = encryption_request
+                            .take()
+                            .ok_or(anyhow!("Encryption Response before Encryption Request"))?;
+                        let mut shared_secret = [0; 16];
+                        rand::rngs::StdRng::from_entropy().fill_bytes(&mut shared_secret);
+
+                        let response = EncryptionResponse::encrypt(&request, &shared_secret)?;
+                        println!("> {response:?} (replaced)");
+                        stream
+                            .write_packet(Packet {
+                                id: 0x01,
+                                data: response.into(),
+                            })
+                            .await?;
+                        stream.enable_encryption(&shared_secret);
+                    } else if record.state == ConnectionState::Play
+                        && record.packet.id == SERVERBOUND_KEEP_ALIVE
+                    {
+                        let response = KeepAlive {
+                            id: keep_alive
+                                .ok_or(anyhow!("Keep Alive response before Keep Alive"))?,
+                        };
+                        println!("> {response:?} (replaced)");
+                        stream
+                            .write_packet(Packet {
+                                id: SERVERBOUND_KEEP_ALIVE,
+                                data: response.into(),
+                            })
+                            .await?;
+                    } else {
+                        println!("> {:?}", record.packet);
+                        stream.write_packet(record.packet.clone()).await?;
+                    }
+                }
+                Direction::Clientbound => {
+                    let packet = match timeout(READ_TIMEOUT, stream.read_packet()).await {
+                        Ok(Ok(packet)) => packet,
+                        Ok(Err(e)) => {
+                            println!("Server closed the connection: {e}");
+                            return Ok(());
+                        }
+                        Err(_) => {
+                            println!("Server stopped responding, expected {:?}", record.packet);
+                            return Ok(());
+                        }
+                    };
+                    println!("< {packet:?}");
+                    if packet.id != record.packet.id {
+                        println!(
+                            "Diverged: expected packet {:#04x}, got {:#04x}",
+                            record.packet.id, packet.id
+                        );
+                    }
+
+                    if record.state == ConnectionState::Login {
+                        match packet.id {
+                            0x01 => {
+                                encryption_request = Some(EncryptionRequest::try_from(packet.data)?)
+                            }
+                            0x03 => {
+                                let set_compression = SetCompression::try_from(packet.data)?;
+                                stream.set_compression(
+                                    usize::try_from(set_compression.threshold).ok(),
+                                );
+                            }
+                            _ => {}
+                        }
+                    } else if record.state == ConnectionState::Play
+                        && packet.id == CLIENTBOUND_KEEP_ALIVE
+                    {
+                        keep_alive = Some(KeepAlive::try_from(packet.data)?.id);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+use std::{
+    fs,
+    future::Future,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::OwnedSemaphorePermit,
+    task, time,
+};
+
+use crate::{
+    access::AccessControl,
+    anvil::AnvilWorld,
+    auth::{Authenticator, MojangAuthenticator},
+    capture::CaptureWriter,
+    chunk::AIR,
+    chunk_manager::ViewConfig,
+    connection::{Connection, Timeouts},
+    crypto::{KeyPair, DEFAULT_KEY_SIZE},
+    generator::{Generator, VoidGenerator},
+    handler::{DefaultHandler, Handler},
+    keylog::KeyLog,
+    protocol::play::Position,
+    proxy::Proxy,
+    registry::{PlayerRegistry, DEFAULT_MAX_PLAYERS},
+    throttle::{Throttle, ThrottleConfig},
+    tick::Ticker,
+    world::{World, DEFAULT_AUTOSAVE_INTERVAL},
+};
+
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:25565";
+/// How long to wait after failing to accept a connection, so running out of file descriptors
+/// does not turn into a busy loop
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// Where the server's key pair comes from
+enum KeySource {
+    Generate(u32),
+    File(PathBuf, u32),
+    KeyPair(KeyPair),
+}
+
+/// Configures a [`Server`]. Everything has a default, so `ServerBuilder::new().build()` starts
+/// an online-mode server on port 25565 with a new key pair.
+pub struct ServerBuilder {
+    address: String,
+    key: KeySource,
+    handler: Arc<dyn Handler>,
+    authenticator: Arc<dyn Authenticator>,
+    timeouts: Timeouts,
+    throttle: ThrottleConfig,
+    view: ViewConfig,
+    access_dir: PathBuf,
+    whitelist: bool,
+    proxy: Option<Proxy>,
+    capture_dir: Option<PathBuf>,
+    key_log: Option<KeyLog>,
+    max_players: usize,
+    generator: Option<Arc<dyn Generator>>,
+    world_dir: Option<PathBuf>,
+    autosave_interval: Duration,
+}
+impl ServerBuilder {
+    pub fn new() -> Self {
+        ServerBuilder {
+            address: DEFAULT_ADDRESS.to_string(),
+            key: KeySource::Generate(DEFAULT_KEY_SIZE),
+            handler: Arc::new(DefaultHandler),
+            authenticator: Arc::new(MojangAuthenticator::default()),
+            timeouts: Timeouts::default(),
+            throttle: ThrottleConfig::default(),
+            view: ViewConfig::default(),
+            access_dir: PathBuf::from("."),
+            whitelist: false,
+            proxy: None,
+            capture_dir: None,
+            key_log: None,
+            max_players: DEFAULT_MAX_PLAYERS,
+            generator: None,
+            world_dir: None,
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+        }
+    }
+
+    pub fn address(mut self, address: &str) -> Self {
+        self.address = address.to_string();
+        self
+    }
+
+    pub fn key_pair(mut self, key_pair: KeyPair) -> Self {
+        self.key = KeySource::KeyPair(key_pair);
+        self
+    }
+
+    /// Load the key pair from `path`, generating and saving a new one of `bits` if it is missing
+    pub fn key_file(mut self, path: impl Into<PathBuf>, bits: u32) -> Self {
+        self.key = KeySource::File(path.into(), bits);
+        self
+    }
+
+    pub fn handler(mut self, handler: impl Handler + 'static) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    pub fn authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Arc::new(authenticator);
+        self
+    }
+
+    pub fn max_players(mut self, max_players: usize) -> Self {
+        self.max_players = max_players;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// How far and how fast chunks are sent around players
+    pub fn view(mut self, view: ViewConfig) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Directory of the ban lists and whitelist
+    pub fn access_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.access_dir = dir.into();
+        self
+    }
+
+    /// Only let players in `whitelist.json` join
+    pub fn whitelist(mut self, enabled: bool) -> Self {
+        self.whitelist = enabled;
+        self
+    }
+
+    /// Relay logged-in players to an upstream server instead of handling them. They never join
+    /// the server's own world, so none is loaded or ticked.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Record every connection to a capture file in `dir`
+    pub fn capture_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.capture_dir = Some(dir.into());
+        self
+    }
+
+    /// Export the shared secret of every connection
+    pub fn key_log(mut self, key_log: KeyLog) -> Self {
+        self.key_log = Some(key_log);
+        self
+    }
+
+    /// Put players in a world made by `generator`, like
+    /// [`FlatGenerator`](crate::generator::FlatGenerator)
+    pub fn generator(mut self, generator: impl Generator + 'static) -> Self {
+        self.generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Load the world from the vanilla world folder `dir` and save changes to it. Chunks that
+    /// were never saved come from the generator, or are empty without one.
+    pub fn world_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.world_dir = Some(dir.into());
+        self
+    }
+
+    /// How often changes to the world are saved, besides when the server stops
+    pub fn autosave_interval(mut self, interval: Duration) -> Self {
+        self.autosave_interval = interval;
+        self
+    }
+
+    /// Load everything the server needs and bind its listener
+    pub async fn build(self) -> Result<Server> {
+        let key_pair = match self.key {
+            KeySource::Generate(bits) => KeyPair::generate(bits)?,
+            KeySource::File(path, bits) => {
+                let key_pair = KeyPair::load_or_generate(&path, bits)?;
+                println!(
+                    "Loaded {}-bit key pair from {}",
+                    key_pair.bits(),
+                    path.display()
+                );
+                key_pair
+            }
+            KeySource::KeyPair(key_pair) => key_pair,
+        };
+        if let Some(capture_dir) = &self.capture_dir {
+            fs::create_dir_all(capture_dir)?;
+        }
+        let access = AccessControl::load(&self.access_dir)?;
+        access.set_whitelist_enabled(self.whitelist);
+
+        let world = match (self.generator, self.world_dir) {
+            _ if self.proxy.is_some() => None,
+            (None, None) => None,
+            (generator, world_dir) => {
+                let generator = generator.unwrap_or_else(|| {
+                    // Without a platform, since the world folder is expected to have the spawn
+                    Arc::new(VoidGenerator::new(Position { x: 0, y: 64, z: 0 }, -1, AIR))
+                });
+                let mut world = World::new(generator);
+                if let Some(dir) = world_dir {
+                    world = world.storage(AnvilWorld::open(dir)?);
+                }
+                Some(Arc::new(world))
+            }
+        };
+
+        let listener = TcpListener::bind(&self.address).await?;
+
+        Ok(Server {
+            listener,
+            shared: Arc::new(Shared {
+                key_pair: Arc::new(key_pair),
+                handler: self.handler,
+                authenticator: self.authenticator,
+                timeouts: self.timeouts,
+                view: self.view,
+                throttle: Arc::new(Throttle::new(self.throttle)),
+                access: Arc::new(access),
+                proxy: self.proxy,
+                capture_dir: self.capture_dir,
+                key_log: self.key_log,
+                registry: Arc::new(PlayerRegistry::new(self.max_players)),
+                world,
+                ticker: Arc::new(Ticker::new()),
+            }),
+            autosave_interval: self.autosave_interval,
+        })
+    }
+}
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What every connection of a server uses
+struct Shared {
+    key_pair: Arc<KeyPair>,
+    handler: Arc<dyn Handler>,
+    authenticator: Arc<dyn Authenticator>,
+    timeouts: Timeouts,
+    view: ViewConfig,
+    throttle: Arc<Throttle>,
+    access: Arc<AccessControl>,
+    proxy: Option<Proxy>,
+    capture_dir: Option<PathBuf>,
+    key_log: Option<KeyLog>,
+    registry: Arc<PlayerRegistry>,
+    world: Option<Arc<World>>,
+    ticker: Arc<Ticker>,
+}
+impl Shared {
+    /// Handle a connection admitted by the throttle. The `permit` is held until the connection
+    /// ends, so proxied connections keep their slot.
+    async fn serve(
+        &self,
+        stream: TcpStream,
+        address: SocketAddr,
+        (_permit, full): (OwnedSemaphorePermit, bool),
+    ) -> Result<()> {
+        let mut connection = Connection::new(stream, self.key_pair.clone()).await?;
+        if full {
+            connection.reject_as_full();
+        }
+        connection.throttle_with(self.throttle.clone());
+        connection.check_access_with(self.access.clone());
+        connection.set_timeouts(self.timeouts);
+        connection.set_view(self.view);
+        connection.handle_with(self.handler.clone());
+        connection.authenticate_with(self.authenticator.clone());
+        connection.register_with(self.registry.clone());
+        if let Some(world) = &self.world {
+            connection.join_with(world.clone());
+        }
+        connection.tick_with(self.ticker.clone());
+        if let Some(capture_dir) = &self.capture_dir {
+            let started = SystemTime::now().duration_since(UNIX_EPOCH)?;
+            let name = format!(
+                "{}-{}-{}.mccap",
+                started.as_secs(),
+                connection.id(),
+                address
+            )
+            .replace(':', "_");
+            connection.record_to(CaptureWriter::create(capture_dir.join(name))?);
+        }
+        if let Some(key_log) = &self.key_log {
+            connection.log_keys_to(key_log.clone());
+        }
+
+        match &self.proxy {
+            Some(proxy) => proxy.handle(connection).await,
+            None => connection.handle().await,
+        }
+    }
+}
+
+/// A Minecraft server, accepting connections until it is dropped
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+    autosave_interval: Duration,
+}
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+
+    /// The players online on this server
+    pub fn registry(&self) -> Arc<PlayerRegistry> {
+        self.shared.registry.clone()
+    }
+
+    /// Runs the server's ticks once it is running, and schedules tasks on them
+    pub fn ticker(&self) -> Arc<Ticker> {
+        self.shared.ticker.clone()
+    }
+
+    /// The world players join, if the server has one
+    pub fn world(&self) -> Option<Arc<World>> {
+        self.shared.world.clone()
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept connections, handling each in its own task
+    pub async fn run(self) -> Result<()> {
+        self.run_until(std::future::pending::<()>()).await
+    }
+
+    /// Accept connections until `shutdown` completes, then save the world. The world is also
+    /// saved every autosave interval in the meantime.
+    pub async fn run_until(self, shutdown: impl Future) -> Result<()> {
+        println!("Listening on {}...", self.local_addr()?);
+
+        let shared = self.shared.clone();
+        let ticker = tokio::spawn(async move {
+            let world = shared.world.clone();
+            shared.ticker.run(world, shared.registry.clone()).await
+        });
+
+        let mut autosave = time::interval(self.autosave_interval);
+        // The first tick completes right away, and there is nothing to save yet
+        autosave.tick().await;
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    // Errors like running out of file descriptors or a client giving up before
+                    // it was accepted pass, so they don't stop the server
+                    let (stream, address) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            println!("Failed to accept a connection: {err}");
+                            time::sleep(ACCEPT_ERROR_DELAY).await;
+                            continue;
+                        }
+                    };
+                    // Dropping the stream closes it before anything is spent on it
+                    let Some(admitted) = self.shared.throttle.admit() else {
+                        println!("Closed connection from {address}: too many connections");
+                        continue;
+                    };
+                    println!("New connection from {address}");
+                    let shared = self.shared.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = shared.serve(stream, address, admitted).await {
+                            println!("Connection from {address} closed: {err}");
+                        }
+                    });
+                }
+                _ = autosave.tick() => {
+                    if let Err(err) = self.save().await {
+                        println!("Failed to save the world: {err}");
+                    }
+                }
+                _ = &mut shutdown => break,
+            }
+        }
+
+        println!("Stopping the server...");
+        ticker.abort();
+        self.save().await
+    }
+
+    /// Save the changes to the world, if it has somewhere to save them
+    pub async fn save(&self) -> Result<()> {
+        let Some(world) = self.world() else {
+            return Ok(());
+        };
+        let saved = task::spawn_blocking(move || world.save()).await??;
+        if saved > 0 {
+            println!("Saved {saved} chunks");
+        }
+        Ok(())
+    }
+}
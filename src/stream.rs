@@ -0,0 +1,178 @@
+use std::{
+    io::{self, ErrorKind},
+    net::SocketAddr,
+};
+
+use aes::Aes128;
+use anyhow::Result;
+use bytes::Bytes;
+use cfb8::Cfb8;
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::{
+    codec::MinecraftCodec,
+    protocol::{varint_len, Reader, Writer},
+};
+
+pub type AesCfb8 = Cfb8<Aes128>;
+
+/// A packet ID with its data. The data shares the buffer it was read from, so cloning and
+/// slicing it is cheap.
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub id: i32,
+    pub data: Bytes,
+}
+impl TryFrom<Bytes> for Packet {
+    type Error = anyhow::Error;
+
+    fn try_from(data: Bytes) -> std::result::Result<Self, Self::Error> {
+        let mut cursor = Reader::new(data);
+        let id = cursor.read_varint()?;
+        Ok(Packet {
+            id,
+            data: cursor.get_leftover_bytes(),
+        })
+    }
+}
+impl From<Packet> for Bytes {
+    fn from(packet: Packet) -> Self {
+        let mut buffer = Writer::with_capacity(varint_len(packet.id) + packet.data.len());
+        buffer.write_varint(packet.id);
+        buffer.write_raw(&packet.data);
+        buffer.into()
+    }
+}
+
+/// Reading half of a [`PacketStream`], with its own decryption and compression state
+pub struct PacketReader<R = OwnedReadHalf> {
+    framed: FramedRead<R, MinecraftCodec>,
+}
+impl<R: AsyncRead + Unpin> PacketReader<R> {
+    pub fn new(reader: R) -> Self {
+        PacketReader {
+            framed: FramedRead::new(reader, MinecraftCodec::new()),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.framed.get_ref()
+    }
+
+    /// Bytes read from the underlying reader that are not part of a returned packet yet
+    pub fn buffered(&self) -> usize {
+        self.framed.read_buffer().len()
+    }
+
+    pub fn codec_mut(&mut self) -> &mut MinecraftCodec {
+        self.framed.decoder_mut()
+    }
+
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.codec_mut().enable_encryption(shared_secret);
+    }
+
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.codec_mut().set_compression(threshold);
+    }
+
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.codec_mut().set_max_packet_size(max_packet_size);
+    }
+
+    pub async fn read_packet(&mut self) -> Result<Packet> {
+        match self.framed.next().await {
+            Some(packet) => packet,
+            None => Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+        }
+    }
+}
+
+/// Writing half of a [`PacketStream`], with its own encryption and compression state
+pub struct PacketWriter<W = OwnedWriteHalf> {
+    framed: FramedWrite<W, MinecraftCodec>,
+}
+impl<W: AsyncWrite + Unpin> PacketWriter<W> {
+    pub fn new(writer: W) -> Self {
+        PacketWriter {
+            framed: FramedWrite::new(writer, MinecraftCodec::new()),
+        }
+    }
+
+    pub fn codec_mut(&mut self) -> &mut MinecraftCodec {
+        self.framed.encoder_mut()
+    }
+
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.codec_mut().enable_encryption(shared_secret);
+    }
+
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.codec_mut().set_compression(threshold);
+    }
+
+    pub async fn write_packet(&mut self, packet: Packet) -> Result<()> {
+        self.framed.send(packet).await
+    }
+}
+
+/// Length-prefixed packets over a TCP stream, used by both sides of the protocol
+pub struct PacketStream {
+    reader: PacketReader,
+    writer: PacketWriter,
+    peer_addr: SocketAddr,
+}
+impl PacketStream {
+    pub fn new(stream: TcpStream) -> Result<Self> {
+        let peer_addr = stream.peer_addr()?;
+        let (reader, writer) = stream.into_split();
+
+        Ok(PacketStream {
+            reader: PacketReader::new(reader),
+            writer: PacketWriter::new(writer),
+            peer_addr,
+        })
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Encrypt everything sent and received from now on
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        self.reader.enable_encryption(shared_secret);
+        self.writer.enable_encryption(shared_secret);
+    }
+
+    /// Compress packets of at least `threshold` bytes, or disable compression with `None`
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.reader.set_compression(threshold);
+        self.writer.set_compression(threshold);
+    }
+
+    /// Limit the size of packets read from now on
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.reader.set_max_packet_size(max_packet_size);
+    }
+
+    pub async fn read_packet(&mut self) -> Result<Packet> {
+        self.reader.read_packet().await
+    }
+
+    pub async fn write_packet(&mut self, packet: Packet) -> Result<()> {
+        self.writer.write_packet(packet).await
+    }
+
+    /// Split into halves that can be used from separate tasks
+    pub fn into_split(self) -> (PacketReader, PacketWriter) {
+        (self.reader, self.writer)
+    }
+}
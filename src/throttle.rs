@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+pub const THROTTLED_MESSAGE: &str = "Connection throttled! Please wait before reconnecting.";
+pub const SERVER_FULL_MESSAGE: &str = "The server is full!";
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleConfig {
+    /// Minimum time between two logins from the same IP, like `connection-throttle` in
+    /// `bukkit.yml`
+    pub login_interval: Duration,
+    /// Maximum number of connections open at once
+    pub max_connections: usize,
+    /// Connections kept open over `max_connections` to tell them the server is full. Any more
+    /// are closed right after they are accepted.
+    pub max_rejected_connections: usize,
+    /// Number of status requests each IP may make per `status_window`
+    pub status_budget: u32,
+    pub status_window: Duration,
+}
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        ThrottleConfig {
+            login_interval: Duration::from_secs(4),
+            max_connections: 256,
+            max_rejected_connections: 16,
+            status_budget: 10,
+            status_window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Limits shared by all connections of a server. Connections over a limit are still
+/// handshaked, so they can be told why they are turned away, up to a few at a time.
+pub struct Throttle {
+    config: ThrottleConfig,
+    connections: Arc<Semaphore>,
+    rejected: Arc<Semaphore>,
+    /// Last login attempt of every IP
+    logins: Mutex<HashMap<IpAddr, Instant>>,
+    /// Start of the current window and the number of status requests in it, for every IP
+    statuses: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Throttle {
+        Throttle {
+            config,
+            connections: Arc::new(Semaphore::new(config.max_connections)),
+            rejected: Arc::new(Semaphore::new(config.max_rejected_connections)),
+            logins: Mutex::new(HashMap::new()),
+            statuses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// Take a slot for a new connection, which is freed when the permit is dropped. Returns
+    /// `None` if the server already has the maximum number of connections.
+    pub fn try_connect(&self) -> Option<OwnedSemaphorePermit> {
+        self.connections.clone().try_acquire_owned().ok()
+    }
+
+    /// Take a slot for a new connection, or else one of the few slots for connections that are
+    /// only told the server is full, in which case the `bool` is `true`. Returns `None` if both
+    /// are taken and the connection should be closed.
+    pub fn admit(&self) -> Option<(OwnedSemaphorePermit, bool)> {
+        if let Some(permit) = self.try_connect() {
+            return Some((permit, false));
+        }
+        let permit = self.rejected.clone().try_acquire_owned().ok()?;
+        Some((permit, true))
+    }
+
+    /// Record a login attempt from `ip`, returning whether it is allowed
+    pub fn allow_login(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let interval = self.config.login_interval;
+        let mut logins = self.logins.lock().unwrap();
+        logins.retain(|_, last| now.duration_since(*last) < interval);
+
+        // Attempts while throttled do not extend the wait
+        match logins.get(&ip) {
+            Some(_) => false,
+            None => {
+                logins.insert(ip, now);
+                true
+            }
+        }
+    }
+
+    /// Record a status request from `ip`, returning whether it is within the budget
+    pub fn allow_status(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let window = self.config.status_window;
+        let mut statuses = self.statuses.lock().unwrap();
+        statuses.retain(|_, (start, _)| now.duration_since(*start) < window);
+
+        let (_, count) = statuses.entry(ip).or_insert((now, 0));
+        *count += 1;
+        *count <= self.config.status_budget
+    }
+}
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(ThrottleConfig::default())
+    }
+}
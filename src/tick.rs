@@ -0,0 +1,253 @@
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    sync::watch,
+    time::{self, Instant},
+};
+
+use crate::{
+    protocol::play::clientbound::{ClientboundPlayPacket, UpdateTime},
+    registry::PlayerRegistry,
+    world::World,
+};
+
+pub const TICKS_PER_SECOND: u64 = 20;
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND);
+/// Ticks the loop may fall behind by before it skips them instead of catching up, like vanilla
+const MAX_TICKS_BEHIND: u32 = 40;
+/// Ticks the statistics are averaged over
+const STATS_TICKS: usize = 100;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Lets the owner of a scheduled task cancel it
+#[derive(Debug, Clone, Default)]
+pub struct TaskHandle {
+    cancelled: Arc<AtomicBool>,
+}
+impl TaskHandle {
+    /// Stop the task from running again. A task cancelling itself finishes its current run.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+struct ScheduledTask {
+    /// Tick to run on next
+    due: u64,
+    /// Ticks between runs of a repeating task
+    period: Option<u64>,
+    task: Box<dyn FnMut() + Send>,
+    handle: TaskHandle,
+}
+
+/// How long recent ticks took, like vanilla's `/debug` and Paper's `/tps`
+#[derive(Debug, Clone, Default)]
+pub struct TickStats {
+    /// Start and duration of the last [`STATS_TICKS`] ticks
+    ticks: VecDeque<(Instant, Duration)>,
+}
+impl TickStats {
+    fn record(&mut self, start: Instant, duration: Duration) {
+        if self.ticks.len() == STATS_TICKS {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back((start, duration));
+    }
+
+    /// Average milliseconds per tick
+    pub fn mspt(&self) -> f64 {
+        if self.ticks.is_empty() {
+            return 0.0;
+        }
+        let total = self
+            .ticks
+            .iter()
+            .map(|(_, duration)| *duration)
+            .sum::<Duration>();
+        total.as_secs_f64() * 1000.0 / self.ticks.len() as f64
+    }
+
+    /// Ticks per second, which is below 20 when ticks take longer than [`TICK_DURATION`]
+    pub fn tps(&self) -> f64 {
+        let (Some((first, _)), Some((last, _))) = (self.ticks.front(), self.ticks.back()) else {
+            return TICKS_PER_SECOND as f64;
+        };
+        let elapsed = last.duration_since(*first).as_secs_f64();
+        if elapsed == 0.0 {
+            return TICKS_PER_SECOND as f64;
+        }
+        ((self.ticks.len() - 1) as f64 / elapsed).min(TICKS_PER_SECOND as f64)
+    }
+}
+
+/// Runs the server 20 times per second. Every tick runs the input queued by connections and
+/// the tasks that are due, and advances the world time. It then wakes the connections, which
+/// handle the play packets their queued input handed back and send the next chunks. Other
+/// packets are sent as soon as they are written, not on ticks.
+///
+/// Queued input and tasks run on the tick loop one after the other, so they should be quick.
+pub struct Ticker {
+    tick: watch::Sender<u64>,
+    queue: Mutex<Vec<Job>>,
+    tasks: Mutex<Vec<ScheduledTask>>,
+    stats: Mutex<TickStats>,
+}
+impl Ticker {
+    pub fn new() -> Ticker {
+        Ticker {
+            tick: watch::channel(0).0,
+            queue: Mutex::new(Vec::new()),
+            tasks: Mutex::new(Vec::new()),
+            stats: Mutex::new(TickStats::default()),
+        }
+    }
+
+    /// Number of ticks since the loop started
+    pub fn current_tick(&self) -> u64 {
+        *self.tick.borrow()
+    }
+
+    /// Notified at the end of every tick with its number
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.tick.subscribe()
+    }
+
+    pub fn stats(&self) -> TickStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Run `input` at the start of the next tick, in the order it was queued
+    pub fn queue(&self, input: impl FnOnce() + Send + 'static) {
+        self.queue.lock().unwrap().push(Box::new(input));
+    }
+
+    /// Run `task` once, `delay` ticks from now
+    pub fn schedule(&self, delay: u64, task: impl FnOnce() + Send + 'static) -> TaskHandle {
+        let mut task = Some(task);
+        self.add_task(delay, None, move || {
+            if let Some(task) = task.take() {
+                task()
+            }
+        })
+    }
+
+    /// Run `task` every `period` ticks, the first time `delay` ticks from now
+    pub fn schedule_repeating(
+        &self,
+        delay: u64,
+        period: u64,
+        task: impl FnMut() + Send + 'static,
+    ) -> TaskHandle {
+        self.add_task(delay, Some(period.max(1)), task)
+    }
+
+    fn add_task(
+        &self,
+        delay: u64,
+        period: Option<u64>,
+        task: impl FnMut() + Send + 'static,
+    ) -> TaskHandle {
+        let handle = TaskHandle::default();
+        self.tasks.lock().unwrap().push(ScheduledTask {
+            // Tasks scheduled without a delay run on the next tick
+            due: self.current_tick() + delay.max(1),
+            period,
+            task: Box::new(task),
+            handle: handle.clone(),
+        });
+        handle
+    }
+
+    /// Tick until the task is dropped, with the world time sent to every player in `registry`
+    /// once a second
+    pub async fn run(&self, world: Option<Arc<World>>, registry: Arc<PlayerRegistry>) {
+        let mut next = Instant::now();
+        loop {
+            time::sleep_until(next).await;
+            let start = Instant::now();
+            self.tick(world.as_deref(), &registry);
+            self.stats.lock().unwrap().record(start, start.elapsed());
+
+            next += TICK_DURATION;
+            let behind = Instant::now().saturating_duration_since(next);
+            if behind > TICK_DURATION * MAX_TICKS_BEHIND {
+                let skipped = behind.as_millis() / TICK_DURATION.as_millis();
+                println!(
+                    "Can't keep up! Running {}ms or {skipped} ticks behind",
+                    behind.as_millis()
+                );
+                next = Instant::now();
+            }
+        }
+    }
+
+    fn tick(&self, world: Option<&World>, registry: &PlayerRegistry) {
+        let tick = self.current_tick() + 1;
+
+        let queue = mem::take(&mut *self.queue.lock().unwrap());
+        for input in queue {
+            input();
+        }
+
+        // Tasks run without the lock held, so they can schedule more
+        let due = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.retain(|task| !task.handle.is_cancelled());
+            let (due, waiting) = mem::take(&mut *tasks)
+                .into_iter()
+                .partition::<Vec<_>, _>(|task| task.due <= tick);
+            *tasks = waiting;
+            due
+        };
+        for mut task in due {
+            // Tasks that ran before may have cancelled it
+            if task.handle.is_cancelled() {
+                continue;
+            }
+            (task.task)();
+            if let Some(period) = task.period {
+                if !task.handle.is_cancelled() {
+                    task.due = tick + period;
+                    self.tasks.lock().unwrap().push(task);
+                }
+            }
+        }
+
+        if let Some(world) = world {
+            world.tick();
+            if tick.is_multiple_of(TICKS_PER_SECOND) {
+                let (world_age, time_of_day) = world.time();
+                for player in registry.players() {
+                    let update_time = UpdateTime {
+                        world_age,
+                        time_of_day,
+                    };
+                    // Players that just left are skipped
+                    player
+                        .handle
+                        .send_play(ClientboundPlayPacket::UpdateTime(update_time));
+                }
+            }
+        }
+
+        self.tick.send_replace(tick);
+    }
+}
+impl Default for Ticker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
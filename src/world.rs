@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+
+use crate::{anvil::AnvilWorld, chunk::Chunk, generator::Generator, protocol::play::Position};
+
+/// How often a world is saved while the server runs, like vanilla's autosave every 6000 ticks
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The world players are in. Chunks come from its region files when they were saved there, and
+/// from the generator otherwise. Only chunks that were changed are kept in memory, until they
+/// are saved.
+pub struct World {
+    generator: Arc<dyn Generator>,
+    storage: Option<AnvilWorld>,
+    /// Changed chunks by their coordinates, and whether they changed since they were last
+    /// saved. They stay here while they are being saved, so nothing reads the old ones.
+    changed: Mutex<HashMap<(i32, i32), (Chunk, bool)>>,
+    /// Ticks since the world was created
+    age: AtomicI64,
+    /// Ticks since the first sunrise, of which a day has 24000
+    time_of_day: AtomicI64,
+}
+impl World {
+    pub fn new(generator: Arc<dyn Generator>) -> World {
+        World {
+            generator,
+            storage: None,
+            changed: Mutex::new(HashMap::new()),
+            age: 0.into(),
+            time_of_day: 0.into(),
+        }
+    }
+
+    /// Load and save chunks in `storage`
+    pub fn storage(mut self, storage: AnvilWorld) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Block the player spawns on top of, from `level.dat` if there is one
+    pub fn spawn_point(&self) -> Position {
+        self.storage
+            .as_ref()
+            .and_then(AnvilWorld::spawn_point)
+            .unwrap_or_else(|| self.generator.spawn_point())
+    }
+
+    /// Age of the world and time of day, in ticks
+    pub fn time(&self) -> (i64, i64) {
+        (
+            self.age.load(Ordering::Relaxed),
+            self.time_of_day.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn set_time_of_day(&self, time_of_day: i64) {
+        self.time_of_day.store(time_of_day, Ordering::Relaxed);
+    }
+
+    /// Advance the time by a tick
+    pub fn tick(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+        self.time_of_day.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Load or generate a chunk, ignoring any changes to it
+    fn load(&self, x: i32, z: i32) -> Chunk {
+        if let Some(storage) = &self.storage {
+            match storage.load_chunk(x, z) {
+                Ok(Some(chunk)) => return chunk,
+                Ok(None) => {}
+                Err(error) => println!("Failed to load chunk {x}, {z}: {error}"),
+            }
+        }
+        self.generator.generate(x, z)
+    }
+
+    /// The chunk at chunk coordinates, as it is now
+    pub fn chunk(&self, x: i32, z: i32) -> Chunk {
+        if let Some((chunk, _)) = self.changed.lock().unwrap().get(&(x, z)) {
+            return chunk.clone();
+        }
+        self.load(x, z)
+    }
+
+    /// Change a chunk, which is kept until the next save
+    pub fn modify_chunk<T>(&self, x: i32, z: i32, modify: impl FnOnce(&mut Chunk) -> T) -> T {
+        let mut changed = self.changed.lock().unwrap();
+        let (chunk, unsaved) = changed
+            .entry((x, z))
+            .or_insert_with(|| (self.load(x, z), true));
+        *unsaved = true;
+        modify(chunk)
+    }
+
+    /// Set a block state, returning the previous one
+    pub fn set_block(&self, position: Position, state: u32) -> u32 {
+        let (x, z) = (position.x.rem_euclid(16), position.z.rem_euclid(16));
+        self.modify_chunk(position.x >> 4, position.z >> 4, |chunk| {
+            chunk.set_block(x as usize, position.y, z as usize, state)
+        })
+    }
+
+    pub fn get_block(&self, position: Position) -> u32 {
+        let (x, z) = (position.x.rem_euclid(16), position.z.rem_euclid(16));
+        self.chunk(position.x >> 4, position.z >> 4)
+            .get_block(x as usize, position.y, z as usize)
+    }
+
+    /// Write the changed chunks to the region files, returning how many there were. Without
+    /// storage, changes stay in memory and are lost when the server stops.
+    pub fn save(&self) -> Result<usize> {
+        let Some(storage) = &self.storage else {
+            return Ok(0);
+        };
+        let chunks = {
+            let mut changed = self.changed.lock().unwrap();
+            changed
+                .values_mut()
+                .filter(|(_, unsaved)| *unsaved)
+                .map(|(chunk, unsaved)| {
+                    *unsaved = false;
+                    chunk.clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        if chunks.is_empty() {
+            return Ok(0);
+        }
+        let result = storage.save_chunks(&chunks);
+
+        let mut changed = self.changed.lock().unwrap();
+        match result {
+            // Chunks that did not change again are in the region files now
+            Ok(()) => changed.retain(|_, (_, unsaved)| *unsaved),
+            Err(_) => {
+                for chunk in &chunks {
+                    if let Some((_, unsaved)) = changed.get_mut(&(chunk.x, chunk.z)) {
+                        *unsaved = true;
+                    }
+                }
+            }
+        }
+        result.map(|()| chunks.len())
+    }
+}
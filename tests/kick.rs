@@ -0,0 +1,67 @@
+use std::{sync::Arc, time::Duration};
+
+use minecraft_protocol::{
+    auth::OfflineAuthenticator,
+    client::{Account, Client},
+    connection::Connection,
+    crypto::KeyPair,
+    registry::{PlayerRegistry, DUPLICATE_LOGIN_MESSAGE},
+};
+use tokio::{net::TcpListener, task::JoinHandle, time};
+
+/// Accept a connection on `listener` and handle it until it ends
+fn serve(
+    listener: Arc<TcpListener>,
+    key_pair: Arc<KeyPair>,
+    registry: Arc<PlayerRegistry>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut connection = Connection::new(stream, key_pair).await.unwrap();
+        connection.authenticate_with(Arc::new(OfflineAuthenticator));
+        connection.register_with(registry);
+        let _ = connection.handle().await;
+    })
+}
+
+#[tokio::test]
+async fn kicking_through_a_handle_ends_the_connection() {
+    let listener = Arc::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    let port = listener.local_addr().unwrap().port();
+    let key_pair = Arc::new(KeyPair::generate(1024).unwrap());
+    let registry = Arc::new(PlayerRegistry::new(10));
+
+    let connection = serve(listener.clone(), key_pair.clone(), registry.clone());
+    let mut client = Client::connect("127.0.0.1", port).await.unwrap();
+    client.login(&Account::offline("kicked")).await.unwrap();
+
+    let player = registry.players().pop().unwrap();
+    assert!(player.handle.kick("bye"));
+    time::timeout(Duration::from_secs(3), connection)
+        .await
+        .expect("Connection is still handled after the kick")
+        .unwrap();
+    assert!(registry.players().is_empty());
+}
+
+#[tokio::test]
+async fn logging_in_again_ends_the_first_connection() {
+    let listener = Arc::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    let port = listener.local_addr().unwrap().port();
+    let key_pair = Arc::new(KeyPair::generate(1024).unwrap());
+    let registry = Arc::new(PlayerRegistry::new(10));
+
+    let first = serve(listener.clone(), key_pair.clone(), registry.clone());
+    let mut client = Client::connect("127.0.0.1", port).await.unwrap();
+    client.login(&Account::offline("twice")).await.unwrap();
+
+    let _second = serve(listener, key_pair, registry.clone());
+    let mut again = Client::connect("127.0.0.1", port).await.unwrap();
+    again.login(&Account::offline("twice")).await.unwrap();
+
+    time::timeout(Duration::from_secs(3), first)
+        .await
+        .unwrap_or_else(|_| panic!("First connection was not ended by {DUPLICATE_LOGIN_MESSAGE:?}"))
+        .unwrap();
+    assert_eq!(registry.players().len(), 1);
+}
@@ -0,0 +1,129 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use minecraft_protocol::{
+    auth::Authenticator,
+    capture::{Capture, CaptureRecord, Direction},
+    connection::{Connection, Timeouts},
+    crypto::KeyPair,
+    player::offline_uuid,
+    protocol::{
+        ConnectionState, EncryptionRequest, EncryptionResponse, Handshake, KeepAlive, LoginStart,
+        LoginSuccess,
+    },
+    replay::Replay,
+    stream::Packet,
+};
+use tokio::net::TcpListener;
+
+/// Encrypts the connection like an online-mode server, but lets everyone in
+#[derive(Default)]
+struct Trusting {
+    server_hashes: Mutex<Vec<String>>,
+}
+#[async_trait]
+impl Authenticator for Trusting {
+    async fn authenticate(&self, username: &str, server_hash: &str) -> Result<LoginSuccess> {
+        self.server_hashes
+            .lock()
+            .unwrap()
+            .push(server_hash.to_string());
+        Ok(LoginSuccess {
+            uuid: offline_uuid(username),
+            username: username.to_string(),
+            properties: Vec::new(),
+        })
+    }
+}
+
+fn record(direction: Direction, state: ConnectionState, id: i32, data: Bytes) -> CaptureRecord {
+    CaptureRecord {
+        timestamp: Duration::ZERO,
+        direction,
+        state,
+        packet: Packet { id, data },
+    }
+}
+
+/// An online login recorded against a server with another key, answering its first Keep Alive
+fn online_login() -> Capture {
+    use ConnectionState::*;
+    use Direction::*;
+
+    let recorded_key = KeyPair::generate(1024).unwrap();
+    let request = EncryptionRequest::new(recorded_key.public_key_der().to_vec(), [1, 2, 3, 4]);
+    let response = EncryptionResponse::encrypt(&request, &[7; 16]).unwrap();
+    let handshake = Handshake {
+        protocol_version: 762,
+        server_address: "localhost".to_string(),
+        server_port: 25565,
+        next_state: Login,
+    };
+    let login_start = LoginStart {
+        username: "replayed".to_string(),
+        uuid: None,
+    };
+    let success = LoginSuccess {
+        uuid: offline_uuid("replayed"),
+        username: "replayed".to_string(),
+        properties: Vec::new(),
+    };
+
+    Capture {
+        started_at: 0,
+        records: vec![
+            record(
+                Serverbound,
+                Handshaking,
+                0x00,
+                handshake.try_into().unwrap(),
+            ),
+            record(Serverbound, Login, 0x00, login_start.into()),
+            record(Clientbound, Login, 0x01, request.try_into().unwrap()),
+            record(Serverbound, Login, 0x01, response.into()),
+            record(Clientbound, Login, 0x02, success.into()),
+            record(Clientbound, Play, 0x23, KeepAlive { id: 1 }.into()),
+            record(Serverbound, Play, 0x12, KeepAlive { id: 1 }.into()),
+        ],
+    }
+}
+
+#[tokio::test]
+async fn replays_an_online_login() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let authenticator = Arc::new(Trusting::default());
+
+    let server = tokio::spawn({
+        let authenticator = authenticator.clone();
+        async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let key_pair = Arc::new(KeyPair::generate(1024).unwrap());
+            let mut connection = Connection::new(stream, key_pair).await.unwrap();
+            connection.authenticate_with(authenticator);
+            connection.set_timeouts(Timeouts {
+                keep_alive_interval: Duration::from_secs(60),
+                ..Timeouts::default()
+            });
+            let _ = connection.handle().await;
+            connection.player().and_then(|player| player.latency)
+        }
+    });
+
+    Replay::new(online_login())
+        .replay_to(address)
+        .await
+        .unwrap();
+
+    // The server kicks players whose Keep Alive response has the wrong ID, before it measures
+    // their latency
+    assert!(server.await.unwrap().is_some());
+    let server_hashes = authenticator.server_hashes.lock().unwrap();
+    assert_eq!(server_hashes.len(), 1);
+    assert!(!server_hashes[0].is_empty());
+}